@@ -0,0 +1,45 @@
+//! Registers every backend crate the CLI depends on, so a new backend only
+//! needs to be added as a dependency. A backend crate is named
+//! `link-keeper-<name>-backend` and has a `register(&mut BackendRegistry)`.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const BACKEND_PREFIX: &str = "link-keeper-";
+const BACKEND_SUFFIX: &str = "-backend";
+
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.toml");
+
+    let manifest = fs::read_to_string("Cargo.toml").expect("the manifest of the CLI");
+    let manifest = manifest.parse::<toml::Value>().expect("a valid manifest");
+
+    let mut crates = manifest
+        .get("dependencies")
+        .and_then(|dependencies| dependencies.as_table())
+        .map(|dependencies| {
+            dependencies
+                .keys()
+                .filter(|name| name.starts_with(BACKEND_PREFIX) && name.ends_with(BACKEND_SUFFIX))
+                .map(|name| name.replace('-', "_"))
+                .collect::<Vec<String>>()
+        })
+        .unwrap_or_default();
+    crates.sort();
+
+    let calls = crates
+        .iter()
+        .map(|name| format!("    {}::register(registry);\n", name))
+        .collect::<String>();
+
+    let generated = format!(
+        "/// Register the backend crates the CLI depends on\n\
+         fn register_backends(registry: &mut BackendRegistry) {{\n{}}}\n",
+        calls
+    );
+
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    fs::write(Path::new(&out_dir).join("backends.rs"), generated)
+        .expect("the registration of the backends to be written");
+}
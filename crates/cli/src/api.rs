@@ -0,0 +1,358 @@
+//! The local REST API run by `link-keeper serve`, for browser extensions,
+//! launchers and editor plugins. It's described by `openapi.json`, served
+//! at `GET /openapi.json`.
+//!
+//! Every other request needs the header `Authorization: Bearer <token>`
+//! with the token kept in `api.token`, and the server only listens on the
+//! loopback interface. Links are sent in the shapes printed by `--json`,
+//! errors as `{"error": {"code": "not_found", "message": "..."}}`.
+//!
+//! Requests are handled one at a time, and the store is locked while one
+//! changes it, so the CLI running at the same time waits for it.
+
+use crate::output::{self, Exit};
+use link_keeper::query::Query;
+use link_keeper::search::Search;
+use link_keeper::{LinkError, LinkKeeper, ReadState};
+use serde_json::{json, Value};
+use std::io::{self, Read};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+pub const DEFAULT_PORT: &str = "7879";
+
+/// The setting the token is kept in, so integrations keep working
+pub const TOKEN_KEY: &str = "api.token";
+
+const OPENAPI: &str = include_str!("openapi.json");
+
+/// Request bodies are a link at most, anything larger is refused
+const MAX_BODY_LENGTH: u64 = 64 * 1024;
+
+/// Why a request failed, answered with the status and a JSON error
+#[derive(Debug)]
+struct ApiError {
+    status: u16,
+    code: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, code: &'static str, message: &str) -> Self {
+        ApiError {
+            status,
+            code,
+            message: message.to_owned(),
+        }
+    }
+
+    fn bad_request(message: &str) -> Self {
+        ApiError::new(400, "bad_request", message)
+    }
+}
+
+impl From<failure::Error> for ApiError {
+    fn from(err: failure::Error) -> Self {
+        match err.downcast_ref::<LinkError>() {
+            Some(LinkError::NotFound(_)) => {
+                ApiError::new(404, Exit::NotFound.name(), &err.to_string())
+            }
+            Some(LinkError::Ambiguous(_)) => {
+                ApiError::new(400, Exit::Ambiguous.name(), &err.to_string())
+            }
+            None => ApiError::new(500, Exit::Error.name(), &err.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for ApiError {
+    fn from(err: io::Error) -> Self {
+        ApiError::new(500, Exit::Error.name(), &err.to_string())
+    }
+}
+
+fn json_response(status: u16, value: &Value) -> Response<io::Cursor<Vec<u8>>> {
+    Response::from_string(value.to_string())
+        .with_status_code(status)
+        .with_header(
+            "Content-Type: application/json"
+                .parse::<Header>()
+                .expect("a valid header"),
+        )
+}
+
+fn authorized(request: &Request, token: &str) -> bool {
+    request
+        .headers()
+        .iter()
+        .filter(|header| header.field.equiv("Authorization"))
+        .any(|header| {
+            header
+                .value
+                .as_str()
+                .strip_prefix("Bearer ")
+                .is_some_and(|given| crate::capture::same_token(given.trim(), token))
+        })
+}
+
+/// The JSON object sent as the body of the request
+fn body(request: &mut Request) -> Result<serde_json::Map<String, Value>, ApiError> {
+    let mut contents = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_LENGTH + 1)
+        .read_to_string(&mut contents)
+        .map_err(|_| ApiError::bad_request("The body should be UTF-8 encoded JSON"))?;
+
+    if contents.len() as u64 > MAX_BODY_LENGTH {
+        return Err(ApiError::new(413, "too_large", "The body is too large"));
+    }
+
+    match serde_json::from_str(&contents) {
+        Ok(Value::Object(fields)) => Ok(fields),
+        Ok(_) => Err(ApiError::bad_request("The body should be a JSON object")),
+        Err(err) => Err(ApiError::bad_request(&format!("Invalid JSON: {}", err))),
+    }
+}
+
+/// A string field of the body, `Some(None)` when it's given as `null`
+fn string_field<'a>(
+    fields: &'a serde_json::Map<String, Value>,
+    key: &str,
+) -> Result<Option<Option<&'a str>>, ApiError> {
+    match fields.get(key) {
+        None => Ok(None),
+        Some(Value::Null) => Ok(Some(None)),
+        Some(Value::String(value)) => Ok(Some(Some(value))),
+        Some(_) => Err(ApiError::bad_request(&format!(
+            "{} should be a string",
+            key
+        ))),
+    }
+}
+
+fn tags_field(fields: &serde_json::Map<String, Value>) -> Result<Option<Vec<String>>, ApiError> {
+    match fields.get("tags") {
+        None => Ok(None),
+        Some(Value::Array(tags)) => tags
+            .iter()
+            .map(|tag| match tag {
+                Value::String(tag) => Ok(tag.to_owned()),
+                _ => Err(ApiError::bad_request("tags should be a list of strings")),
+            })
+            .collect::<Result<Vec<String>, ApiError>>()
+            .map(Some),
+        Some(_) => Err(ApiError::bad_request("tags should be a list of strings")),
+    }
+}
+
+/// The filters given in the query string, named like the options of `list`
+fn query(parameters: &[(String, String)]) -> Result<Query, ApiError> {
+    let mut query = Query::new();
+
+    for (key, value) in parameters {
+        query = match key.as_str() {
+            "text" => query.text(value),
+            "category" => query.category(value),
+            "tag" => query.tag(value),
+            "domain" => query.domain(value),
+            "state" => query.state(
+                value
+                    .parse::<ReadState>()
+                    .map_err(|err| ApiError::bad_request(&err.to_string()))?,
+            ),
+            "since" => query.added_after(
+                crate::parse_date(value)
+                    .map_err(|_| ApiError::bad_request("since should be a date"))?,
+            ),
+            "limit" => query.limit(
+                value
+                    .parse::<usize>()
+                    .map_err(|_| ApiError::bad_request("limit should be a number"))?,
+            ),
+            _ => query,
+        };
+    }
+
+    Ok(query)
+}
+
+fn add(keeper: &LinkKeeper, request: &mut Request) -> Result<(u16, Value), ApiError> {
+    let fields = body(request)?;
+    let url = match string_field(&fields, "url")? {
+        Some(Some(url)) => url.trim(),
+        _ => return Err(ApiError::bad_request("url is missing")),
+    };
+
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(ApiError::bad_request(
+            "Only http and https links can be stored",
+        ));
+    }
+
+    let _lock = keeper.lock_store()?;
+
+    if keeper.link_already_exists(url)? {
+        return Err(ApiError::new(
+            409,
+            Exit::Duplicate.name(),
+            &format!("{} is already stored", url),
+        ));
+    }
+
+    let link = keeper
+        .prepare_link(
+            url,
+            string_field(&fields, "category")?.flatten(),
+            &tags_field(&fields)?.unwrap_or_default(),
+        )
+        .with_title(string_field(&fields, "title")?.flatten());
+    let errors = keeper.add_link(link.clone())?;
+
+    Ok((
+        201,
+        json!({ "link": output::link(&link), "backend_errors": output::errors(&errors) }),
+    ))
+}
+
+fn update(keeper: &LinkKeeper, request: &mut Request, id: &str) -> Result<Value, ApiError> {
+    let fields = body(request)?;
+    let state = match string_field(&fields, "state")? {
+        Some(Some(state)) => Some(
+            state
+                .parse::<ReadState>()
+                .map_err(|err| ApiError::bad_request(&err.to_string()))?,
+        ),
+        Some(None) => return Err(ApiError::bad_request("state can't be null")),
+        None => None,
+    };
+
+    let _lock = keeper.lock_store()?;
+    let mut link = keeper.get_link(id)?;
+    let original = link.clone();
+
+    if let Some(title) = string_field(&fields, "title")? {
+        link = link.with_title(title);
+    }
+
+    if let Some(category) = string_field(&fields, "category")? {
+        link = link.with_category(category);
+    }
+
+    if let Some(tags) = tags_field(&fields)? {
+        link = link.with_tags(tags);
+    }
+
+    let mut errors = vec![];
+
+    if link != original {
+        errors.extend(keeper.update(link.clone())?);
+    }
+
+    // Setting the state records when it changed
+    if let Some(state) = state.filter(|state| *state != link.state()) {
+        errors.extend(keeper.set_state(&link.id(), state)?);
+    }
+
+    Ok(json!({
+        "link": output::link(&keeper.get_link(&link.id())?),
+        "backend_errors": output::errors(&errors),
+    }))
+}
+
+fn search(keeper: &LinkKeeper, parameters: &[(String, String)]) -> Result<Value, ApiError> {
+    let terms = parameters
+        .iter()
+        .filter(|(key, _)| key == "q")
+        .map(|(_, value)| value.as_str())
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    let search = Search::parse(&terms, query(parameters)?)
+        .map_err(|err| ApiError::bad_request(&err.to_string()))?;
+
+    Ok(output::search_results(&keeper.search(&search)?))
+}
+
+/// Answer a request with a status and a JSON body
+fn handle(
+    keeper: &LinkKeeper,
+    request: &mut Request,
+    token: &str,
+) -> Result<(u16, Value), ApiError> {
+    let url = request.url().to_owned();
+    let (path, query_string) = match url.find('?') {
+        Some(index) => (&url[..index], &url[index + 1..]),
+        None => (url.as_str(), ""),
+    };
+    let parameters = form_urlencoded::parse(query_string.as_bytes())
+        .into_owned()
+        .collect::<Vec<(String, String)>>();
+    let segments = path.trim_matches('/').split('/').collect::<Vec<&str>>();
+    let method = request.method().clone();
+
+    if let (Method::Get, ["openapi.json"]) = (&method, segments.as_slice()) {
+        return serde_json::from_str(OPENAPI)
+            .map(|openapi| (200, openapi))
+            .map_err(|err| ApiError::new(500, Exit::Error.name(), &err.to_string()));
+    }
+
+    if !authorized(request, token) {
+        return Err(ApiError::new(
+            401,
+            "unauthorized",
+            &format!("Send the token in {} as a bearer token", TOKEN_KEY),
+        ));
+    }
+
+    match (&method, segments.as_slice()) {
+        (Method::Get, ["links"]) => Ok((200, output::links(&keeper.query(&query(&parameters)?)?))),
+        (Method::Post, ["links"]) => add(keeper, request),
+        (Method::Get, ["links", id]) => Ok((200, output::link(&keeper.get_link(id)?))),
+        (Method::Patch, ["links", id]) => Ok((200, update(keeper, request, id)?)),
+        (Method::Delete, ["links", id]) => {
+            let errors = keeper.remove(id)?;
+            Ok((200, json!({ "backend_errors": output::errors(&errors) })))
+        }
+        (Method::Get, ["search"]) => Ok((200, search(keeper, &parameters)?)),
+        (Method::Get, ["tags"]) => Ok((200, output::tag_counts(&keeper.tag_counts()?))),
+        (_, ["links"]) | (_, ["links", _]) | (_, ["search"]) | (_, ["tags"]) => Err(ApiError::new(
+            405,
+            "method_not_allowed",
+            "The method isn't allowed here",
+        )),
+        _ => Err(ApiError::new(404, "not_found", "Not found")),
+    }
+}
+
+/// Listen on the port of the loopback interface, any free one with 0
+pub fn bind(port: u16) -> Result<Server, failure::Error> {
+    Server::http(("127.0.0.1", port))
+        .map_err(|err| failure::format_err!("Can't listen on port {}: {}", port, err))
+}
+
+/// Answer the requests sent to the server until stopped
+pub fn serve(keeper: &LinkKeeper, server: &Server, token: &str) -> Result<(), failure::Error> {
+    for mut request in server.incoming_requests() {
+        let (status, value) = match handle(keeper, &mut request, token) {
+            Ok(answer) => answer,
+            Err(err) => (
+                err.status,
+                json!({ "error": { "code": err.code, "message": err.message } }),
+            ),
+        };
+
+        log::info!(
+            method = request.method().as_str(),
+            path = request.url().split('?').next().unwrap_or_default(),
+            status = status;
+            "Answered request"
+        );
+
+        // A client that went away only loses its own answer
+        if let Err(err) = request.respond(json_response(status, &value)) {
+            log::warn!(error = err.to_string(); "Could not answer request");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,456 @@
+//! The full-screen browser started by `link-keeper tui`.
+
+use link_keeper::{query::Query, tags, Link, LinkKeeper, ReadState};
+use std::collections::BTreeMap;
+use std::io;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+use tui::backend::{Backend, TermionBackend};
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Color, Modifier, Style};
+use tui::widgets::{Block, Borders, Paragraph, SelectableList, Text, Widget};
+use tui::Terminal;
+
+const HELP: &str =
+    "/ filter  tab switch pane  o open  e edit title  c category  t tags  r read  d delete  q quit";
+
+/// What the sidebar narrows the links down to
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    All,
+    Category(String),
+    Tag(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Focus {
+    Sidebar,
+    Links,
+}
+
+/// What typed text is used for
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Input {
+    Filter,
+    Title,
+    Category,
+    Tags,
+    ConfirmDelete,
+}
+
+struct Browser<'a> {
+    keeper: &'a LinkKeeper<'a>,
+    /// The sidebar entries with their labels
+    filters: Vec<(Filter, String)>,
+    filter: usize,
+    text: String,
+    links: Vec<Link>,
+    selected: usize,
+    focus: Focus,
+    input: Option<Input>,
+    buffer: String,
+    status: String,
+}
+
+impl<'a> Browser<'a> {
+    fn new(keeper: &'a LinkKeeper<'a>) -> Result<Self, failure::Error> {
+        let mut browser = Browser {
+            keeper,
+            filters: vec![],
+            filter: 0,
+            text: String::new(),
+            links: vec![],
+            selected: 0,
+            focus: Focus::Links,
+            input: None,
+            buffer: String::new(),
+            status: HELP.to_owned(),
+        };
+
+        browser.reload()?;
+
+        Ok(browser)
+    }
+
+    /// Read the links again and rebuild the sidebar from their categories
+    /// and tags
+    fn reload(&mut self) -> Result<(), failure::Error> {
+        let links = self.keeper.get_links()?;
+        let current = self
+            .filters
+            .get(self.filter)
+            .map(|(filter, _)| filter.clone());
+
+        let mut categories: BTreeMap<&str, usize> = BTreeMap::new();
+
+        for category in links.iter().filter_map(|link| link.category()) {
+            *categories.entry(category).or_default() += 1;
+        }
+
+        self.filters = vec![(Filter::All, format!("All {}", links.len()))];
+
+        self.filters
+            .extend(categories.into_iter().map(|(category, count)| {
+                (
+                    Filter::Category(category.to_owned()),
+                    format!("[{}] {}", category, count),
+                )
+            }));
+
+        self.filters
+            .extend(tags::counts(&links).into_iter().map(|(tag, count)| {
+                let depth = tag.matches(tags::TAG_SEPARATOR).count();
+                let name = tag.rsplit(tags::TAG_SEPARATOR).next().unwrap_or(&tag);
+                let label = format!("{}#{} {}", "  ".repeat(depth), name, count);
+
+                (Filter::Tag(tag), label)
+            }));
+
+        self.filter = current
+            .and_then(|current| {
+                self.filters
+                    .iter()
+                    .position(|(filter, _)| *filter == current)
+            })
+            .unwrap_or(0);
+
+        self.refresh_links()
+    }
+
+    /// Select the links matching the sidebar entry and the filter text
+    fn refresh_links(&mut self) -> Result<(), failure::Error> {
+        let mut query = Query::new();
+
+        if !self.text.is_empty() {
+            query = query.text(&self.text);
+        }
+
+        query = match &self.filters[self.filter].0 {
+            Filter::All => query,
+            Filter::Category(category) => query.category(category),
+            Filter::Tag(tag) => query.tag(tag),
+        };
+
+        self.links = self.keeper.query(&query)?;
+        self.selected = self.selected.min(self.links.len().saturating_sub(1));
+
+        Ok(())
+    }
+
+    fn selected_link(&self) -> Option<&Link> {
+        self.links.get(self.selected)
+    }
+
+    fn report(&mut self, done: &str, errors: Vec<failure::Error>) {
+        self.status = match errors.first() {
+            Some(err) => format!("{}, but: {}", done, err),
+            None => done.to_owned(),
+        };
+    }
+
+    fn move_selection(&mut self, down: bool) -> Result<(), failure::Error> {
+        let (position, count) = match self.focus {
+            Focus::Sidebar => (&mut self.filter, self.filters.len()),
+            Focus::Links => (&mut self.selected, self.links.len()),
+        };
+
+        if down && *position + 1 < count {
+            *position += 1;
+        } else if !down && *position > 0 {
+            *position -= 1;
+        }
+
+        if self.focus == Focus::Sidebar {
+            self.selected = 0;
+            self.refresh_links()?;
+        }
+
+        Ok(())
+    }
+
+    fn start_input(&mut self, input: Input) {
+        let link = match (input, self.selected_link()) {
+            (Input::Filter, _) => {
+                self.buffer = self.text.to_owned();
+                self.input = Some(input);
+                return;
+            }
+            (_, Some(link)) => link,
+            (_, None) => return,
+        };
+
+        self.buffer = match input {
+            Input::Title => link.title().unwrap_or("").to_owned(),
+            Input::Category => link.category().unwrap_or("").to_owned(),
+            Input::Tags => link.tags().join(", "),
+            _ => String::new(),
+        };
+
+        self.input = Some(input);
+    }
+
+    /// Store what was typed on the selected link
+    fn submit(&mut self, input: Input) -> Result<(), failure::Error> {
+        let link = match self.selected_link() {
+            Some(link) => link.clone(),
+            None => return Ok(()),
+        };
+
+        let value = Some(self.buffer.trim()).filter(|value| !value.is_empty());
+
+        let link = match input {
+            Input::Title => link.with_title(value),
+            Input::Category => link.with_category(value),
+            Input::Tags => {
+                link.with_tags(self.buffer.split(',').map(|tag| tag.to_owned()).collect())
+            }
+            _ => return Ok(()),
+        };
+
+        let errors = self.keeper.update(link)?;
+        self.report("Saved", errors);
+
+        self.reload()
+    }
+
+    /// Handle a key, returns false when the browser should quit
+    fn handle(&mut self, key: Key) -> Result<bool, failure::Error> {
+        match (self.input, key) {
+            (Some(Input::ConfirmDelete), Key::Char('y')) => {
+                self.input = None;
+
+                if let Some(id) = self.selected_link().map(|link| link.id()) {
+                    let errors = self.keeper.remove(&id)?;
+                    self.report("Deleted", errors);
+                    self.reload()?;
+                }
+            }
+            (Some(Input::ConfirmDelete), _) => {
+                self.input = None;
+                self.status = HELP.to_owned();
+            }
+            (Some(Input::Filter), Key::Char('\n')) => self.input = None,
+            (Some(Input::Filter), Key::Esc) => {
+                self.input = None;
+                self.text.clear();
+                self.refresh_links()?;
+            }
+            (Some(Input::Filter), Key::Backspace) => {
+                self.text.pop();
+                self.refresh_links()?;
+            }
+            (Some(Input::Filter), Key::Char(character)) => {
+                self.text.push(character);
+                self.selected = 0;
+                self.refresh_links()?;
+            }
+            (Some(input), Key::Char('\n')) => {
+                self.input = None;
+                self.submit(input)?;
+            }
+            (Some(_), Key::Esc) => self.input = None,
+            (Some(_), Key::Backspace) => {
+                self.buffer.pop();
+            }
+            (Some(_), Key::Char(character)) => self.buffer.push(character),
+            (Some(_), _) => {}
+            (None, Key::Char('q')) | (None, Key::Ctrl('c')) => return Ok(false),
+            (None, Key::Esc) if !self.text.is_empty() => {
+                self.text.clear();
+                self.refresh_links()?;
+            }
+            (None, Key::Char('\t')) => {
+                self.focus = match self.focus {
+                    Focus::Sidebar => Focus::Links,
+                    Focus::Links => Focus::Sidebar,
+                };
+            }
+            (None, Key::Down) | (None, Key::Char('j')) => self.move_selection(true)?,
+            (None, Key::Up) | (None, Key::Char('k')) => self.move_selection(false)?,
+            (None, Key::Char('/')) => self.start_input(Input::Filter),
+            (None, Key::Char('e')) => self.start_input(Input::Title),
+            (None, Key::Char('c')) => self.start_input(Input::Category),
+            (None, Key::Char('t')) => self.start_input(Input::Tags),
+            (None, Key::Char('d')) if self.selected_link().is_some() => {
+                self.input = Some(Input::ConfirmDelete);
+            }
+            (None, Key::Char('o')) | (None, Key::Char('\n')) => {
+                if let Some(link) = self.selected_link() {
+                    open::that(link.link())?;
+                }
+            }
+            (None, Key::Char('r')) => {
+                if let Some(link) = self.selected_link() {
+                    let state = match link.state() {
+                        ReadState::Read => ReadState::Unread,
+                        _ => ReadState::Read,
+                    };
+
+                    let errors = self.keeper.set_state(&link.id(), state)?;
+                    self.report("Saved", errors);
+                    self.reload()?;
+                }
+            }
+            (None, _) => {}
+        }
+
+        Ok(true)
+    }
+
+    fn status_line(&self) -> String {
+        match self.input {
+            Some(Input::Filter) => format!("Filter: {}_", self.text),
+            Some(Input::Title) => format!("Title: {}_", self.buffer),
+            Some(Input::Category) => format!("Category: {}_", self.buffer),
+            Some(Input::Tags) => format!("Tags, separated by commas: {}_", self.buffer),
+            Some(Input::ConfirmDelete) => "Delete this link? (y/n)".to_owned(),
+            None => self.status.to_owned(),
+        }
+    }
+
+    fn details(&self) -> Vec<Text<'_>> {
+        let link = match self.selected_link() {
+            Some(link) => link,
+            None => return vec![Text::raw("No links")],
+        };
+
+        let bold = Style::default().modifier(Modifier::Bold);
+        let mut details = vec![
+            Text::styled(format!("{}\n", link.title().unwrap_or("Untitled")), bold),
+            Text::raw(format!("{}\n\n", link.link())),
+            Text::raw(format!("Id: {}\n", link.id())),
+            Text::raw(format!("Category: {}\n", link.category().unwrap_or("-"))),
+            Text::raw(format!("Tags: {}\n", link.tags().join(", "))),
+            Text::raw(format!("State: {}\n", link.state())),
+        ];
+
+        if let Some(added) = link.added() {
+            details.push(Text::raw(format!(
+                "Added: {}\n",
+                added.format("%Y-%m-%d %H:%M")
+            )));
+        }
+
+        if link.snapshot().is_some() {
+            details.push(Text::raw("Archived\n"));
+        }
+
+        if let Some(description) = link
+            .metadata()
+            .and_then(|metadata| metadata.description.as_ref())
+        {
+            details.push(Text::raw(format!("\n{}\n", description)));
+        }
+
+        details
+    }
+
+    fn draw<B: Backend>(&self, terminal: &mut Terminal<B>) -> io::Result<()> {
+        let highlight = Style::default().fg(Color::Yellow).modifier(Modifier::Bold);
+        let border = |focus: Focus| {
+            if self.focus == focus {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            }
+        };
+
+        let sidebar = self
+            .filters
+            .iter()
+            .map(|(_, label)| label.as_str())
+            .collect::<Vec<&str>>();
+        let links = self
+            .links
+            .iter()
+            .map(|link| link.title().unwrap_or_else(|| link.link()))
+            .collect::<Vec<&str>>();
+        let links_title = format!("Links {}", self.links.len());
+        let details = self.details();
+        let status = [Text::raw(self.status_line())];
+
+        terminal.draw(|mut f| {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(1)].as_ref())
+                .split(f.size());
+
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(
+                    [
+                        Constraint::Percentage(20),
+                        Constraint::Percentage(45),
+                        Constraint::Percentage(35),
+                    ]
+                    .as_ref(),
+                )
+                .split(rows[0]);
+
+            SelectableList::default()
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_style(border(Focus::Sidebar))
+                        .title("Categories and tags"),
+                )
+                .items(&sidebar)
+                .select(Some(self.filter))
+                .highlight_style(highlight)
+                .highlight_symbol(">")
+                .render(&mut f, columns[0]);
+
+            SelectableList::default()
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_style(border(Focus::Links))
+                        .title(&links_title),
+                )
+                .items(&links)
+                .select(Some(self.selected))
+                .highlight_style(highlight)
+                .highlight_symbol(">")
+                .render(&mut f, columns[1]);
+
+            Paragraph::new(details.iter())
+                .block(Block::default().borders(Borders::ALL).title("Details"))
+                .wrap(true)
+                .render(&mut f, columns[2]);
+
+            Paragraph::new(status.iter()).render(&mut f, rows[1]);
+        })
+    }
+}
+
+/// Run the browser until the user quits
+pub fn run(keeper: &LinkKeeper) -> Result<(), failure::Error> {
+    let stdout = io::stdout().into_raw_mode()?;
+    let stdout = AlternateScreen::from(stdout);
+    let mut terminal = Terminal::new(TermionBackend::new(stdout))?;
+    let mut browser = Browser::new(keeper)?;
+
+    terminal.hide_cursor()?;
+
+    let stdin = io::stdin();
+    let mut keys = stdin.keys();
+
+    loop {
+        browser.draw(&mut terminal)?;
+
+        match keys.next() {
+            Some(key) => {
+                if !browser.handle(key?)? {
+                    break;
+                }
+            }
+            None => break,
+        }
+    }
+
+    terminal.show_cursor()?;
+
+    Ok(())
+}
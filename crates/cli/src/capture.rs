@@ -0,0 +1,206 @@
+//! The local endpoint run by `link-keeper capture`. A bookmarklet sends it
+//! the page open in the browser, e.g. `GET /add?token=..&url=..&title=..`.
+//!
+//! Any page could send requests to it, so only requests with the token of
+//! the bookmarklet are accepted and it only listens on the loopback
+//! interface.
+
+use link_keeper::{Link, LinkKeeper};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+pub const DEFAULT_PORT: &str = "7878";
+
+/// The setting the token is kept in, so the bookmarklet keeps working
+pub const TOKEN_KEY: &str = "capture.token";
+
+/// A new random token for the bookmarklet
+pub fn new_token() -> Result<String, failure::Error> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|err| failure::format_err!("Can't create a token: {}", err))?;
+
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// The bookmarklet that sends the open page, in a small window that closes
+/// itself once the link is stored
+pub fn bookmarklet(port: u16, token: &str) -> String {
+    format!(
+        "javascript:(function(){{window.open('http://127.0.0.1:{}/add?token={}&url='+encodeURIComponent(location.href)+'&title='+encodeURIComponent(document.title),'link-keeper','width=420,height=160');}})();",
+        port, token
+    )
+}
+
+/// A page sent by the bookmarklet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    pub url: String,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Compare in constant time, so the token can't be guessed byte by byte
+pub fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (given, token)| difference | (given ^ token))
+            == 0
+}
+
+/// Read a capture from the path and query of a request, or the status and
+/// message to answer with
+fn parse(url: &str, token: &str) -> Result<Capture, (u16, &'static str)> {
+    let (path, query) = match url.find('?') {
+        Some(index) => (&url[..index], &url[index + 1..]),
+        None => (url, ""),
+    };
+
+    if path != "/add" {
+        return Err((404, "Not found"));
+    }
+
+    let mut given_token = None;
+    let mut capture = Capture {
+        url: String::new(),
+        title: None,
+        tags: vec![],
+    };
+
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            "token" => given_token = Some(value.into_owned()),
+            "url" => capture.url = value.trim().to_owned(),
+            "title" if !value.trim().is_empty() => capture.title = Some(value.trim().to_owned()),
+            "tags" => capture.tags.extend(
+                value
+                    .split(',')
+                    .map(|tag| tag.trim().to_owned())
+                    .filter(|tag| !tag.is_empty()),
+            ),
+            _ => {}
+        }
+    }
+
+    if !given_token.is_some_and(|given| same_token(&given, token)) {
+        return Err((403, "The bookmarklet's token is wrong, copy it again"));
+    }
+
+    if !capture.url.starts_with("http://") && !capture.url.starts_with("https://") {
+        return Err((400, "Only http and https links can be stored"));
+    }
+
+    Ok(capture)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A page telling what happened, closing itself when the link was stored
+fn page(status: u16, message: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    let close = if status == 200 {
+        "<script>setTimeout(function(){window.close()},1200)</script>"
+    } else {
+        ""
+    };
+
+    Response::from_string(format!(
+        "<!DOCTYPE html><meta charset=\"utf-8\"><title>Link keeper</title><p>{}</p>{}",
+        message, close
+    ))
+    .with_status_code(status)
+    .with_header(
+        "Content-Type: text/html; charset=utf-8"
+            .parse::<Header>()
+            .expect("a valid header"),
+    )
+}
+
+/// Answer a request, a client that went away only loses its own answer
+fn respond(request: Request, status: u16, message: &str) {
+    if let Err(err) = request.respond(page(status, message)) {
+        log::warn!(error = err.to_string(); "Could not answer capture");
+    }
+}
+
+/// Store the captured link with the errors of the backends that failed, or
+/// nothing when it's already stored
+fn store(
+    keeper: &LinkKeeper,
+    capture: &Capture,
+) -> Result<Option<(Link, Vec<failure::Error>)>, failure::Error> {
+    let _lock = keeper.lock_store()?;
+
+    if keeper.link_already_exists(&capture.url)? {
+        return Ok(None);
+    }
+
+    let link = keeper
+        .prepare_link(&capture.url, None, &capture.tags)
+        .with_title(capture.title.as_deref());
+    let errors = keeper.add_link(link.clone())?;
+
+    Ok(Some((link, errors)))
+}
+
+/// Store the links sent by the bookmarklet until stopped, or after the first
+/// one with `once`. Every stored link is handed to `stored` with the errors
+/// of the backends that failed.
+pub fn serve<F>(
+    keeper: &LinkKeeper,
+    port: u16,
+    token: &str,
+    once: bool,
+    stored: F,
+) -> Result<(), failure::Error>
+where
+    F: Fn(&Link, &[failure::Error]),
+{
+    let server = Server::http(("127.0.0.1", port))
+        .map_err(|err| failure::format_err!("Can't listen on port {}: {}", port, err))?;
+
+    for request in server.incoming_requests() {
+        if *request.method() != Method::Get {
+            respond(request, 405, "Only GET requests are accepted");
+            continue;
+        }
+
+        let capture = match parse(request.url(), token) {
+            Ok(capture) => capture,
+            Err((status, message)) => {
+                log::warn!(status = status; "Rejected capture: {}", message);
+                respond(request, status, message);
+                continue;
+            }
+        };
+
+        let name = escape(capture.title.as_deref().unwrap_or(&capture.url));
+
+        match store(keeper, &capture) {
+            Ok(None) => respond(request, 200, &format!("<b>{}</b> is already stored", name)),
+            Ok(Some((link, errors))) => {
+                stored(&link, &errors);
+                respond(request, 200, &format!("Stored <b>{}</b>", name));
+
+                if once {
+                    break;
+                }
+            }
+            Err(err) => {
+                log::error!(error = err.to_string(); "Could not store capture");
+                respond(
+                    request,
+                    500,
+                    &format!("<b>{}</b> could not be stored", name),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
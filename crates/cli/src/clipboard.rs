@@ -0,0 +1,58 @@
+//! Reading the clipboard with the command line tools of the desktop, found
+//! when they're needed.
+
+use std::env;
+use std::io;
+use std::process::Command;
+
+/// The tools tried in order, with the environment variable that tells that
+/// the session they work in is running. macOS always has a clipboard.
+const TOOLS: &[(Option<&str>, &str, &[&str])] = &[
+    (Some("WAYLAND_DISPLAY"), "wl-paste", &["--no-newline"]),
+    (
+        Some("DISPLAY"),
+        "xclip",
+        &["-selection", "clipboard", "-out"],
+    ),
+    (Some("DISPLAY"), "xsel", &["--clipboard", "--output"]),
+    (None, "pbpaste", &[]),
+];
+
+/// The text on the clipboard
+pub fn read() -> Result<String, failure::Error> {
+    let mut missing = vec![];
+
+    for (session, tool, args) in TOOLS {
+        if session.is_some_and(|session| env::var_os(session).is_none())
+            || (session.is_none() && !cfg!(target_os = "macos"))
+        {
+            continue;
+        }
+
+        match Command::new(tool).args(*args).output() {
+            Ok(output) if output.status.success() => {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+            }
+            Ok(output) => {
+                return Err(failure::format_err!(
+                    "{} could not read the clipboard: {}",
+                    tool,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => missing.push(*tool),
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    if missing.is_empty() {
+        Err(failure::format_err!(
+            "No clipboard to read, neither WAYLAND_DISPLAY nor DISPLAY is set"
+        ))
+    } else {
+        Err(failure::format_err!(
+            "Reading the clipboard needs {}, install wl-clipboard on Wayland or xclip on X11",
+            missing.join(" or ")
+        ))
+    }
+}
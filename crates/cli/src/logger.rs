@@ -0,0 +1,130 @@
+//! Writes what the library and the backends log to stderr, as text for
+//! people or as one JSON object per line for other programs.
+
+use chrono::Utc;
+use console::style;
+use log::kv::{self, Key, Value, VisitSource};
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl Format {
+    pub fn names() -> [&'static str; 2] {
+        ["text", "json"]
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// The level to log at after `-v` and `-q` were given the number of times.
+/// Warnings are logged by default.
+pub fn level(verbose: u64, quiet: u64) -> LevelFilter {
+    match verbose as i64 - quiet as i64 {
+        i64::MIN..=-2 => LevelFilter::Off,
+        -1 => LevelFilter::Error,
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Log records at the level and more severe ones to stderr
+pub fn init(level: LevelFilter, format: Format) -> Result<(), SetLoggerError> {
+    log::set_boxed_logger(Box::new(Logger { format }))?;
+    log::set_max_level(level);
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Logger {
+    format: Format,
+}
+
+/// The key-values of a record, in the order they were given
+#[derive(Default)]
+struct Fields(Vec<(String, String)>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.push((key.to_string(), value.to_string()));
+
+        Ok(())
+    }
+}
+
+impl Logger {
+    fn text(record: &Record, fields: &Fields) -> String {
+        let level = match record.level() {
+            Level::Error => style("error").red(),
+            Level::Warn => style("warning").yellow(),
+            Level::Info => style("info").green(),
+            Level::Debug => style("debug").dim(),
+            Level::Trace => style("trace").dim(),
+        };
+
+        let mut line = format!("{}: {}", level, record.args());
+
+        for (key, value) in &fields.0 {
+            line.push_str(&format!(" {}", style(format!("{}={}", key, value)).dim()));
+        }
+
+        line
+    }
+
+    fn json(record: &Record, fields: &Fields) -> String {
+        let fields = fields
+            .0
+            .iter()
+            .map(|(key, value)| (key.to_owned(), serde_json::Value::String(value.to_owned())))
+            .collect::<serde_json::Map<String, serde_json::Value>>();
+
+        serde_json::json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "level": record.level().to_string().to_lowercase(),
+            "target": record.target(),
+            "message": record.args().to_string(),
+            "fields": fields,
+        })
+        .to_string()
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut fields = Fields::default();
+        let _ = record.key_values().visit(&mut fields);
+
+        let line = match self.format {
+            Format::Text => Self::text(record, &fields),
+            Format::Json => Self::json(record, &fields),
+        };
+
+        // Nowhere to report it if stderr can't be written to
+        let _ = writeln!(io::stderr(), "{}", line);
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+    }
+}
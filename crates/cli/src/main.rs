@@ -1,73 +1,524 @@
-use clap::{App, Arg, SubCommand};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{value_t, App, Arg, ArgMatches, ErrorKind, SubCommand};
 use console::style;
-use dialoguer::{Confirmation, Input, PasswordInput, Select};
+use dialoguer::{Checkboxes, Confirmation, Input, PasswordInput, Select};
 use link_keeper::{
-    backend::{AccessToken, Backend},
-    LinkKeeper,
+    annotation::Annotation,
+    archive::Archiver,
+    backend::{BackendRegistry, ConfigField, FieldKind},
+    check::{Checker, Outcome},
+    config::{self, Config},
+    export,
+    http::UreqClient,
+    import,
+    metadata::Enricher,
+    migrate, normalize,
+    query::Query,
+    reading::Pick,
+    search::Search,
+    tags, ImportAction, Link, LinkKeeper, LinkKeeperBuilder, ReadState,
 };
-use link_keeper_git_backend::{Git, GitConfig};
-use link_keeper_github_backend::{Github, GithubConfig};
+use output::Exit;
 use std::env;
-use std::fmt;
-use std::io;
-use std::path::PathBuf;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::time::Duration;
 
-#[derive(Debug, PartialEq)]
-pub enum AvailableBackend {
-    Git,
-    Github,
+mod api;
+mod browser;
+mod capture;
+mod clipboard;
+mod logger;
+mod output;
+
+// Generated by build.rs from the backend crates in Cargo.toml
+include!(concat!(env!("OUT_DIR"), "/backends.rs"));
+
+/// All backends known to the CLI. Backend crates only need to be added as a
+/// dependency to show up in `backend add` and be loaded from the config.
+fn backend_registry() -> BackendRegistry {
+    let mut registry = BackendRegistry::new();
+    register_backends(&mut registry);
+
+    registry
+}
+
+/// The `backend add` flag that sets the field, secrets name the environment
+/// variable holding them
+fn field_flag(field: &ConfigField) -> String {
+    match field.kind {
+        FieldKind::Secret => format!("{}-env", field.flag),
+        _ => field.flag.to_owned(),
+    }
 }
 
-impl fmt::Display for AvailableBackend {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        match self {
-            AvailableBackend::Git => fmt.write_fmt(format_args!("Git")),
-            AvailableBackend::Github => fmt.write_fmt(format_args!("Github")),
+/// A flag for every field of every backend, backends with fields of the same
+/// name share the flag
+fn backend_flags(registry: &BackendRegistry) -> Vec<(String, String, FieldKind)> {
+    let mut flags: Vec<(String, String, FieldKind)> = vec![];
+
+    for name in registry.names() {
+        let fields = registry
+            .get(name)
+            .map(|factory| factory.config_schema())
+            .unwrap_or_default();
+
+        for field in fields {
+            let flag = field_flag(&field);
+            let help = match field.kind {
+                FieldKind::Secret => format!("{}: {}, read from this variable", name, field.prompt),
+                _ => format!("{}: {}", name, field.prompt),
+            };
+
+            match flags.iter_mut().find(|(existing, _, _)| *existing == flag) {
+                Some((_, existing_help, _)) => {
+                    existing_help.push_str(&format!("\n{}", help));
+                }
+                None => flags.push((flag, help, field.kind)),
+            }
         }
     }
+
+    flags
 }
 
-impl From<usize> for AvailableBackend {
-    fn from(num: usize) -> Self {
-        match num {
-            0 => AvailableBackend::Git,
-            1 => AvailableBackend::Github,
-            _ => panic!("Unknown"),
+/// The value of a field as it's stored, paths are made absolute so they
+/// don't depend on where link keeper is run
+fn field_value(field: &ConfigField, input: &str) -> toml::Value {
+    match field.kind {
+        FieldKind::Toggle => toml::Value::Boolean(input == "true"),
+        FieldKind::Path if !input.is_empty() => toml::Value::String(
+            env::current_dir()
+                .map(|current_dir| current_dir.join(input))
+                .unwrap_or_else(|_| Path::new(input).to_owned())
+                .to_string_lossy()
+                .into_owned(),
+        ),
+        FieldKind::Text | FieldKind::Secret | FieldKind::Path => {
+            toml::Value::String(input.to_owned())
         }
     }
 }
 
-impl From<&str> for AvailableBackend {
-    fn from(string: &str) -> Self {
-        match string {
-            "git" => AvailableBackend::Git,
-            "github" => AvailableBackend::Github,
-            _ => panic!("Unknown"),
+/// The value of a field given with its flag
+fn flag_value(
+    field: &ConfigField,
+    matches: &ArgMatches,
+) -> Result<Option<toml::Value>, failure::Error> {
+    let flag = field_flag(field);
+
+    match (field.kind, matches.value_of(&flag)) {
+        (FieldKind::Toggle, _) => {
+            Ok(Some(toml::Value::Boolean(true)).filter(|_| matches.is_present(&flag)))
         }
+        (FieldKind::Secret, Some(variable)) => env::var(variable)
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .map(|secret| Some(toml::Value::String(secret)))
+            .ok_or_else(|| failure::format_err!("{} is not set", variable)),
+        (_, value) => Ok(value.map(|value| field_value(field, value))),
     }
 }
 
-fn get_old_backends(old_toml_config: &toml::Value) -> Option<Vec<Box<dyn Backend>>> {
-    old_toml_config.as_table().and_then(|table| {
-        table
-            .get("backends")
-            .and_then(|backends| backends.as_table())
-            .map(|backends| {
-                backends
-                    .into_iter()
-                    .filter_map(|(key, value)| match AvailableBackend::from(key.as_str()) {
-                        AvailableBackend::Git => Some(Box::new(Git {
-                            config: value.clone().try_into::<GitConfig>().unwrap(),
-                        })
-                            as Box<dyn Backend>),
-                        AvailableBackend::Github => Some(Box::new(Github {
-                            config: value.clone().try_into::<GithubConfig>().unwrap(),
-                        })
-                            as Box<dyn Backend>),
-                    })
-                    .collect()
-            })
-    })
+fn prompt_field(field: &ConfigField) -> io::Result<toml::Value> {
+    let value = match field.kind {
+        FieldKind::Secret => PasswordInput::new().with_prompt(field.prompt).interact()?,
+        FieldKind::Toggle => {
+            let default = field.default.as_deref() == Some("true");
+
+            return Ok(toml::Value::Boolean(
+                Confirmation::new()
+                    .with_text(field.prompt)
+                    .default(default)
+                    .interact()?,
+            ));
+        }
+        FieldKind::Text | FieldKind::Path => {
+            let mut input = Input::<String>::new();
+            input.with_prompt(field.prompt);
+
+            if let Some(default) = &field.default {
+                input.default(default.to_owned());
+            }
+
+            input.interact()?
+        }
+    };
+
+    Ok(field_value(field, &value))
+}
+
+/// A backend's configuration from the flags given to `backend add`, asking
+/// for the fields they leave out. When nobody can be asked the defaults are
+/// used instead.
+fn backend_config(
+    name: &str,
+    fields: &[ConfigField],
+    matches: &ArgMatches,
+    flags: &[(String, String, FieldKind)],
+    interactive: bool,
+) -> Result<toml::Value, failure::Error> {
+    if let Some((flag, _, _)) = flags.iter().find(|(flag, _, _)| {
+        matches.is_present(flag) && !fields.iter().any(|field| field_flag(field) == *flag)
+    }) {
+        return Err(failure::format_err!("--{} doesn't apply to {}", flag, name));
+    }
+
+    let mut config = toml::value::Table::new();
+
+    for field in fields {
+        let value = match flag_value(field, matches)? {
+            Some(value) => value,
+            None if interactive => prompt_field(field)?,
+            None => match &field.default {
+                Some(default) => field_value(field, default),
+                None => {
+                    return Err(failure::format_err!(
+                        "--{} is needed to add {}",
+                        field_flag(field),
+                        name
+                    ))
+                }
+            },
+        };
+
+        config.insert(field.key.to_owned(), value);
+    }
+
+    Ok(toml::Value::Table(config))
+}
+
+/// Arguments for filtering links, see `query_from_matches`
+fn query_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("category")
+            .long("category")
+            .takes_value(true)
+            .help("Only links in this category"),
+        Arg::with_name("tag")
+            .long("tag")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Only links with this tag, can be given multiple times"),
+        Arg::with_name("domain")
+            .long("domain")
+            .takes_value(true)
+            .help("Only links on this domain"),
+        Arg::with_name("state")
+            .long("state")
+            .takes_value(true)
+            .possible_values(&["unread", "reading", "read", "archived"])
+            .help("Only links in this reading state"),
+        Arg::with_name("since")
+            .long("since")
+            .takes_value(true)
+            .help("Only links added after this date, e.g. 2019-03-01"),
+        Arg::with_name("limit")
+            .long("limit")
+            .takes_value(true)
+            .help("At most this many links"),
+    ]
+}
+
+/// The settings given with `--set key=value` and `--store`
+fn config_overrides(matches: &ArgMatches) -> Vec<(String, toml::Value)> {
+    let mut overrides = matches
+        .values_of("set")
+        .into_iter()
+        .flatten()
+        .map(|setting| match setting.find('=') {
+            Some(index) => (
+                setting[..index].trim().to_owned(),
+                config::parse_value(setting[index + 1..].trim()),
+            ),
+            None => (setting.trim().to_owned(), toml::Value::Boolean(true)),
+        })
+        .collect::<Vec<(String, toml::Value)>>();
+
+    if let Some(store) = matches.value_of("store") {
+        let store = Path::new(store);
+        let directory = match store.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+            _ => Path::new(".").to_owned(),
+        };
+
+        overrides.push((
+            "store_path".to_owned(),
+            toml::Value::String(directory.to_string_lossy().into_owned()),
+        ));
+
+        if let Some(file_name) = store.file_name() {
+            overrides.push((
+                "store_file_name".to_owned(),
+                toml::Value::String(file_name.to_string_lossy().into_owned()),
+            ));
+        }
+    }
+
+    overrides
+}
+
+fn parse_date(date: &str) -> Result<DateTime<Utc>, failure::Error> {
+    match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        Ok(date) => Ok(DateTime::from_naive_utc_and_offset(
+            date.and_hms_opt(0, 0, 0).unwrap_or_default(),
+            Utc,
+        )),
+        Err(_) => Ok(date.parse::<DateTime<Utc>>()?),
+    }
+}
+
+fn query_from_matches(matches: &ArgMatches) -> Result<Query, failure::Error> {
+    let mut query = Query::new();
+
+    if let Some(category) = matches.value_of("category") {
+        query = query.category(category);
+    }
+
+    for tag in matches.values_of("tag").into_iter().flatten() {
+        query = query.tag(tag);
+    }
+
+    if let Some(domain) = matches.value_of("domain") {
+        query = query.domain(domain);
+    }
+
+    if let Some(state) = matches.value_of("state") {
+        query = query.state(state.parse::<ReadState>()?);
+    }
+
+    if let Some(since) = matches.value_of("since") {
+        query = query.added_after(parse_date(since).map_err(|_| {
+            failure::format_err!("--since should be a date like 2019-03-01, not {}", since)
+        })?);
+    }
+
+    if let Some(limit) = matches.value_of("limit") {
+        query = query.limit(
+            limit
+                .parse::<usize>()
+                .map_err(|_| failure::format_err!("--limit should be a number, not {}", limit))?,
+        );
+    }
+
+    Ok(query)
+}
+
+/// Let the user pick among the tags and category suggested for a link
+fn choose_suggestions(keeper: &LinkKeeper, link: Link) -> Result<Link, io::Error> {
+    // Suggestions are only a help, the link is added without them
+    let suggestions = match keeper.suggest(&link) {
+        Ok(suggestions) => suggestions,
+        Err(err) => {
+            log::warn!(link = link.link(), error = err.to_string(); "Could not suggest tags");
+            return Ok(link);
+        }
+    };
+    let mut link = link;
+
+    if let (None, Some(category)) = (link.category(), &suggestions.category) {
+        if confirm(&format!("Store it in the category {}?", category), false)? {
+            link = link.with_category(Some(category));
+        }
+    }
+
+    if !suggestions.tags.is_empty() {
+        println!("{}", style("Suggested tags, select with space:").bold());
+
+        let chosen = Checkboxes::new()
+            .items(
+                &suggestions
+                    .tags
+                    .iter()
+                    .map(|tag| tag.as_str())
+                    .collect::<Vec<&str>>(),
+            )
+            .interact()?;
+
+        let mut tags = link.tags().to_vec();
+        tags.extend(
+            chosen
+                .into_iter()
+                .map(|index| suggestions.tags[index].to_owned()),
+        );
+
+        link = link.with_tags(tags);
+    }
+
+    Ok(link)
+}
+
+/// Ask whether to go ahead, `--yes` answers for the user and nobody is
+/// asked when no one is there to answer
+fn confirm(text: &str, yes: bool) -> io::Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+
+    if !console::user_attended() {
+        return Ok(false);
+    }
+
+    Confirmation::new().with_text(text).interact()
+}
+
+/// Every registered backend by name and whether it's activated in the
+/// configuration
+fn backend_states(keeper: &LinkKeeper) -> Vec<(String, bool)> {
+    keeper
+        .get_available_backends()
+        .into_iter()
+        .map(|name| {
+            let activated = keeper.config().get(&format!("backends.{}", name)).is_some();
+
+            (name, activated)
+        })
+        .collect()
+}
+
+/// Report invalid arguments and exit, printing help and the version as clap
+/// does. The arguments couldn't be parsed, so `--json` is looked for by hand.
+fn fail_usage(err: clap::Error) -> ! {
+    if let ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed = err.kind {
+        err.exit()
+    }
+
+    if env::args().any(|arg| arg == "--json") {
+        let message = console::strip_ansi_codes(&err.message);
+        let message = message.lines().next().unwrap_or_default();
+
+        output::fail(
+            true,
+            Exit::Usage,
+            message.strip_prefix("error: ").unwrap_or(message),
+        )
+    }
+
+    eprintln!("{}", err.message);
+    process::exit(Exit::Usage.code())
+}
+
+/// The link with the given id, exiting if there's none
+fn find_link(keeper: &LinkKeeper, id: &str, json: bool) -> Link {
+    output::or_fail(json, keeper.get_link(id))
+}
+
+fn print_link(link: &Link) {
+    println!(
+        "{} {}",
+        style(link.id()).yellow(),
+        style(link.title().unwrap_or_else(|| link.link())).bold()
+    );
+    println!("         {}", link.link());
+}
+
+fn print_backend_errors(link: &str, errors: &[failure::Error]) {
+    for error in errors {
+        eprintln!(
+            "{}{}",
+            style("warning").yellow().bold(),
+            style(format!(": {}: {}", link, error)).bold(),
+        );
+    }
+}
+
+/// Lines starting with this are left out of the written note
+const EDITOR_COMMENT: &str = "#";
+
+/// Create a file with a random name in the temporary directory that only
+/// the user can read. It must not exist yet, so a planted symlink or another
+/// `note` running at the same time isn't written to.
+fn create_note_file(contents: &[u8]) -> Result<PathBuf, io::Error> {
+    let mut bytes = [0u8; 8];
+    getrandom::getrandom(&mut bytes).map_err(|err| io::Error::other(err.to_string()))?;
+
+    let name = bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    let path = env::temp_dir().join(format!("link-keeper-note-{}.md", name));
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options.open(&path)?.write_all(contents)?;
+
+    Ok(path)
+}
+
+/// Let the user write a note in `$VISUAL` or `$EDITOR`, showing the link and
+/// its existing notes as comments. `None` if nothing was written.
+fn edit_note(link: &Link, highlight: bool) -> Result<Option<String>, io::Error> {
+    let mut template = format!(
+        "\n{} Write the {} for {} above.\n{} Lines starting with '{}' are ignored, an empty {} is not saved.\n",
+        EDITOR_COMMENT,
+        if highlight { "highlight" } else { "note" },
+        link.link(),
+        EDITOR_COMMENT,
+        EDITOR_COMMENT,
+        if highlight { "highlight" } else { "note" },
+    );
+
+    for annotation in link.annotations() {
+        template.push_str(&format!(
+            "{}\n{} {:?} from {}:\n",
+            EDITOR_COMMENT,
+            EDITOR_COMMENT,
+            annotation.kind,
+            annotation.created.format("%Y-%m-%d")
+        ));
+
+        for line in annotation.text.lines() {
+            template.push_str(&format!("{}   {}\n", EDITOR_COMMENT, line));
+        }
+    }
+
+    let path = create_note_file(template.as_bytes())?;
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_owned());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    let status = Command::new(program).args(words).arg(&path).status();
+    let text = fs::read_to_string(&path);
+    fs::remove_file(&path)?;
+
+    if !status?.success() {
+        return Err(io::Error::other(format!(
+            "{} exited with an error",
+            program
+        )));
+    }
+
+    let text = text?
+        .lines()
+        .filter(|line| !line.starts_with(EDITOR_COMMENT))
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    if text.trim().is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(text.trim().to_owned()))
+    }
+}
+
+/// Print the id under which a newly added link can be opened
+fn print_added(link: &str) {
+    println!(
+        "{}: {} {}",
+        style("Added").green().bold(),
+        Link::new(link, None).id(),
+        link
+    );
 }
 
 const PKG_VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
@@ -76,118 +527,1477 @@ const PKG_NAME: Option<&'static str> = option_env!("CARGO_PKG_NAME");
 fn main() -> Result<(), io::Error> {
     let add_command = "add";
     let add_link_command = "link";
+    let add_file_command = "file";
+    let add_clipboard_command = "clipboard";
+    let add_fetch_command = "fetch";
+    let add_archive_command = "archive";
+    let add_category_command = "category";
+    let add_tag_command = "tag";
+    let add_no_suggest_command = "no-suggest";
+    let tags_command = "tags";
+    let tags_list_command = "list";
+    let tags_rename_command = "rename";
+    let tags_merge_command = "merge";
+    let tags_delete_command = "delete";
+    let open_command = "open";
+    let search_command = "search";
+    let tui_command = "tui";
+    let next_command = "next";
+    let next_random_command = "random";
+    let next_open_command = "open";
+    let mark_command = "mark";
+    let mark_state_command = "state";
+    let mark_ids_command = "ids";
+    let stats_command = "stats";
+    let note_command = "note";
+    let note_id_command = "id";
+    let note_highlight_command = "highlight";
+    let search_terms_command = "terms";
+    let open_id_command = "id";
+    let open_archived_command = "archived";
+    let open_text_command = "text";
+    let refresh_command = "refresh";
+    let check_command = "check";
+    let check_parallel_command = "parallel";
+    let check_timeout_command = "timeout";
+    let check_fix_redirects_command = "fix-redirects";
+    let set_command = "set";
+    let store_command = "store";
+    let json_command = "json";
+    let yes_command = "yes";
+    let verbose_command = "verbose";
+    let quiet_command = "quiet";
+    let log_format_command = "log-format";
+    let config_command = "config";
+    let config_get_command = "get";
+    let config_set_command = "set";
+    let config_show_command = "show";
+    let config_key_command = "key";
+    let config_value_command = "value";
+    let config_origin_command = "origin";
+    let config_project_command = "project";
+    let migrate_command = "migrate";
+    let migrate_check_command = "check";
     let backend_command = "backend";
     let backend_add_command = "add";
+    let backend_list_command = "list";
+    let backend_name_command = "backend";
+    let backend_add_name_command = "name";
+    let list_command = "list";
+    let status_command = "status";
+    let capture_command = "capture";
+    let capture_port_command = "port";
+    let capture_once_command = "once";
+    let serve_command = "serve";
+    let serve_port_command = "port";
+    let import_command = "import";
+    let import_file_command = "file";
+    let import_format_command = "format";
+    let import_dry_run_command = "dry-run";
+    let export_command = "export";
+    let export_format_command = "format";
+    let export_output_command = "output";
 
-    // TODO: Add configure subcommand
-    let mut keeper = LinkKeeper::new();
+    let importer_names = import::importers()
+        .iter()
+        .map(|importer| importer.name())
+        .collect::<Vec<&str>>();
 
-    keeper.register_backends(&get_old_backends).unwrap();
+    let exporter_names = export::exporters()
+        .iter()
+        .map(|exporter| exporter.name())
+        .collect::<Vec<&str>>();
 
-    let matches = App::new(PKG_NAME.unwrap_or_else(|| "link-keeper"))
-        .version(PKG_VERSION.unwrap_or_else(|| "0.1.0"))
+    let backend_names = backend_registry().names();
+    let backend_flags = backend_flags(&backend_registry());
+
+    let matches = App::new(PKG_NAME.unwrap_or("link-keeper"))
+        .version(PKG_VERSION.unwrap_or("0.1.0"))
         .author("Jesper Håkansson. <jesper@jesperh.se>")
         .about("Keep your links stored.")
+        .arg(
+            Arg::with_name(set_command)
+                .short("c")
+                .long("set")
+                .value_name("KEY=VALUE")
+                .multiple(true)
+                .number_of_values(1)
+                .global(true)
+                .help("Override a setting for this run"),
+        )
+        .arg(
+            Arg::with_name(store_command)
+                .long("store")
+                .value_name("FILE")
+                .global(true)
+                .help("Use the store in this file for this run"),
+        )
+        .arg(
+            Arg::with_name(json_command)
+                .long("json")
+                .global(true)
+                .help("Print JSON for add, list, search, next, stats, status, check and backend list"),
+        )
+        .arg(
+            Arg::with_name(yes_command)
+                .short("y")
+                .long("yes")
+                .global(true)
+                .help("Answer yes instead of asking, e.g. to merge a link that's already stored"),
+        )
+        .arg(
+            Arg::with_name(verbose_command)
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .global(true)
+                .help("Log more, repeat for even more"),
+        )
+        .arg(
+            Arg::with_name(quiet_command)
+                .short("q")
+                .long("quiet")
+                .multiple(true)
+                .global(true)
+                .help("Log only errors, twice for nothing at all"),
+        )
+        .arg(
+            Arg::with_name(log_format_command)
+                .long("log-format")
+                .takes_value(true)
+                .possible_values(&logger::Format::names())
+                .default_value("text")
+                .global(true)
+                .help("How to write the log to stderr"),
+        )
+        .subcommand(
+            SubCommand::with_name(migrate_command)
+                .arg(
+                    Arg::with_name(migrate_check_command)
+                        .long("check")
+                        .help("Only show what would change"),
+                )
+                .about("Upgrade configuration files and the store written by older versions"),
+        )
+        .subcommand(
+            SubCommand::with_name(config_command)
+                .about("Show and change the configuration")
+                .subcommand(
+                    SubCommand::with_name(config_get_command)
+                        .arg(Arg::with_name(config_key_command).required(true))
+                        .arg(
+                            Arg::with_name(config_origin_command)
+                                .long("origin")
+                                .help("Show where the value comes from"),
+                        )
+                        .about("Print the value of a setting"),
+                )
+                .subcommand(
+                    SubCommand::with_name(config_set_command)
+                        .arg(Arg::with_name(config_key_command).required(true))
+                        .arg(Arg::with_name(config_value_command).required(true))
+                        .arg(
+                            Arg::with_name(config_project_command)
+                                .long("project")
+                                .help("Change the project's .link-keeper.toml instead of the global config"),
+                        )
+                        .about("Change a setting in a configuration file"),
+                )
+                .subcommand(
+                    SubCommand::with_name(config_show_command)
+                        .arg(
+                            Arg::with_name(config_origin_command)
+                                .long("origin")
+                                .help("Show where every value comes from"),
+                        )
+                        .about("Print every setting in effect"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name(backend_command)
                 .about("Backend subcommand, add and remove backends")
                 .subcommand(
                     SubCommand::with_name(backend_add_command)
+                        .arg(
+                            Arg::with_name(backend_add_name_command)
+                                .possible_values(&backend_names)
+                                .help("The backend to add instead of choosing one"),
+                        )
+                        .arg(
+                            Arg::with_name(backend_name_command)
+                                .long("backend")
+                                .takes_value(true)
+                                .possible_values(&backend_names)
+                                .conflicts_with(backend_add_name_command)
+                                .help("The backend to add instead of choosing one"),
+                        )
+                        .args(
+                            &backend_flags
+                                .iter()
+                                .map(|(flag, help, kind)| {
+                                    let arg = Arg::with_name(flag).long(flag).help(help);
+
+                                    match kind {
+                                        FieldKind::Toggle => arg,
+                                        FieldKind::Secret => {
+                                            arg.takes_value(true).value_name("VARIABLE")
+                                        }
+                                        FieldKind::Text | FieldKind::Path => {
+                                            arg.takes_value(true)
+                                        }
+                                    }
+                                })
+                                .collect::<Vec<Arg>>(),
+                        )
                         .about("Add a backend to store links at"),
+                )
+                .subcommand(
+                    SubCommand::with_name(backend_list_command)
+                        .about("List the backends and whether they're activated"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name(list_command)
+                .args(&query_args())
+                .about("List the stored links"),
+        )
+        .subcommand(
+            SubCommand::with_name(capture_command)
+                .arg(
+                    Arg::with_name(capture_port_command)
+                        .long("port")
+                        .takes_value(true)
+                        .default_value(capture::DEFAULT_PORT)
+                        .help("The port to listen on"),
+                )
+                .arg(
+                    Arg::with_name(capture_once_command)
+                        .long("once")
+                        .help("Stop after the first stored link"),
+                )
+                .about("Store the pages sent by a bookmarklet in the browser"),
+        )
+        .subcommand(
+            SubCommand::with_name(serve_command)
+                .arg(
+                    Arg::with_name(serve_port_command)
+                        .long("port")
+                        .takes_value(true)
+                        .default_value(api::DEFAULT_PORT)
+                        .help("The port to listen on, any free one with 0"),
+                )
+                .about("Serve the links to other programs over a local HTTP API"),
+        )
+        .subcommand(
+            SubCommand::with_name(status_command)
+                .about("Show where the links are stored and which backends are activated"),
+        )
         .subcommand(
             SubCommand::with_name(add_command)
                 .arg(
                     Arg::with_name(add_link_command)
-                        .help("The link to be stored. For example: https://github.com/drager/link-keeper")
-                        .required(true),
+                        .help("The link to be stored. For example: https://github.com/drager/link-keeper. Use - to store the links in stdin")
+                        .required_unless_one(&[add_file_command, add_clipboard_command]),
+                )
+                .arg(
+                    Arg::with_name(add_clipboard_command)
+                        .long("clipboard")
+                        .conflicts_with_all(&[add_link_command, add_file_command])
+                        .help("Store the links on the clipboard, read with wl-paste, xclip or xsel"),
+                )
+                .arg(
+                    Arg::with_name(add_file_command)
+                        .long("file")
+                        .takes_value(true)
+                        .conflicts_with(add_link_command)
+                        .help("Store the links in this file, they're picked out of any text around them"),
+                )
+                .arg(
+                    Arg::with_name(add_fetch_command)
+                        .long("fetch")
+                        .help("Fetch the page and store its title, description and favicon"),
+                )
+                .arg(
+                    Arg::with_name(add_archive_command)
+                        .long("archive")
+                        .help("Store a copy of the page to read it offline"),
+                )
+                .arg(
+                    Arg::with_name(add_category_command)
+                        .long("category")
+                        .takes_value(true)
+                        .help("The category to store the link in"),
+                )
+                .arg(
+                    Arg::with_name(add_tag_command)
+                        .long("tag")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Tag the link, can be given multiple times. Nest tags with /, e.g. lang/rust"),
+                )
+                .arg(
+                    Arg::with_name(add_no_suggest_command)
+                        .long("no-suggest")
+                        .help("Don't suggest tags and a category for the link"),
                 )
                 .about("Store a link at the given backend"),
         )
-        .get_matches();
+        .subcommand(
+            SubCommand::with_name(open_command)
+                .arg(
+                    Arg::with_name(open_id_command)
+                        .help("The id of the link, or the start of it")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(open_archived_command)
+                        .long("archived")
+                        .help("Open the archived copy instead of the live page"),
+                )
+                .arg(
+                    Arg::with_name(open_text_command)
+                        .long("text")
+                        .requires(open_archived_command)
+                        .help("Print the extracted text of the archived copy"),
+                )
+                .about("Open a stored link in the browser"),
+        )
+        .subcommand(
+            SubCommand::with_name(tags_command)
+                .about("List, rename, merge and delete tags")
+                .subcommand(
+                    SubCommand::with_name(tags_list_command)
+                        .about("List every tag with how many links have it"),
+                )
+                .subcommand(
+                    SubCommand::with_name(tags_rename_command)
+                        .arg(Arg::with_name("from").required(true))
+                        .arg(Arg::with_name("to").required(true))
+                        .about("Rename a tag and the tags nested in it"),
+                )
+                .subcommand(
+                    SubCommand::with_name(tags_merge_command)
+                        .arg(
+                            Arg::with_name("from")
+                                .multiple(true)
+                                .required(true)
+                                .help("The tags to merge"),
+                        )
+                        .arg(
+                            Arg::with_name("into")
+                                .long("into")
+                                .takes_value(true)
+                                .required(true)
+                                .help("The tag to merge them into"),
+                        )
+                        .about("Replace tags with another tag"),
+                )
+                .subcommand(
+                    SubCommand::with_name(tags_delete_command)
+                        .arg(Arg::with_name("tag").required(true))
+                        .about("Remove a tag and the tags nested in it from every link"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(next_command)
+                .args(&query_args())
+                .arg(
+                    Arg::with_name(next_random_command)
+                        .long("random")
+                        .help("Pick a random unread link instead of the oldest"),
+                )
+                .arg(
+                    Arg::with_name(next_open_command)
+                        .long("open")
+                        .help("Open the link and mark it as being read"),
+                )
+                .about("Pick the next unread link to read"),
+        )
+        .subcommand(
+            SubCommand::with_name(mark_command)
+                .arg(
+                    Arg::with_name(mark_state_command)
+                        .possible_values(&["unread", "reading", "read", "archived"])
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(mark_ids_command)
+                        .help("The ids of the links, or the start of them")
+                        .multiple(true)
+                        .required(true),
+                )
+                .about("Set the reading state of links"),
+        )
+        .subcommand(SubCommand::with_name(stats_command).about("Show reading statistics"))
+        .subcommand(
+            SubCommand::with_name(note_command)
+                .arg(
+                    Arg::with_name(note_id_command)
+                        .help("The id of the link, or the start of it")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(note_highlight_command)
+                        .long("highlight")
+                        .help("Add a quote from the page instead of a note"),
+                )
+                .about("Write a note about a link in $EDITOR"),
+        )
+        .subcommand(
+            SubCommand::with_name(tui_command).about("Browse the stored links in the terminal"),
+        )
+        .subcommand(
+            SubCommand::with_name(search_command)
+                .args(&query_args())
+                .arg(
+                    Arg::with_name(search_terms_command)
+                        .help("What to search for. Quote phrases and filter with tag:, category:, domain: or state:")
+                        .multiple(true)
+                        .required(true),
+                )
+                .about("Search the stored links and their archived pages"),
+        )
+        .subcommand(
+            SubCommand::with_name(check_command)
+                .args(&query_args())
+                .arg(
+                    Arg::with_name(check_parallel_command)
+                        .long("parallel")
+                        .takes_value(true)
+                        .default_value("8")
+                        .help("How many links to check at the same time"),
+                )
+                .arg(
+                    Arg::with_name(check_timeout_command)
+                        .long("timeout")
+                        .takes_value(true)
+                        .default_value("10")
+                        .help("Seconds to wait for each link"),
+                )
+                .arg(
+                    Arg::with_name(check_fix_redirects_command)
+                        .long("fix-redirects")
+                        .help("Replace permanently redirected links with where they lead"),
+                )
+                .about("Check that the stored links still work"),
+        )
+        .subcommand(
+            SubCommand::with_name(refresh_command)
+                .args(&query_args())
+                .about("Fetch the title, description and favicon of stored links again"),
+        )
+        .subcommand(
+            SubCommand::with_name(import_command)
+                .arg(
+                    Arg::with_name(import_file_command)
+                        .help("The file to import, e.g. a bookmark file exported by a browser")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(import_format_command)
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&importer_names)
+                        .default_value("netscape")
+                        .help("The format of the file"),
+                )
+                .arg(
+                    Arg::with_name(import_dry_run_command)
+                        .long("dry-run")
+                        .help("Show what would be imported without storing anything"),
+                )
+                .about("Import links from a bookmark file or another service"),
+        )
+        .subcommand(
+            SubCommand::with_name(export_command)
+                .arg(
+                    Arg::with_name(export_format_command)
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&exporter_names)
+                        .default_value("html")
+                        .help("The format to export to"),
+                )
+                .args(&query_args())
+                .arg(
+                    Arg::with_name(export_output_command)
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .help("File to write to instead of stdout"),
+                )
+                .about("Export the stored links"),
+        )
+        .get_matches_safe()
+        .unwrap_or_else(|err| fail_usage(err));
 
-    if let Some(backend_matches) = matches.subcommand_matches(backend_command) {
-        if let Some(_backend_add_matches) = backend_matches.subcommand_matches(backend_add_command)
-        {
-            let available_backends = keeper.get_available_backends();
-            let selected_backend = AvailableBackend::from(
-                Select::new()
-                    .with_prompt("Choose to add one of the following backends")
-                    .items(&available_backends)
-                    .default(0)
-                    .interact()?,
-            );
+    let json = matches.is_present(json_command);
 
-            match selected_backend {
-                AvailableBackend::Github => {
-                    let access_token: String = PasswordInput::new()
-                        .with_prompt(&format!("Your {} access token", selected_backend))
-                        .interact()?;
-
-                    keeper
-                        .add_backend(Box::new(Github {
-                            config: GithubConfig {
-                                access_token: AccessToken(access_token),
-                            },
-                        }))
-                        .unwrap();
-                }
-                AvailableBackend::Git => {
-                    let current_dir: String =
-                        env::current_dir().unwrap().to_str().unwrap().to_owned();
-                    let repository_path: String = Input::new()
-                        .with_prompt(&format!(
-                            "In what repository should the links be stored? (default: {:?})",
-                            current_dir
-                        ))
-                        .default(current_dir)
-                        .show_default(false)
-                        .interact()?;
-
-                    keeper
-                        .add_backend(Box::new(Git {
-                            config: GitConfig {
-                                repository_path: PathBuf::from(repository_path),
-                            },
-                        }))
-                        .unwrap();
-                }
-            }
-        }
-    }
-
-    let _ = matches
-        .subcommand_matches(add_command)
-        .and_then(|add_matches| {
-            add_matches.value_of(add_link_command).map(|new_link| {
-                if keeper.get_activated_backends().is_empty() {
-                    eprintln!(
-                        "{}{}",
-                        style("warning").yellow().bold(),
-                        style(format!(": No backend activated...\n")).bold(),
-                    );
+    logger::init(
+        logger::level(
+            matches.occurrences_of(verbose_command),
+            matches.occurrences_of(quiet_command),
+        ),
+        logger::Format::from_name(matches.value_of(log_format_command).unwrap())
+            .unwrap_or(logger::Format::Text),
+    )
+    .unwrap_or_else(|err| output::fail(json, Exit::Error, &format!("Can't log: {}", err)));
+
+    let yes = matches.is_present(yes_command);
+    let mut backend_failed = false;
+
+    let mut config = output::or_fail(json, Config::load(&config_overrides(&matches)));
+
+    if let Some(config_matches) = matches.subcommand_matches(config_command) {
+        if let Some(get_matches) = config_matches.subcommand_matches(config_get_command) {
+            let key = get_matches.value_of(config_key_command).unwrap();
+
+            match config.get(key) {
+                Some((value, origin)) => {
+                    let value = match value {
+                        toml::Value::String(string) => string,
+                        value => value.to_string(),
+                    };
+
+                    if get_matches.is_present(config_origin_command) {
+                        println!("{}\t{}", value, style(origin).dim());
+                    } else {
+                        println!("{}", value);
+                    }
+                }
+                None => output::fail(json, Exit::Error, &format!("{} is not set", key)),
+            }
+        }
+
+        if let Some(set_matches) = config_matches.subcommand_matches(config_set_command) {
+            let key = set_matches.value_of(config_key_command).unwrap();
+            let value = config::parse_value(set_matches.value_of(config_value_command).unwrap());
+
+            let path = config
+                .set(key, value, set_matches.is_present(config_project_command))
+                .unwrap_or_else(|err| {
+                    output::fail(json, Exit::Error, &format!("Can't set {}: {}", key, err))
+                });
+
+            println!(
+                "{}: {} in {}",
+                style("Set").green().bold(),
+                key,
+                path.display()
+            );
+        }
+
+        if let Some(show_matches) = config_matches.subcommand_matches(config_show_command) {
+            for (key, value, origin) in config.show() {
+                if show_matches.is_present(config_origin_command) {
+                    println!("{} = {}\t{}", key, value, style(origin).dim());
+                } else {
+                    println!("{} = {}", key, value);
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    if let Some(migrate_matches) = matches.subcommand_matches(migrate_command) {
+        if migrate_matches.is_present(migrate_check_command) {
+            let store = output::or_fail(json, config.settings()).store_file();
+            let migrations = output::or_fail(json, migrate::migrate(&mut config, &store, true));
+
+            for migration in &migrations {
+                println!("{}: {}", style("Would migrate").yellow().bold(), migration);
+
+                for change in &migration.changes {
+                    println!("  - {}", change);
+                }
+            }
+
+            if migrations.is_empty() {
+                println!("Everything is up to date");
+            }
+
+            return Ok(());
+        }
+    }
+
+    // The bookmarklet and the integrations keep the token, so it's only
+    // created once
+    let token_key = if matches.subcommand_matches(capture_command).is_some() {
+        Some(capture::TOKEN_KEY)
+    } else if matches.subcommand_matches(serve_command).is_some() {
+        Some(api::TOKEN_KEY)
+    } else {
+        None
+    };
+
+    if let Some(token_key) = token_key.filter(|key| config.get(key).is_none()) {
+        let token = output::or_fail(json, capture::new_token());
+
+        if let Err(err) = config.set(token_key, toml::Value::String(token), false) {
+            output::fail(
+                json,
+                Exit::Error,
+                &format!("Can't keep the token in {}: {}", token_key, err),
+            );
+        }
+    }
+
+    let mut keeper = output::or_fail(
+        json,
+        LinkKeeperBuilder::new()
+            .config(config)
+            .backends(backend_registry())
+            .migrate(true)
+            .build(),
+    );
+
+    for migration in keeper.migrations() {
+        eprintln!("{}: {}", style("Migrated").green().bold(), migration);
+
+        if let Some(backup) = &migration.backup {
+            eprintln!("  the old file is kept at {}", backup.display());
+        }
+    }
+
+    if matches.subcommand_matches(migrate_command).is_some() && keeper.migrations().is_empty() {
+        println!("Everything is up to date");
+    }
+
+    if let Some(backend_matches) = matches.subcommand_matches(backend_command) {
+        if let Some(backend_add_matches) = backend_matches.subcommand_matches(backend_add_command) {
+            let interactive = !yes && console::user_attended();
+            let available_backends = keeper.get_available_backends();
+            let selected_backend = match backend_add_matches
+                .value_of(backend_add_name_command)
+                .or_else(|| backend_add_matches.value_of(backend_name_command))
+            {
+                Some(name) => name,
+                None if interactive => {
+                    &available_backends[Select::new()
+                        .with_prompt("Choose to add one of the following backends")
+                        .items(&available_backends)
+                        .default(0)
+                        .interact()?]
+                }
+                None => output::fail(json, Exit::Usage, "Name the backend to add"),
+            };
+
+            let config_schema = keeper
+                .backend_registry()
+                .get(selected_backend)
+                .map(|factory| factory.config_schema())
+                .unwrap_or_default();
+
+            let backend_config = output::or_usage(
+                json,
+                backend_config(
+                    selected_backend,
+                    &config_schema,
+                    backend_add_matches,
+                    &backend_flags,
+                    interactive,
+                ),
+            );
+            let backend = keeper
+                .backend_registry()
+                .create(selected_backend, &backend_config)
+                .unwrap_or_else(|err| output::fail(json, Exit::Error, &err.to_string()));
+
+            output::or_fail(json, keeper.add_backend(backend));
+
+            if json {
+                output::print(&serde_json::json!({ "name": selected_backend, "activated": true }));
+            } else {
+                println!(
+                    "{}: {}",
+                    style("Added backend").green().bold(),
+                    selected_backend
+                );
+            }
+        }
+
+        if backend_matches
+            .subcommand_matches(backend_list_command)
+            .is_some()
+        {
+            let backends = backend_states(&keeper);
+
+            if json {
+                output::print(&serde_json::Value::Array(
+                    backends
+                        .iter()
+                        .map(|(name, activated)| {
+                            serde_json::json!({ "name": name, "activated": activated })
+                        })
+                        .collect(),
+                ));
+            } else {
+                for (name, activated) in &backends {
+                    if *activated {
+                        println!("{} {}", name, style("activated").green());
+                    } else {
+                        println!("{}", name);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(list_matches) = matches.subcommand_matches(list_command) {
+        let query = output::or_usage(json, query_from_matches(list_matches));
+        let links = output::or_fail(json, keeper.query(&query));
+
+        if json {
+            output::print(&output::links(&links));
+        } else {
+            for link in &links {
+                print_link(link);
+            }
+        }
+    }
+
+    if let Some(capture_matches) = matches.subcommand_matches(capture_command) {
+        let port = value_t!(capture_matches, capture_port_command, u16)
+            .unwrap_or_else(|err| fail_usage(err));
+        // Created above when missing
+        let token = match keeper.config().get(capture::TOKEN_KEY) {
+            Some((toml::Value::String(token), _)) => token,
+            _ => output::fail(json, Exit::Error, "capture.token should be a string"),
+        };
+
+        eprintln!(
+            "{} on http://127.0.0.1:{}, add this bookmarklet to the browser's bookmarks:",
+            style("Listening").green().bold(),
+            port
+        );
+        println!("{}", capture::bookmarklet(port, &token));
+
+        capture::serve(
+            &keeper,
+            port,
+            &token,
+            capture_matches.is_present(capture_once_command),
+            |link, errors| {
+                print_backend_errors(link.link(), errors);
+                print_added(link.link());
+            },
+        )
+        .unwrap_or_else(|err| output::fail(json, Exit::Error, &err.to_string()));
+    }
+
+    if let Some(serve_matches) = matches.subcommand_matches(serve_command) {
+        let port =
+            value_t!(serve_matches, serve_port_command, u16).unwrap_or_else(|err| fail_usage(err));
+        // Created above when missing
+        let token = match keeper.config().get(api::TOKEN_KEY) {
+            Some((toml::Value::String(token), _)) => token,
+            _ => output::fail(json, Exit::Error, "api.token should be a string"),
+        };
+
+        let server =
+            api::bind(port).unwrap_or_else(|err| output::fail(json, Exit::Error, &err.to_string()));
+        let url = match server.server_addr().to_ip() {
+            Some(address) => format!("http://{}", address),
+            None => format!("http://127.0.0.1:{}", port),
+        };
+
+        // Programs starting the server read where it listens and the token
+        if json {
+            output::print(&serde_json::json!({ "url": url, "token": token }));
+        } else {
+            eprintln!(
+                "{} on {}, send the token in {} as a bearer token",
+                style("Listening").green().bold(),
+                url,
+                api::TOKEN_KEY
+            );
+        }
+
+        api::serve(&keeper, &server, &token)
+            .unwrap_or_else(|err| output::fail(json, Exit::Error, &err.to_string()));
+    }
+
+    if matches.subcommand_matches(status_command).is_some() {
+        let store = output::or_fail(json, keeper.config().settings()).store_file();
+        let links = output::or_fail(json, keeper.get_links()).len();
+        let stats = output::or_fail(json, keeper.reading_stats());
+        let config_files = keeper
+            .config()
+            .global_path()
+            .into_iter()
+            .chain(keeper.config().project_path())
+            .filter(|path| path.exists())
+            .map(|path| path.display().to_string())
+            .collect::<Vec<String>>();
+        let backends = backend_states(&keeper)
+            .into_iter()
+            .filter(|(_, activated)| *activated)
+            .map(|(name, _)| name)
+            .collect::<Vec<String>>();
+
+        if json {
+            output::print(&serde_json::json!({
+                "store": store.display().to_string(),
+                "links": links,
+                "config": config_files,
+                "backends": backends,
+                "unread": stats.unread,
+                "reading": stats.reading,
+                "read": stats.read,
+                "archived": stats.archived,
+            }));
+        } else {
+            println!("Store: {} ({} links)", store.display(), links);
+            println!(
+                "Config: {}",
+                if config_files.is_empty() {
+                    "none".to_owned()
+                } else {
+                    config_files.join(", ")
                 }
+            );
+            println!(
+                "Backends: {}",
+                if backends.is_empty() {
+                    "none".to_owned()
+                } else {
+                    backends.join(", ")
+                }
+            );
+            println!(
+                "{} unread, {} reading, {} read, {} archived",
+                stats.unread, stats.reading, stats.read, stats.archived
+            );
+        }
+    }
+
+    if let Some(import_matches) = matches.subcommand_matches(import_command) {
+        if let Some(file) = import_matches.value_of(import_file_command) {
+            let dry_run = import_matches.is_present(import_dry_run_command);
+            let importer = import_matches
+                .value_of(import_format_command)
+                .and_then(import::importer)
+                .unwrap_or_else(|| Box::new(import::Netscape));
+
+            let report = output::or_fail(
+                json,
+                keeper.import(importer.as_ref(), Path::new(file), dry_run),
+            );
+            backend_failed |= !report.backend_errors.is_empty();
+
+            if json {
+                output::print(&output::import_report(&report, dry_run));
+            } else if dry_run {
+                for (action, link) in &report.entries {
+                    let action = match action {
+                        ImportAction::Added => style("add").green(),
+                        ImportAction::Merged => style("merge").yellow(),
+                        ImportAction::Skipped => style("skip").dim(),
+                    };
+
+                    println!("{:>5} {}", action, link.link());
+                }
+            }
+
+            if !json {
+                print_backend_errors(
+                    &format!("{} links", report.added + report.merged),
+                    &report.backend_errors,
+                );
+
+                println!(
+                    "{}: {} added, {} skipped, {} merged",
+                    style(if dry_run { "Would import" } else { "Imported" })
+                        .green()
+                        .bold(),
+                    report.added,
+                    report.skipped,
+                    report.merged
+                );
+            }
+        }
+    }
 
-                // TODO: Real error handling
-                if keeper.link_already_exists(new_link).unwrap() {
+    if let Some(export_matches) = matches.subcommand_matches(export_command) {
+        let exporter = export_matches
+            .value_of(export_format_command)
+            .and_then(export::exporter)
+            .unwrap_or_else(|| Box::new(export::Netscape));
+
+        let query = output::or_usage(json, query_from_matches(export_matches));
+        let exported = output::or_fail(json, keeper.export(exporter.as_ref(), &query));
+
+        match export_matches.value_of(export_output_command) {
+            Some(output) => output::or_fail(json, fs::write(output, exported)),
+            None => print!("{}", exported),
+        }
+    }
+
+    if let Some(check_matches) = matches.subcommand_matches(check_command) {
+        let checker = Checker::new(Box::new(UreqClient::new()))
+            .parallelism(
+                value_t!(check_matches, check_parallel_command, usize)
+                    .unwrap_or_else(|err| fail_usage(err)),
+            )
+            .timeout(Duration::from_secs(
+                value_t!(check_matches, check_timeout_command, u64)
+                    .unwrap_or_else(|err| fail_usage(err)),
+            ));
+        let query = output::or_usage(json, query_from_matches(check_matches));
+
+        let report = output::or_fail(
+            json,
+            keeper.check(
+                &checker,
+                &query,
+                check_matches.is_present(check_fix_redirects_command),
+            ),
+        );
+        backend_failed |= !report.backend_errors.is_empty();
+
+        if json {
+            output::print(&output::check_report(&report));
+        }
+
+        for result in report.results.iter().filter(|_| !json) {
+            let status = result
+                .status
+                .status
+                .map(|status| status.to_string())
+                .unwrap_or_default();
+
+            match result.status.outcome() {
+                Outcome::Ok => {}
+                Outcome::Redirected => {
+                    let chain = result
+                        .status
+                        .redirects
+                        .iter()
+                        .map(|redirect| format!(" -{}-> {}", redirect.status, redirect.to))
+                        .collect::<String>();
+
+                    println!("{} {}{}", style("redirect").yellow(), result.link, chain);
+                }
+                Outcome::NotFound | Outcome::Broken => {
+                    println!("{} {} {}", style("broken").red(), status, result.link);
+                }
+                Outcome::Tls | Outcome::Unreachable => {
+                    let error = result
+                        .status
+                        .error
+                        .as_ref()
+                        .map(|error| error.to_string())
+                        .unwrap_or_default();
+
+                    println!("{} {}: {}", style("failed").red(), result.link, error);
+                }
+            }
+
+            if let Some(rewritten_to) = &result.rewritten_to {
+                println!(
+                    "{} {} -> {}",
+                    style("rewrote").green(),
+                    result.link,
+                    rewritten_to
+                );
+            }
+        }
+
+        let count = |outcomes: &[Outcome]| {
+            report
+                .results
+                .iter()
+                .filter(|result| outcomes.contains(&result.status.outcome()))
+                .count()
+        };
+
+        if !json {
+            for (link, error) in &report.backend_errors {
+                eprintln!(
+                    "{}{}",
+                    style("warning").yellow().bold(),
+                    style(format!(": {}: {}", link, error)).bold(),
+                );
+            }
+
+            println!(
+                "{}: {} links, {} ok, {} redirected, {} broken, {} failed",
+                style("Checked").green().bold(),
+                report.results.len(),
+                count(&[Outcome::Ok]),
+                count(&[Outcome::Redirected]),
+                count(&[Outcome::NotFound, Outcome::Broken]),
+                count(&[Outcome::Tls, Outcome::Unreachable]),
+            );
+        }
+    }
+
+    if let Some(refresh_matches) = matches.subcommand_matches(refresh_command) {
+        keeper.set_enricher(Enricher::new(Box::new(UreqClient::new())));
+
+        let query = output::or_usage(json, query_from_matches(refresh_matches));
+        let report = output::or_fail(json, keeper.refresh(&query));
+
+        if json {
+            output::print(&serde_json::json!({
+                "refreshed": report.refreshed,
+                "failed": output::failures(&report.failed),
+            }));
+        } else {
+            for (link, error) in &report.failed {
+                eprintln!(
+                    "{}{}",
+                    style("warning").yellow().bold(),
+                    style(format!(": {}: {}", link, error)).bold(),
+                );
+            }
+
+            println!(
+                "{}: {} links",
+                style("Refreshed").green().bold(),
+                report.refreshed
+            );
+        }
+    }
+
+    if let Some(tags_matches) = matches.subcommand_matches(tags_command) {
+        if tags_matches.subcommand_matches(tags_list_command).is_some() {
+            let counts = output::or_fail(json, keeper.tag_counts());
+
+            if json {
+                output::print(&output::tag_counts(&counts));
+            }
+
+            for (tag, count) in counts.iter().filter(|_| !json) {
+                let depth = tag.matches(tags::TAG_SEPARATOR).count();
+                let name = tag.rsplit(tags::TAG_SEPARATOR).next().unwrap_or(tag);
+
+                println!("{}{} {}", "  ".repeat(depth), style(name).bold(), count);
+            }
+        }
+
+        let report = if let Some(rename_matches) =
+            tags_matches.subcommand_matches(tags_rename_command)
+        {
+            Some(output::or_fail(
+                json,
+                keeper.rename_tag(
+                    rename_matches.value_of("from").unwrap(),
+                    rename_matches.value_of("to").unwrap(),
+                ),
+            ))
+        } else if let Some(merge_matches) = tags_matches.subcommand_matches(tags_merge_command) {
+            Some(output::or_fail(
+                json,
+                keeper.merge_tags(
+                    &merge_matches
+                        .values_of("from")
+                        .unwrap()
+                        .collect::<Vec<&str>>(),
+                    merge_matches.value_of("into").unwrap(),
+                ),
+            ))
+        } else if let Some(delete_matches) = tags_matches.subcommand_matches(tags_delete_command) {
+            let tag = delete_matches.value_of("tag").unwrap();
+
+            if confirm(&format!("Remove {} from every link?", tag), yes)? {
+                Some(output::or_fail(json, keeper.delete_tag(tag)))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        if let Some(report) = report {
+            backend_failed |= !report.failed.is_empty();
+
+            if json {
+                output::print(&serde_json::json!({
+                    "updated": report.updated,
+                    "failed": output::failures(&report.failed),
+                }));
+            } else {
+                for (link, error) in &report.failed {
                     eprintln!(
                         "{}{}",
                         style("warning").yellow().bold(),
-                        style(format!(": Link already exists\n")).bold(),
+                        style(format!(": {}: {}", link, error)).bold(),
                     );
+                }
 
-                    if Confirmation::new()
-                        .with_text("Do you want to add it anyway?")
-                        .interact()
-                        .unwrap()
-                    {
-                        keeper.add(new_link, None).unwrap();
-                    }
+                println!(
+                    "{}: {} links",
+                    style("Updated").green().bold(),
+                    report.updated
+                );
+            }
+        }
+    }
+
+    if let Some(next_matches) = matches.subcommand_matches(next_command) {
+        let pick = if next_matches.is_present(next_random_command) {
+            Pick::Random
+        } else {
+            Pick::Oldest
+        };
+
+        let query = output::or_usage(json, query_from_matches(next_matches));
+
+        let next = output::or_fail(json, keeper.next(&query, pick));
+
+        if json {
+            output::print(&next.as_ref().map(output::link).unwrap_or_default());
+        }
+
+        match next {
+            Some(link) => {
+                if !json {
+                    print_link(&link);
+                }
+
+                if next_matches.is_present(next_open_command) {
+                    output::or_fail(json, open::that(link.link()));
+
+                    let errors =
+                        output::or_fail(json, keeper.set_state(&link.id(), ReadState::Reading));
+                    backend_failed |= !errors.is_empty();
+                    print_backend_errors(link.link(), &errors);
+                }
+            }
+            None if !json => println!("Nothing left to read"),
+            None => {}
+        }
+    }
+
+    if let Some(mark_matches) = matches.subcommand_matches(mark_command) {
+        let state = output::or_usage(
+            json,
+            mark_matches
+                .value_of(mark_state_command)
+                .unwrap()
+                .parse::<ReadState>(),
+        );
+        let mut marked = vec![];
+        let mut all_errors = vec![];
+
+        for id in mark_matches.values_of(mark_ids_command).unwrap() {
+            let link = find_link(&keeper, id, json);
+            let errors = output::or_fail(json, keeper.set_state(&link.id(), state));
+
+            backend_failed |= !errors.is_empty();
+
+            if json {
+                marked.push(find_link(&keeper, &link.id(), json));
+                all_errors.extend(errors);
+            } else {
+                print_backend_errors(link.link(), &errors);
+                println!(
+                    "{}: {} as {}",
+                    style("Marked").green().bold(),
+                    link.link(),
+                    state
+                );
+            }
+        }
+
+        if json {
+            output::print(&serde_json::json!({
+                "links": output::links(&marked),
+                "backend_errors": output::errors(&all_errors),
+            }));
+        }
+    }
+
+    if let Some(note_matches) = matches.subcommand_matches(note_command) {
+        let link = find_link(
+            &keeper,
+            note_matches.value_of(note_id_command).unwrap(),
+            json,
+        );
+        let highlight = note_matches.is_present(note_highlight_command);
+
+        match output::or_fail(json, edit_note(&link, highlight)) {
+            Some(text) => {
+                let annotation = if highlight {
+                    Annotation::highlight(&text)
+                } else {
+                    Annotation::note(&text)
+                };
+                let errors = output::or_fail(json, keeper.annotate(&link.id(), annotation));
+
+                backend_failed |= !errors.is_empty();
+
+                if json {
+                    output::print(&serde_json::json!({
+                        "link": output::link(&find_link(&keeper, &link.id(), json)),
+                        "noted": true,
+                        "backend_errors": output::errors(&errors),
+                    }));
                 } else {
-                    keeper.add(new_link, None).unwrap();
+                    print_backend_errors(link.link(), &errors);
+                    println!("{}: {}", style("Noted").green().bold(), link.link());
                 }
+            }
+            None if json => output::print(&serde_json::json!({
+                "link": output::link(&link),
+                "noted": false,
+                "backend_errors": [],
+            })),
+            None => println!("Nothing written, no note added"),
+        }
+    }
+
+    if matches.subcommand_matches(stats_command).is_some() {
+        let stats = output::or_fail(json, keeper.reading_stats());
+
+        if json {
+            output::print(&output::stats(&stats));
+        } else {
+            println!(
+                "{} unread, {} reading, {} read, {} archived",
+                stats.unread, stats.reading, stats.read, stats.archived
+            );
+            println!(
+                "Read {} links in the last week and {} in the last 30 days, {:.1} a week on average",
+                stats.read_last_week, stats.read_last_month, stats.weekly_average
+            );
+
+            if let Some(days) = stats.median_days_to_read {
+                println!("Links are read {:.1} days after being added", days);
+            }
+        }
+    }
+
+    if matches.subcommand_matches(tui_command).is_some() {
+        output::or_fail(json, browser::run(&keeper));
+    }
+
+    if let Some(search_matches) = matches.subcommand_matches(search_command) {
+        let terms = search_matches
+            .values_of(search_terms_command)
+            .map(|terms| {
+                terms
+                    .map(|term| {
+                        // Keep phrases that were quoted on the command line
+                        if term.contains(char::is_whitespace) && !term.contains('"') {
+                            format!("\"{}\"", term)
+                        } else {
+                            term.to_owned()
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ")
             })
-        });
+            .unwrap_or_default();
+
+        let query = output::or_usage(json, query_from_matches(search_matches));
+        let search = output::or_fail(json, Search::parse(&terms, query));
+
+        let results = output::or_fail(json, keeper.search(&search));
+
+        if json {
+            output::print(&output::search_results(&results));
+        } else {
+            for result in &results {
+                print_link(&result.link);
+            }
+        }
+    }
+
+    if let Some(open_matches) = matches.subcommand_matches(open_command) {
+        let link = find_link(
+            &keeper,
+            open_matches.value_of(open_id_command).unwrap(),
+            json,
+        );
+
+        if open_matches.is_present(open_archived_command) {
+            match link.snapshot() {
+                Some(snapshot) if open_matches.is_present(open_text_command) => {
+                    print!(
+                        "{}",
+                        output::or_fail(
+                            json,
+                            fs::read_to_string(snapshot.text_path(&keeper.archive_path()))
+                        )
+                    );
+                }
+                Some(snapshot) => {
+                    output::or_fail(json, open::that(snapshot.html_path(&keeper.archive_path())))
+                }
+                None => output::fail(
+                    json,
+                    Exit::Error,
+                    &format!("{} has not been archived", link.link()),
+                ),
+            }
+        } else {
+            output::or_fail(json, open::that(link.link()));
+        }
+    }
+
+    if matches
+        .subcommand_matches(add_command)
+        .is_some_and(|add_matches| add_matches.is_present(add_fetch_command))
+    {
+        keeper.set_enricher(Enricher::new(Box::new(UreqClient::new())));
+    }
+
+    if matches
+        .subcommand_matches(add_command)
+        .is_some_and(|add_matches| add_matches.is_present(add_archive_command))
+    {
+        keeper.set_archiver(Archiver::new(Box::new(UreqClient::new())));
+    }
+
+    if let Some(add_matches) = matches.subcommand_matches(add_command) {
+        let category = add_matches.value_of(add_category_command);
+        let tags = add_matches
+            .values_of(add_tag_command)
+            .map(|tags| tags.map(|tag| tag.to_owned()).collect::<Vec<String>>())
+            .unwrap_or_default();
+
+        let suggest =
+            !add_matches.is_present(add_no_suggest_command) && !yes && console::user_attended();
+
+        if keeper.get_activated_backends().is_empty() && !json {
+            eprintln!(
+                "{}{}",
+                style("warning").yellow().bold(),
+                style(": No backend activated...\n").bold(),
+            );
+        }
+
+        let mut batch = match (
+            add_matches.value_of(add_file_command),
+            add_matches.value_of(add_link_command),
+        ) {
+            (Some(file), _) => Some(normalize::extract_urls(&output::or_fail(
+                json,
+                fs::read_to_string(file),
+            ))),
+            (None, Some("-")) => {
+                let mut text = String::new();
+                output::or_fail(json, io::stdin().read_to_string(&mut text));
+
+                Some(normalize::extract_urls(&text))
+            }
+            _ if add_matches.is_present(add_clipboard_command) => {
+                let text = clipboard::read()
+                    .unwrap_or_else(|err| output::fail(json, Exit::Error, &err.to_string()));
+
+                Some(normalize::extract_urls(&text))
+            }
+            _ => None,
+        };
+
+        let mut new_link = add_matches
+            .value_of(add_link_command)
+            .map(|new_link| new_link.to_owned());
+
+        // A single copied link is added like one given as an argument
+        if add_matches.is_present(add_clipboard_command)
+            && batch.as_ref().is_some_and(|urls| urls.len() == 1)
+        {
+            new_link = batch.take().and_then(|mut urls| urls.pop());
+        }
+
+        if let Some(urls) = batch {
+            if urls.is_empty() {
+                output::fail(json, Exit::Error, "No links found");
+            }
+
+            let report = output::or_fail(json, keeper.add_urls(&urls, category, &tags));
+            backend_failed |= !report.backend_errors.is_empty();
+
+            if json {
+                output::print(&serde_json::json!({
+                    "added": output::links(&report.added),
+                    "duplicates": output::links(&report.duplicates),
+                    "backend_errors": output::errors(&report.backend_errors),
+                }));
+            } else {
+                for link in &report.duplicates {
+                    eprintln!(
+                        "{}: {}",
+                        style("Skipped duplicate").yellow().bold(),
+                        link.link()
+                    );
+                }
+
+                print_backend_errors(
+                    &format!("{} links", report.added.len()),
+                    &report.backend_errors,
+                );
+
+                for link in &report.added {
+                    print_added(link.link());
+                }
+            }
+        } else {
+            let new_link = new_link.as_deref().unwrap();
+            let exists = output::or_fail(json, keeper.link_already_exists(new_link));
+
+            if exists {
+                if !yes && console::user_attended() {
+                    eprintln!(
+                        "{}{}",
+                        style("warning").yellow().bold(),
+                        style(": Link already exists\n").bold(),
+                    );
+                }
+
+                if !confirm("Do you want to add its tags and details to it?", yes)? {
+                    output::fail(
+                        json,
+                        Exit::Duplicate,
+                        &format!("{} is already stored", new_link),
+                    );
+                }
+            }
+
+            let mut link = keeper.prepare_link(new_link, category, &tags);
+
+            if suggest {
+                link = choose_suggestions(&keeper, link)?;
+            }
+
+            let errors = output::or_fail(json, keeper.add_link(link.clone()));
+            backend_failed |= !errors.is_empty();
+
+            if exists {
+                link = output::or_fail(json, keeper.get_link(&link.id()));
+            }
+
+            if json {
+                output::print(&serde_json::json!({
+                    "link": output::link(&link),
+                    "merged": exists,
+                    "backend_errors": output::errors(&errors),
+                }));
+            } else {
+                print_backend_errors(new_link, &errors);
+
+                if exists {
+                    println!(
+                        "{}: {} {}",
+                        style("Merged").green().bold(),
+                        link.id(),
+                        new_link
+                    );
+                } else {
+                    print_added(new_link);
+                }
+            }
+        }
+    }
+
+    if backend_failed {
+        process::exit(Exit::Backend.code());
+    }
+
     Ok(())
 }
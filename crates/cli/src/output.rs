@@ -0,0 +1,284 @@
+//! What the commands print with `--json`, and the exit codes of the CLI.
+//!
+//! With `--json` a command prints a single JSON document on one line to
+//! stdout. The shapes below are kept stable, fields are only ever added.
+//!
+//! A link:
+//!
+//! ```json
+//! {"id": "1a2b3c4d", "url": "https://example.com", "title": null,
+//!  "category": null, "tags": ["lang/rust"], "state": "unread",
+//!  "added": "2019-03-01T12:00:00+00:00"}
+//! ```
+//!
+//! - `add`: `{"link": <link>, "merged": false, "backend_errors": ["..."]}`,
+//!   `merged` when the link was already stored and merged into it, or with
+//!   `-` and `--file` `{"added": [<link>, ...], "duplicates": [<link>, ...],
+//!   "backend_errors": ["..."]}`
+//! - `list`: `[<link>, ...]`
+//! - `search`: `[{"link": <link>, "score": 1.5}, ...]`
+//! - `next`: `<link>`, or `null` when nothing is left to read
+//! - `stats`: the reading statistics, see `stats`
+//! - `backend list`: `[{"name": "git", "activated": true}, ...]`
+//! - `backend add`: `{"name": "git", "activated": true}`
+//! - `mark`: `{"links": [<link>, ...], "backend_errors": ["..."]}`
+//! - `note`: `{"link": <link>, "noted": true, "backend_errors": ["..."]}`
+//! - `import`: `{"dry_run": false, "added": 1, "skipped": 0, "merged": 0,
+//!   "entries": [{"action": "added", "link": <link>}, ...], "backend_errors":
+//!   ["..."]}`, the action being `added`, `skipped` or `merged`
+//! - `tags list`: `[{"tag": "lang/rust", "count": 2}, ...]`
+//! - `tags rename`, `merge` and `delete`: `{"updated": 2, "failed": [{"url":
+//!   "...", "error": "..."}]}`
+//! - `refresh`: `{"refreshed": 2, "failed": [{"url": "...", "error":
+//!   "..."}]}`
+//! - `check`: `{"results": [<result>, ...], "summary": {"links": 1, "ok": 1,
+//!   "redirected": 0, "broken": 0, "failed": 0}, "backend_errors": [{"url":
+//!   "...", "error": "..."}]}`, a result being
+//!   `{"url": "...", "outcome": "ok", "status": 200, "redirects": [{"status":
+//!   301, "to": "..."}], "error": null, "rewritten_to": null}`
+//! - `status`: `{"store": "...", "links": 3, "config": ["..."], "backends":
+//!   ["git"], "unread": 1, "reading": 1, "read": 1, "archived": 0}`
+//! - `serve`: `{"url": "http://127.0.0.1:7879", "token": "..."}` once it
+//!   listens, see `api` for what it answers with
+//!
+//! Errors are printed to stderr as `{"error": {"code": "not_found",
+//! "message": "..."}}`, the code being the name of the `Exit`: `error`,
+//! `usage`, `duplicate`, `not_found`, `backend` or `ambiguous`.
+
+use console::style;
+use link_keeper::check::Outcome;
+use link_keeper::reading::ReadingStats;
+use link_keeper::search::SearchResult;
+use link_keeper::{CheckReport, CheckResult, ImportAction, ImportReport, Link, LinkError};
+use serde_json::{json, Value};
+use std::process;
+
+/// Why the CLI exits with an error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exit {
+    Error,
+    /// The arguments are invalid
+    Usage,
+    /// The link is already stored and wasn't added again
+    Duplicate,
+    /// No link has the given id
+    NotFound,
+    /// At least one backend failed, the store was changed anyway
+    Backend,
+    /// More than one link has an id starting with the given one
+    Ambiguous,
+}
+
+impl Exit {
+    pub fn code(self) -> i32 {
+        match self {
+            Exit::Error => 1,
+            Exit::Usage => 2,
+            Exit::Duplicate => 3,
+            Exit::NotFound => 4,
+            Exit::Backend => 5,
+            Exit::Ambiguous => 6,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Exit::Error => "error",
+            Exit::Usage => "usage",
+            Exit::Duplicate => "duplicate",
+            Exit::NotFound => "not_found",
+            Exit::Backend => "backend",
+            Exit::Ambiguous => "ambiguous",
+        }
+    }
+
+    /// How to exit because of an error returned by the library
+    pub fn from_error(err: &failure::Error) -> Self {
+        match err.downcast_ref::<LinkError>() {
+            Some(LinkError::NotFound(_)) => Exit::NotFound,
+            Some(LinkError::Ambiguous(_)) => Exit::Ambiguous,
+            None => Exit::Error,
+        }
+    }
+}
+
+/// Report the error and exit
+pub fn fail(json: bool, exit: Exit, message: &str) -> ! {
+    if json {
+        eprintln!(
+            "{}",
+            json!({ "error": { "code": exit.name(), "message": message } })
+        );
+    } else {
+        eprintln!(
+            "{}{}",
+            style("error").red().bold(),
+            style(format!(": {}", message)).bold(),
+        );
+    }
+
+    process::exit(exit.code())
+}
+
+/// The value of the result, or report the error and exit with the code it
+/// calls for
+pub fn or_fail<T, E>(json: bool, result: Result<T, E>) -> T
+where
+    E: Into<failure::Error>,
+{
+    result.unwrap_or_else(|err| {
+        let err = err.into();
+        fail(json, Exit::from_error(&err), &err.to_string())
+    })
+}
+
+/// The value of the result, or report the error as invalid arguments and
+/// exit
+pub fn or_usage<T, E>(json: bool, result: Result<T, E>) -> T
+where
+    E: Into<failure::Error>,
+{
+    result.unwrap_or_else(|err| fail(json, Exit::Usage, &err.into().to_string()))
+}
+
+pub fn print(value: &Value) {
+    println!("{}", value);
+}
+
+pub fn link(link: &Link) -> Value {
+    json!({
+        "id": link.id(),
+        "url": link.link(),
+        "title": link.title(),
+        "category": link.category(),
+        "tags": link.tags(),
+        "state": link.state().to_string(),
+        "added": link.added().map(|added| added.to_rfc3339()),
+    })
+}
+
+pub fn links(links: &[Link]) -> Value {
+    Value::Array(links.iter().map(link).collect())
+}
+
+pub fn search_results(results: &[SearchResult]) -> Value {
+    Value::Array(
+        results
+            .iter()
+            .map(|result| json!({ "link": link(&result.link), "score": result.score }))
+            .collect(),
+    )
+}
+
+pub fn errors(errors: &[failure::Error]) -> Value {
+    Value::Array(
+        errors
+            .iter()
+            .map(|error| Value::String(error.to_string()))
+            .collect(),
+    )
+}
+
+/// Links that couldn't be changed and why
+pub fn failures(failed: &[(String, String)]) -> Value {
+    Value::Array(
+        failed
+            .iter()
+            .map(|(url, error)| json!({ "url": url, "error": error }))
+            .collect(),
+    )
+}
+
+pub fn tag_counts(counts: &[(String, usize)]) -> Value {
+    Value::Array(
+        counts
+            .iter()
+            .map(|(tag, count)| json!({ "tag": tag, "count": count }))
+            .collect(),
+    )
+}
+
+pub fn import_report(report: &ImportReport, dry_run: bool) -> Value {
+    json!({
+        "dry_run": dry_run,
+        "added": report.added,
+        "skipped": report.skipped,
+        "merged": report.merged,
+        "entries": report
+            .entries
+            .iter()
+            .map(|(action, entry)| {
+                let action = match action {
+                    ImportAction::Added => "added",
+                    ImportAction::Skipped => "skipped",
+                    ImportAction::Merged => "merged",
+                };
+
+                json!({ "action": action, "link": link(entry) })
+            })
+            .collect::<Vec<Value>>(),
+        "backend_errors": errors(&report.backend_errors),
+    })
+}
+
+pub fn outcome(outcome: Outcome) -> &'static str {
+    match outcome {
+        Outcome::Ok => "ok",
+        Outcome::Redirected => "redirected",
+        Outcome::NotFound => "not_found",
+        Outcome::Broken => "broken",
+        Outcome::Tls => "tls",
+        Outcome::Unreachable => "unreachable",
+    }
+}
+
+fn check_result(result: &CheckResult) -> Value {
+    json!({
+        "url": result.link,
+        "outcome": outcome(result.status.outcome()),
+        "status": result.status.status,
+        "redirects": result
+            .status
+            .redirects
+            .iter()
+            .map(|redirect| json!({ "status": redirect.status, "to": redirect.to }))
+            .collect::<Vec<Value>>(),
+        "error": result.status.error.as_ref().map(|error| error.to_string()),
+        "rewritten_to": result.rewritten_to,
+    })
+}
+
+pub fn check_report(report: &CheckReport) -> Value {
+    let count = |outcomes: &[Outcome]| {
+        report
+            .results
+            .iter()
+            .filter(|result| outcomes.contains(&result.status.outcome()))
+            .count()
+    };
+
+    json!({
+        "results": report.results.iter().map(check_result).collect::<Vec<Value>>(),
+        "summary": {
+            "links": report.results.len(),
+            "ok": count(&[Outcome::Ok]),
+            "redirected": count(&[Outcome::Redirected]),
+            "broken": count(&[Outcome::NotFound, Outcome::Broken]),
+            "failed": count(&[Outcome::Tls, Outcome::Unreachable]),
+        },
+        "backend_errors": failures(&report.backend_errors),
+    })
+}
+
+pub fn stats(stats: &ReadingStats) -> Value {
+    json!({
+        "unread": stats.unread,
+        "reading": stats.reading,
+        "read": stats.read,
+        "archived": stats.archived,
+        "read_last_week": stats.read_last_week,
+        "read_last_month": stats.read_last_month,
+        "weekly_average": stats.weekly_average,
+        "median_days_to_read": stats.median_days_to_read,
+    })
+}
@@ -0,0 +1,254 @@
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{self, Child, Command, Stdio};
+use std::thread;
+
+/// `link-keeper serve` running with its own home directory, stopped when
+/// dropped
+struct Server {
+    child: Child,
+    home: PathBuf,
+    url: String,
+    token: String,
+}
+
+impl Server {
+    fn start(name: &str) -> Self {
+        let home = env::temp_dir().join(format!("link-keeper-api-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&home);
+        fs::create_dir_all(&home).unwrap();
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_link-keeper-cli"))
+            .args(["--json", "serve", "--port", "0"])
+            .current_dir(&home)
+            .env("HOME", &home)
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_DATA_HOME")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let started: Value = serde_json::from_str(&line).unwrap();
+
+        Server {
+            child,
+            home,
+            url: started["url"].as_str().unwrap().to_owned(),
+            token: started["token"].as_str().unwrap().to_owned(),
+        }
+    }
+
+    /// Send a request with the token, answered with the status and body
+    fn request(&self, method: &str, path: &str, body: Option<Value>) -> (u16, Value) {
+        self.request_with(method, path, body, Some(&format!("Bearer {}", self.token)))
+    }
+
+    fn request_with(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+        authorization: Option<&str>,
+    ) -> (u16, Value) {
+        let mut request = ureq::request(method, &format!("{}{}", self.url, path));
+
+        if let Some(authorization) = authorization {
+            request = request.set("Authorization", authorization);
+        }
+
+        let response = match body {
+            Some(body) => request
+                .set("Content-Type", "application/json")
+                .send_string(&body.to_string()),
+            None => request.call(),
+        };
+
+        let response = match response {
+            Ok(response) => response,
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(err) => panic!("{}", err),
+        };
+
+        let status = response.status();
+        let body = response.into_string().unwrap();
+
+        (status, serde_json::from_str(&body).unwrap())
+    }
+
+    fn add(&self, url: &str, tags: &[&str]) -> Value {
+        let (status, body) =
+            self.request("POST", "/links", Some(json!({ "url": url, "tags": tags })));
+        assert_eq!(status, 201, "{}", body);
+
+        body["link"].clone()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.home);
+    }
+}
+
+fn urls(links: &Value) -> Vec<&str> {
+    links
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|link| link["url"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn requests_need_the_token() {
+    let server = Server::start("token");
+
+    let (status, body) = server.request_with("GET", "/links", None, None);
+    assert_eq!(status, 401);
+    assert_eq!(body["error"]["code"], "unauthorized");
+
+    let (status, _) = server.request_with("GET", "/links", None, Some("Bearer wrong"));
+    assert_eq!(status, 401);
+
+    let (status, body) = server.request("GET", "/links", None);
+    assert_eq!(status, 200);
+    assert_eq!(body, json!([]));
+
+    // Describing the API gives nothing away
+    let (status, body) = server.request_with("GET", "/openapi.json", None, None);
+    assert_eq!(status, 200);
+    assert_eq!(body["openapi"], "3.0.3");
+
+    for path in &["/links", "/links/{id}", "/search", "/tags"] {
+        assert!(body["paths"][path].is_object(), "{} isn't described", path);
+    }
+}
+
+#[test]
+fn links_are_added_listed_and_searched() {
+    let server = Server::start("add");
+
+    let link = server.add("https://www.rust-lang.org/learn", &["lang/rust", "#docs"]);
+    assert_eq!(link["tags"], json!(["lang/rust", "docs"]));
+    assert_eq!(link["state"], "unread");
+    server.add("https://example.com/cooking", &["food"]);
+
+    let (status, body) = server.request(
+        "POST",
+        "/links",
+        Some(json!({ "url": "https://www.rust-lang.org/learn/#intro" })),
+    );
+    assert_eq!(status, 409);
+    assert_eq!(body["error"]["code"], "duplicate");
+
+    let (_, body) = server.request("GET", "/links", None);
+    assert_eq!(
+        urls(&body),
+        vec![
+            "https://www.rust-lang.org/learn",
+            "https://example.com/cooking"
+        ]
+    );
+
+    let (_, body) = server.request("GET", "/links?tag=lang&state=unread", None);
+    assert_eq!(urls(&body), vec!["https://www.rust-lang.org/learn"]);
+
+    let id = link["id"].as_str().unwrap();
+    let (status, body) = server.request("GET", &format!("/links/{}", &id[..4]), None);
+    assert_eq!(status, 200);
+    assert_eq!(body, link);
+
+    let (_, body) = server.request("GET", "/search?q=cooking", None);
+    assert_eq!(body[0]["link"]["url"], "https://example.com/cooking");
+
+    let (_, body) = server.request("GET", "/tags", None);
+    assert!(body
+        .as_array()
+        .unwrap()
+        .contains(&json!({ "tag": "lang", "count": 1 })));
+}
+
+#[test]
+fn links_are_changed_and_removed() {
+    let server = Server::start("change");
+    let link = server.add("https://example.com/article", &["later"]);
+    let path = format!("/links/{}", link["id"].as_str().unwrap());
+
+    let (status, body) = server.request(
+        "PATCH",
+        &path,
+        Some(json!({ "title": "An article", "tags": ["done"], "state": "read" })),
+    );
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["link"]["title"], "An article");
+    assert_eq!(body["link"]["tags"], json!(["done"]));
+    assert_eq!(body["link"]["state"], "read");
+    assert_eq!(body["backend_errors"], json!([]));
+
+    // Left out fields are kept, null clears them
+    let (_, body) = server.request("PATCH", &path, Some(json!({ "title": null })));
+    assert_eq!(body["link"]["title"], Value::Null);
+    assert_eq!(body["link"]["state"], "read");
+
+    let (status, _) = server.request("DELETE", &path, None);
+    assert_eq!(status, 200);
+
+    let (status, body) = server.request("GET", &path, None);
+    assert_eq!(status, 404);
+    assert_eq!(body["error"]["code"], "not_found");
+
+    let (status, _) = server.request("DELETE", &path, None);
+    assert_eq!(status, 404);
+}
+
+#[test]
+fn invalid_requests_are_refused() {
+    let server = Server::start("invalid");
+
+    let (status, body) = server.request(
+        "POST",
+        "/links",
+        Some(json!({ "url": "ftp://example.com" })),
+    );
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["code"], "bad_request");
+
+    let (status, _) = server.request("POST", "/links", Some(json!(["https://example.com"])));
+    assert_eq!(status, 400);
+
+    let (status, _) = server.request("GET", "/links?state=forgotten", None);
+    assert_eq!(status, 400);
+
+    let (status, body) = server.request("PUT", "/links", None);
+    assert_eq!(status, 405);
+    assert_eq!(body["error"]["code"], "method_not_allowed");
+
+    let (status, _) = server.request("GET", "/nothing", None);
+    assert_eq!(status, 404);
+}
+
+#[test]
+fn links_added_at_the_same_time_are_all_stored() {
+    let server = Server::start("concurrent");
+
+    thread::scope(|scope| {
+        for index in 0..8 {
+            let server = &server;
+            scope.spawn(move || server.add(&format!("https://example.com/{}", index), &[]));
+        }
+    });
+
+    let (_, body) = server.request("GET", "/links", None);
+    assert_eq!(body.as_array().unwrap().len(), 8);
+}
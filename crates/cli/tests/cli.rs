@@ -0,0 +1,737 @@
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// An empty home directory for the test, removed first if an earlier run
+/// left it
+fn temp_home(name: &str) -> PathBuf {
+    let home = env::temp_dir().join(format!("link-keeper-cli-{}-{}", name, process::id()));
+
+    let _ = fs::remove_dir_all(&home);
+    fs::create_dir_all(&home).unwrap();
+
+    home
+}
+
+fn command(home: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_link-keeper-cli"));
+    command
+        .args(args)
+        .current_dir(home)
+        .env("HOME", home)
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("XDG_DATA_HOME")
+        .env_remove("VISUAL")
+        .env_remove("EDITOR")
+        .stdin(Stdio::null());
+
+    command
+}
+
+fn run(home: &Path, args: &[&str]) -> Output {
+    command(home, args).output().unwrap()
+}
+
+fn stdout_json(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+/// Add the link without suggestions, answered with its id
+fn add(home: &Path, url: &str) -> String {
+    let output = run(home, &["--json", "add", "--no-suggest", url]);
+    assert!(output.status.success());
+
+    stdout_json(&output)["link"]["id"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+/// An editor that writes the text to the file it's given, and records the
+/// file's path in `edited` and its permissions in `mode`
+#[cfg(unix)]
+fn editor(home: &Path, text: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = home.join("editor.sh");
+    fs::write(
+        &path,
+        format!(
+            "#!/bin/sh\n\
+             echo \"$1\" > \"{edited}\"\n\
+             (stat -c %a \"$1\" 2>/dev/null || stat -f %Lp \"$1\") > \"{mode}\"\n\
+             printf '{text}' > \"$1\"\n",
+            edited = home.join("edited").display(),
+            mode = home.join("mode").display(),
+            text = text
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+    path
+}
+
+#[cfg(unix)]
+#[test]
+fn notes_are_written_in_a_new_private_file() {
+    let home = temp_home("note");
+    let id = add(&home, "https://example.com");
+
+    // A file at the name `note` used to write to must be left alone
+    let planted = env::temp_dir().join(format!("link-keeper-note-{}.md", id));
+    fs::write(&planted, "planted").unwrap();
+
+    let output = command(&home, &["--json", "note", &id])
+        .env("EDITOR", editor(&home, "A note\\n# left out\\n"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let noted = stdout_json(&output);
+    assert_eq!(noted["noted"], true);
+    assert_eq!(noted["link"]["id"], id.as_str());
+
+    let edited = PathBuf::from(fs::read_to_string(home.join("edited")).unwrap().trim());
+    assert_ne!(edited, planted);
+    assert!(!edited.exists());
+    assert_eq!(fs::read_to_string(home.join("mode")).unwrap().trim(), "600");
+    assert_eq!(fs::read_to_string(&planted).unwrap(), "planted");
+
+    let exported = run(&home, &["export", "--format", "markdown"]);
+    let exported = String::from_utf8(exported.stdout).unwrap();
+    assert!(exported.contains("\n  - Note ("));
+    assert!(exported.contains("): A note\n"));
+    assert!(!exported.contains("left out"));
+
+    fs::remove_file(&planted).unwrap();
+    fs::remove_dir_all(&home).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn empty_notes_arent_added() {
+    let home = temp_home("empty-note");
+    let id = add(&home, "https://example.com");
+
+    let output = command(&home, &["--json", "note", &id])
+        .env("EDITOR", editor(&home, "# only a comment\\n"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(stdout_json(&output)["noted"], false);
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn tags_are_renamed_with_their_children() {
+    let home = temp_home("tags-rename");
+    for (url, tag) in &[
+        ("https://example.com/a", "lang"),
+        ("https://example.com/b", "lang/rust"),
+        ("https://example.com/c", "language"),
+    ] {
+        let output = run(&home, &["--json", "add", "--no-suggest", "--tag", tag, url]);
+        assert!(output.status.success());
+    }
+
+    let output = run(&home, &["--json", "tags", "rename", "lang", "code/lang"]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout_json(&output),
+        serde_json::json!({ "updated": 2, "failed": [] })
+    );
+
+    let output = run(
+        &home,
+        &["--json", "tags", "merge", "language", "--into", "code/lang"],
+    );
+    assert_eq!(stdout_json(&output)["updated"], 1);
+
+    let listed = stdout_json(&run(&home, &["--json", "tags", "list"]));
+    assert_eq!(
+        listed,
+        serde_json::json!([
+            { "tag": "code", "count": 3 },
+            { "tag": "code/lang", "count": 3 },
+            { "tag": "code/lang/rust", "count": 1 },
+        ])
+    );
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn links_backends_fail_to_update_are_reported() {
+    let home = temp_home("tags-failed");
+    let output = run(
+        &home,
+        &[
+            "--json",
+            "add",
+            "--no-suggest",
+            "--tag",
+            "lang",
+            "https://example.com",
+        ],
+    );
+    assert!(output.status.success());
+
+    let output = run(
+        &home,
+        &[
+            "--json",
+            "--set",
+            "backends.exec.command=/bin/false",
+            "--yes",
+            "tags",
+            "delete",
+            "lang",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(5));
+
+    let report = stdout_json(&output);
+    assert_eq!(report["updated"], 1);
+    assert_eq!(report["failed"][0]["url"], "https://example.com");
+    assert!(report["failed"][0]["error"].is_string());
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+fn stderr_json(output: &Output) -> Value {
+    serde_json::from_slice(&output.stderr).unwrap()
+}
+
+/// The exit code and the error code printed with `--json`
+fn failure(output: &Output) -> (Option<i32>, String) {
+    (
+        output.status.code(),
+        stderr_json(output)["error"]["code"]
+            .as_str()
+            .unwrap()
+            .to_owned(),
+    )
+}
+
+#[test]
+fn links_are_printed_in_the_documented_shape() {
+    let home = temp_home("shapes");
+    let output = run(
+        &home,
+        &[
+            "--json",
+            "add",
+            "--no-suggest",
+            "--tag",
+            "lang/rust",
+            "https://example.com",
+        ],
+    );
+    assert!(output.status.success());
+
+    let added = stdout_json(&output);
+    assert_eq!(added["merged"], false);
+    assert_eq!(added["backend_errors"], serde_json::json!([]));
+
+    let link = &added["link"];
+    let mut keys = link.as_object().unwrap().keys().collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(
+        keys,
+        ["added", "category", "id", "state", "tags", "title", "url"]
+    );
+    assert_eq!(link["url"], "https://example.com");
+    assert_eq!(link["tags"], serde_json::json!(["lang/rust"]));
+    assert_eq!(link["state"], "unread");
+    assert!(link["title"].is_null());
+
+    let id = link["id"].as_str().unwrap();
+
+    let listed = stdout_json(&run(&home, &["--json", "list"]));
+    assert_eq!(listed, serde_json::json!([link]));
+
+    let marked = stdout_json(&run(&home, &["--json", "mark", "read", id]));
+    assert_eq!(marked["links"][0]["id"], id);
+    assert_eq!(marked["links"][0]["state"], "read");
+    assert_eq!(marked["backend_errors"], serde_json::json!([]));
+
+    let output = run(
+        &home,
+        &[
+            "--json",
+            "--yes",
+            "add",
+            "--no-suggest",
+            "https://example.com",
+        ],
+    );
+    assert!(output.status.success());
+    assert_eq!(stdout_json(&output)["merged"], true);
+
+    let status = stdout_json(&run(&home, &["--json", "status"]));
+    assert_eq!(status["links"], 1);
+    assert_eq!(status["read"], 1);
+    assert_eq!(status["unread"], 0);
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn errors_exit_with_their_code() {
+    let home = temp_home("exit-codes");
+    let id = add(&home, "https://example.com");
+
+    let output = run(
+        &home,
+        &["--json", "add", "--no-suggest", "https://example.com"],
+    );
+    assert_eq!(failure(&output), (Some(3), "duplicate".to_owned()));
+
+    let missing = if id.starts_with('0') { "f" } else { "0" };
+    let output = run(&home, &["--json", "mark", "read", missing]);
+    assert_eq!(failure(&output), (Some(4), "not_found".to_owned()));
+    assert_eq!(
+        stderr_json(&output)["error"]["message"],
+        format!("No link with id {}", missing)
+    );
+
+    let output = run(&home, &["--json", "mark", "someday", &id]);
+    assert_eq!(failure(&output), (Some(2), "usage".to_owned()));
+
+    let output = run(&home, &["--json", "list", "--limit", "some"]);
+    assert_eq!(failure(&output), (Some(2), "usage".to_owned()));
+    assert_eq!(
+        stderr_json(&output)["error"]["message"],
+        "--limit should be a number, not some"
+    );
+
+    let output = run(&home, &["--json", "frobnicate"]);
+    assert_eq!(failure(&output), (Some(2), "usage".to_owned()));
+
+    let output = run(&home, &["frobnicate"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("frobnicate"));
+
+    assert!(run(&home, &["--help"]).status.success());
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn ids_more_than_one_link_starts_with_are_ambiguous() {
+    let home = temp_home("ambiguous");
+
+    // With 17 links two ids start with the same digit
+    let mut ids = (0..17)
+        .map(|number| add(&home, &format!("https://example.com/{}", number)))
+        .collect::<Vec<_>>();
+    ids.sort();
+    let prefix = ids
+        .windows(2)
+        .find(|pair| pair[0][..1] == pair[1][..1])
+        .map(|pair| pair[0][..1].to_owned())
+        .unwrap();
+
+    let output = run(&home, &["--json", "mark", "read", &prefix]);
+    assert_eq!(failure(&output), (Some(6), "ambiguous".to_owned()));
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+/// Suggestions are only made with a terminal, `script` gives the CLI one
+#[cfg(target_os = "linux")]
+#[test]
+fn links_are_added_when_suggestions_cant_be_made() {
+    let home = temp_home("suggest-invalid");
+    let added = Command::new("script")
+        .args(["-qec"])
+        .arg(format!(
+            "{} --set suggestions.domains=oops add https://example.com",
+            env!("CARGO_BIN_EXE_link-keeper-cli")
+        ))
+        .arg("/dev/null")
+        .current_dir(&home)
+        .env("HOME", &home)
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("XDG_DATA_HOME")
+        .stdin(Stdio::null())
+        .output();
+
+    // Without `script` there's no terminal to test with
+    let added = match added {
+        Ok(added) => added,
+        Err(_) => return,
+    };
+    assert!(added.status.success());
+    assert!(String::from_utf8_lossy(&added.stdout).contains("Could not suggest tags"));
+
+    let listed = stdout_json(&run(&home, &["--json", "list"]));
+    assert_eq!(listed[0]["url"], "https://example.com");
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+/// The global configuration written to the home directory
+fn config(home: &Path) -> toml::Value {
+    let path = home
+        .join(".config")
+        .join("link-keeper")
+        .join("link-keeper.toml");
+
+    toml::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn backends_are_added_with_their_flags() {
+    let home = temp_home("backend-flags");
+
+    let output = run(
+        &home,
+        &[
+            "--json", "backend", "add", "git", "--repo", "links", "--remote", "upstream", "--push",
+        ],
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        stdout_json(&output),
+        serde_json::json!({ "name": "git", "activated": true })
+    );
+
+    // Left out fields get their defaults, paths are made absolute
+    let output = run(
+        &home,
+        &["--json", "backend", "add", "vault", "--vault", "notes"],
+    );
+    assert!(output.status.success());
+
+    let config = config(&home);
+    let git = &config["backends"]["git"];
+    assert_eq!(git["repository_path"].as_str(), home.join("links").to_str());
+    assert_eq!(git["remote"].as_str(), Some("upstream"));
+    assert_eq!(git["push"].as_bool(), Some(true));
+    assert!(home.join("links").join(".git").is_dir());
+
+    let vault = &config["backends"]["vault"];
+    assert_eq!(vault["vault_path"].as_str(), home.join("notes").to_str());
+    assert_eq!(vault["folder"].as_str(), Some("links"));
+    assert_eq!(vault["layout"].as_str(), Some("notes"));
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn executables_are_added_as_they_are_named() {
+    let home = temp_home("backend-exec");
+
+    let output = run(
+        &home,
+        &[
+            "--json",
+            "backend",
+            "add",
+            "exec",
+            "--command",
+            "python3",
+            "--args",
+            "stub/link-keeper-stub.py links.json",
+        ],
+    );
+    assert!(output.status.success());
+
+    // Looked up on the `PATH` rather than next to where it was added
+    let exec = &config(&home)["backends"]["exec"];
+    assert_eq!(exec["command"].as_str(), Some("python3"));
+    assert_eq!(
+        exec["args"],
+        toml::Value::try_from(vec!["stub/link-keeper-stub.py", "links.json"]).unwrap()
+    );
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn secrets_are_read_from_the_variable_their_flag_names() {
+    let home = temp_home("backend-secret");
+
+    let output = command(
+        &home,
+        &[
+            "--json",
+            "backend",
+            "add",
+            "github",
+            "--token-env",
+            "LINK_KEEPER_TEST_TOKEN",
+            "--repo",
+            "owner/name",
+        ],
+    )
+    .env("LINK_KEEPER_TEST_TOKEN", "secret")
+    .output()
+    .unwrap();
+    assert!(output.status.success());
+
+    let github = &config(&home)["backends"]["github"];
+    assert_eq!(github["access_token"].as_str(), Some("secret"));
+    assert_eq!(github["repository"].as_str(), Some("owner/name"));
+
+    let output = command(
+        &home,
+        &[
+            "--json",
+            "backend",
+            "add",
+            "github",
+            "--token-env",
+            "LINK_KEEPER_TEST_TOKEN",
+            "--repo",
+            "owner/name",
+        ],
+    )
+    .env_remove("LINK_KEEPER_TEST_TOKEN")
+    .output()
+    .unwrap();
+    assert_eq!(failure(&output), (Some(2), "usage".to_owned()));
+    assert_eq!(
+        stderr_json(&output)["error"]["message"],
+        "LINK_KEEPER_TEST_TOKEN is not set"
+    );
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn backends_cant_be_added_with_wrong_or_missing_flags() {
+    let home = temp_home("backend-wrong-flags");
+
+    let output = run(&home, &["--json", "backend", "add", "vault", "--push"]);
+    assert_eq!(failure(&output), (Some(2), "usage".to_owned()));
+    assert_eq!(
+        stderr_json(&output)["error"]["message"],
+        "--push doesn't apply to vault"
+    );
+
+    let output = run(&home, &["--json", "backend", "add", "vault"]);
+    assert_eq!(failure(&output), (Some(2), "usage".to_owned()));
+    assert_eq!(
+        stderr_json(&output)["error"]["message"],
+        "--vault is needed to add vault"
+    );
+
+    let output = run(&home, &["--json", "backend", "add"]);
+    assert_eq!(failure(&output), (Some(2), "usage".to_owned()));
+
+    assert!(!home.join(".config").join("link-keeper").exists());
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+/// A port nothing listens on
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// The status of a GET request to the local server, retried until it
+/// listens
+fn get(port: u16, path: &str) -> u16 {
+    let mut stream = (0..100)
+        .find_map(|_| {
+            TcpStream::connect(("127.0.0.1", port))
+                .map_err(|_| thread::sleep(Duration::from_millis(50)))
+                .ok()
+        })
+        .unwrap();
+    write!(stream, "GET {} HTTP/1.0\r\nHost: 127.0.0.1\r\n\r\n", path).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    response.split(' ').nth(1).unwrap().parse().unwrap()
+}
+
+#[test]
+fn captures_need_the_token_and_a_web_link() {
+    let home = temp_home("capture");
+    let port = free_port();
+
+    let mut capture = command(&home, &["capture", "--port", &port.to_string(), "--once"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // Kept open until it stops, it prints the links it stores
+    let mut stdout = BufReader::new(capture.stdout.take().unwrap());
+    let mut bookmarklet = String::new();
+    stdout.read_line(&mut bookmarklet).unwrap();
+    let token = bookmarklet
+        .split("token=")
+        .nth(1)
+        .and_then(|rest| rest.split('&').next())
+        .unwrap()
+        .to_owned();
+
+    assert_eq!(
+        get(port, "/add?token=wrong&url=https%3A%2F%2Fexample.com"),
+        403
+    );
+    assert_eq!(get(port, "/add?url=https%3A%2F%2Fexample.com"), 403);
+    assert_eq!(
+        get(
+            port,
+            &format!("/add?token={}&url=javascript%3Aalert(1)", token)
+        ),
+        400
+    );
+    assert_eq!(get(port, &format!("/other?token={}", token)), 404);
+    assert_eq!(
+        get(
+            port,
+            &format!(
+                "/add?token={}&url=https%3A%2F%2Fexample.com&title=Example&tags=rust%2C%20web",
+                token
+            )
+        ),
+        200
+    );
+
+    // Stopped after the first stored link
+    assert!(capture.wait().unwrap().success());
+    let mut added = String::new();
+    stdout.read_to_string(&mut added).unwrap();
+    assert!(added.contains("https://example.com"));
+
+    let listed = stdout_json(&run(&home, &["--json", "list"]));
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["url"], "https://example.com");
+    assert_eq!(listed[0]["title"], "Example");
+    assert_eq!(listed[0]["tags"], serde_json::json!(["rust", "web"]));
+    assert_eq!(config(&home)["capture"]["token"].as_str(), Some(&*token));
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn captures_that_cant_be_stored_dont_stop_the_server() {
+    let home = temp_home("capture-broken");
+    let port = free_port();
+
+    let mut capture = command(&home, &["capture", "--port", &port.to_string(), "--once"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let mut stdout = BufReader::new(capture.stdout.take().unwrap());
+    let mut bookmarklet = String::new();
+    stdout.read_line(&mut bookmarklet).unwrap();
+    let token = bookmarklet
+        .split("token=")
+        .nth(1)
+        .and_then(|rest| rest.split('&').next())
+        .unwrap()
+        .to_owned();
+    let add = format!("/add?token={}&url=https%3A%2F%2Fexample.com", token);
+
+    let store = home
+        .join(".local")
+        .join("share")
+        .join("link-keeper")
+        .join("link_keeper.json");
+    fs::create_dir_all(store.parent().unwrap()).unwrap();
+    fs::write(&store, "not a store").unwrap();
+    assert_eq!(get(port, &add), 500);
+
+    fs::remove_file(&store).unwrap();
+    assert_eq!(get(port, &add), 200);
+
+    assert!(capture.wait().unwrap().success());
+    let mut added = String::new();
+    stdout.read_to_string(&mut added).unwrap();
+    assert!(added.contains("https://example.com"));
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+/// Add from the clipboard with only the tools in `bin` to read it with
+fn add_from_clipboard(home: &Path, session: &str) -> Output {
+    command(home, &["--json", "add", "--clipboard", "--no-suggest"])
+        .env("PATH", home.join("bin"))
+        .env_remove("WAYLAND_DISPLAY")
+        .env_remove("DISPLAY")
+        .env(session, ":0")
+        .output()
+        .unwrap()
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn missing_clipboard_tools_are_named() {
+    let home = temp_home("clipboard-missing");
+    fs::create_dir_all(home.join("bin")).unwrap();
+
+    let output = add_from_clipboard(&home, "WAYLAND_DISPLAY");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr_json(&output)["error"]["message"],
+        "Reading the clipboard needs wl-paste, install wl-clipboard on Wayland or xclip on X11"
+    );
+
+    let output = add_from_clipboard(&home, "DISPLAY");
+    assert_eq!(
+        stderr_json(&output)["error"]["message"],
+        "Reading the clipboard needs xclip or xsel, install wl-clipboard on Wayland or xclip on X11"
+    );
+
+    let output = add_from_clipboard(&home, "LINK_KEEPER_NO_SESSION");
+    assert_eq!(
+        stderr_json(&output)["error"]["message"],
+        "No clipboard to read, neither WAYLAND_DISPLAY nor DISPLAY is set"
+    );
+
+    fs::remove_dir_all(&home).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn links_on_the_clipboard_are_added() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = temp_home("clipboard");
+    let bin = home.join("bin");
+    fs::create_dir_all(&bin).unwrap();
+
+    let tool = bin.join("wl-paste");
+    fs::write(
+        &tool,
+        "#!/bin/sh\nprintf 'See https://example.com/a and\\n<https://example.com/b>, or not'\n",
+    )
+    .unwrap();
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = add_from_clipboard(&home, "WAYLAND_DISPLAY");
+    assert!(output.status.success());
+
+    let added = stdout_json(&output)["added"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|link| link["url"].as_str().unwrap().to_owned())
+        .collect::<Vec<String>>();
+    assert_eq!(added, ["https://example.com/a", "https://example.com/b"]);
+
+    fs::remove_dir_all(&home).unwrap();
+}
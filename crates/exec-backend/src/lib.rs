@@ -0,0 +1,312 @@
+//! A backend that delegates to an external executable.
+//!
+//! For every operation the configured command is spawned, a single JSON
+//! request is written to its stdin and a single JSON response is read back
+//! from its stdout:
+//!
+//! ```json
+//! {"version": 1, "method": "add_link", "params": {"link": {"link": "https://example.com"}}}
+//! {"version": 1, "result": null}
+//! ```
+//!
+//! Failures are reported with `{"version": 1, "error": "Some message"}`.
+//! The supported methods are `add_link`, `remove_link`, `update_link`, `list`,
+//! `sign_in` and `sign_out`. See `stub/link-keeper-stub.py` for a reference implementation.
+
+use failure::Fail;
+use link_keeper::{
+    backend::{AccessToken, Backend, BackendFactory, BackendRegistry, ConfigField, FieldKind},
+    Link, LinkKeeper,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Version of the JSON protocol spoken with the executable
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How often an executable that answered is checked for having exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug)]
+pub enum ExecError {
+    Timeout(PathBuf, u64),
+    NoResponse(PathBuf),
+    UnsupportedVersion(u32),
+    Backend(String),
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ExecError::Timeout(command, seconds) => fmt.write_fmt(format_args!(
+                "{:?} did not respond within {} seconds",
+                command, seconds
+            )),
+            ExecError::NoResponse(command) => {
+                fmt.write_fmt(format_args!("{:?} exited without a response", command))
+            }
+            ExecError::UnsupportedVersion(version) => {
+                fmt.write_fmt(format_args!("Unsupported protocol version {}", version))
+            }
+            ExecError::Backend(error) => fmt.write_fmt(format_args!("Backend error: {}", error)),
+        }
+    }
+}
+
+impl Fail for ExecError {}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Exec {
+    pub config: ExecConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExecConfig {
+    pub command: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    version: u32,
+    method: &'a str,
+    params: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    version: u32,
+    #[serde(default)]
+    result: serde_json::Value,
+    error: Option<String>,
+}
+
+impl Exec {
+    fn timeout(&self) -> ExecError {
+        ExecError::Timeout(self.config.command.to_owned(), self.config.timeout_secs)
+    }
+
+    /// Wait for the child to exit until the deadline, then kill it
+    fn wait(&self, child: &mut Child, deadline: Instant) -> Result<(), failure::Error> {
+        loop {
+            if child.try_wait()?.is_some() {
+                return Ok(());
+            }
+
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+
+                return Err(self.timeout().into());
+            }
+
+            thread::sleep(EXIT_POLL_INTERVAL);
+        }
+    }
+
+    /// Spawn the executable, send one request and wait for its response
+    fn call(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, failure::Error> {
+        let mut request = serde_json::to_vec(&Request {
+            version: PROTOCOL_VERSION,
+            method,
+            params,
+        })?;
+        request.push(b'\n');
+
+        let deadline = Instant::now() + Duration::from_secs(self.config.timeout_secs);
+        let mut child = Command::new(&self.config.command)
+            .args(&self.config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+
+        // Written on its own thread, an executable that doesn't read a large
+        // request would otherwise block before the deadline is waited for.
+        // Killing it on timeout ends the write.
+        if let Some(mut stdin) = child.stdin.take() {
+            thread::spawn(move || {
+                let _ = stdin.write_all(&request);
+                // Dropping stdin closes it so the executable sees EOF
+            });
+        }
+
+        let (sender, receiver) = mpsc::channel();
+
+        if let Some(mut stdout) = child.stdout.take() {
+            thread::spawn(move || {
+                let mut output = String::new();
+                let _ = sender.send(stdout.read_to_string(&mut output).map(|_| output));
+            });
+        }
+
+        let output = match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            Ok(output) => output?,
+            Err(_) => {
+                let _ = child.kill();
+                let _ = child.wait();
+
+                return Err(self.timeout().into());
+            }
+        };
+
+        // Closing stdout doesn't mean the executable exits
+        self.wait(&mut child, deadline)?;
+
+        if output.trim().is_empty() {
+            return Err(ExecError::NoResponse(self.config.command.to_owned()).into());
+        }
+
+        let response = serde_json::from_str::<Response>(&output)?;
+
+        if response.version != PROTOCOL_VERSION {
+            return Err(ExecError::UnsupportedVersion(response.version).into());
+        }
+
+        match response.error {
+            Some(error) => Err(ExecError::Backend(error).into()),
+            None => Ok(response.result),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ExecFactory;
+
+impl BackendFactory for ExecFactory {
+    fn name(&self) -> &'static str {
+        "exec"
+    }
+
+    fn config_schema(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField {
+                key: "command",
+                flag: "command",
+                prompt: "What executable should links be handed to?",
+                kind: FieldKind::Text,
+                default: None,
+            },
+            ConfigField {
+                key: "args",
+                flag: "args",
+                prompt: "What arguments should it be given? (separated by spaces)",
+                kind: FieldKind::Text,
+                default: Some(String::new()),
+            },
+            ConfigField {
+                key: "timeout_secs",
+                flag: "timeout",
+                prompt: "How many seconds should it be given to respond?",
+                kind: FieldKind::Text,
+                default: Some(default_timeout_secs().to_string()),
+            },
+        ]
+    }
+
+    fn build(&self, config: &toml::Value) -> Result<Box<dyn Backend>, failure::Error> {
+        let mut config = config.clone();
+
+        // Values entered during setup are always strings
+        if let Some(table) = config.as_table_mut() {
+            let timeout_secs = table
+                .get("timeout_secs")
+                .and_then(|timeout| timeout.as_str())
+                .map(|timeout| timeout.parse::<i64>());
+
+            if let Some(timeout_secs) = timeout_secs {
+                table.insert(
+                    "timeout_secs".to_owned(),
+                    toml::Value::Integer(timeout_secs?),
+                );
+            }
+
+            let args = table
+                .get("args")
+                .and_then(|args| args.as_str())
+                .map(|args| {
+                    args.split_whitespace()
+                        .map(|arg| toml::Value::String(arg.to_owned()))
+                        .collect()
+                });
+
+            if let Some(args) = args {
+                table.insert("args".to_owned(), toml::Value::Array(args));
+            }
+        }
+
+        Ok(Box::new(Exec {
+            config: config.try_into::<ExecConfig>()?,
+        }))
+    }
+}
+
+/// Register the Exec backend in the given registry
+pub fn register(registry: &mut BackendRegistry) {
+    registry.register(Box::new(ExecFactory));
+}
+
+impl fmt::Display for Exec {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        fmt.write_fmt(format_args!("Exec"))
+    }
+}
+
+impl Backend for Exec {
+    fn add(&self, _link_keeper: &mut LinkKeeper) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn sign_in(&self, access_token: &AccessToken) -> Result<(), failure::Error> {
+        self.call("sign_in", json!({ "access_token": access_token }))
+            .map(|_| ())
+    }
+
+    fn sign_out(&self, access_token: &AccessToken) -> Result<(), failure::Error> {
+        self.call("sign_out", json!({ "access_token": access_token }))
+            .map(|_| ())
+    }
+
+    fn add_link(&self, link: &Link) -> Result<(), failure::Error> {
+        self.call("add_link", json!({ "link": link })).map(|_| ())
+    }
+
+    fn remove_link(&self, link: &Link) -> Result<(), failure::Error> {
+        self.call("remove_link", json!({ "link": link }))
+            .map(|_| ())
+    }
+
+    fn update_link(&self, link: &Link) -> Result<(), failure::Error> {
+        self.call("update_link", json!({ "link": link }))
+            .map(|_| ())
+    }
+
+    fn list_links(&self) -> Result<Vec<Link>, failure::Error> {
+        Ok(serde_json::from_value::<Vec<Link>>(
+            self.call("list", json!({}))?,
+        )?)
+    }
+
+    fn get_toml_config(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(&self.config)
+    }
+}
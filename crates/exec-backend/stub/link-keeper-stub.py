@@ -0,0 +1,74 @@
+#!/usr/bin/env python3
+"""Reference implementation of the link-keeper exec backend protocol.
+
+Links are kept in the JSON file given as the first argument, or else by
+LINK_KEEPER_STUB_STORE (default: ./link-keeper-stub.json).
+"""
+
+import json
+import os
+import sys
+
+PROTOCOL_VERSION = 1
+STORE = (
+    sys.argv[1]
+    if len(sys.argv) > 1
+    else os.environ.get("LINK_KEEPER_STUB_STORE", "link-keeper-stub.json")
+)
+
+
+def load():
+    try:
+        with open(STORE) as store:
+            return json.load(store)
+    except FileNotFoundError:
+        return []
+
+
+def save(links):
+    with open(STORE, "w") as store:
+        json.dump(links, store)
+
+
+def handle(method, params):
+    if method == "add_link":
+        links = load()
+        if params["link"] not in links:
+            links.append(params["link"])
+        save(links)
+        return None
+    if method == "remove_link":
+        save([link for link in load() if link["link"] != params["link"]["link"]])
+        return None
+    if method == "update_link":
+        save(
+            [
+                params["link"] if link["link"] == params["link"]["link"] else link
+                for link in load()
+            ]
+        )
+        return None
+    if method == "list":
+        return load()
+    if method in ("sign_in", "sign_out"):
+        return None
+    raise ValueError("Unknown method: {}".format(method))
+
+
+def main():
+    request = json.loads(sys.stdin.readline())
+
+    if request.get("version") != PROTOCOL_VERSION:
+        response = {"error": "Unsupported protocol version"}
+    else:
+        try:
+            response = {"result": handle(request["method"], request.get("params", {}))}
+        except Exception as error:
+            response = {"error": str(error)}
+
+    response["version"] = PROTOCOL_VERSION
+    json.dump(response, sys.stdout)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,196 @@
+use link_keeper::backend::{Backend, BackendFactory};
+use link_keeper::Link;
+use link_keeper_exec_backend::ExecFactory;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+/// An empty directory for the test, removed first if an earlier run left it
+fn temp_dir(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("link-keeper-exec-{}-{}", name, process::id()));
+
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+
+    directory
+}
+
+fn backend(command: &str, args: &[&str], timeout_secs: i64) -> Box<dyn Backend> {
+    let mut config = toml::value::Table::new();
+    config.insert(
+        "command".to_owned(),
+        toml::Value::String(command.to_owned()),
+    );
+    config.insert(
+        "args".to_owned(),
+        toml::Value::Array(
+            args.iter()
+                .map(|arg| toml::Value::String((*arg).to_owned()))
+                .collect(),
+        ),
+    );
+    config.insert(
+        "timeout_secs".to_owned(),
+        toml::Value::Integer(timeout_secs),
+    );
+
+    ExecFactory.build(&toml::Value::Table(config)).unwrap()
+}
+
+/// The reference implementation keeping its links in the directory
+fn stub(directory: &Path) -> Box<dyn Backend> {
+    let script = concat!(env!("CARGO_MANIFEST_DIR"), "/stub/link-keeper-stub.py");
+    let store = directory.join("links.json");
+
+    backend("python3", &[script, store.to_str().unwrap()], 10)
+}
+
+fn urls(backend: &dyn Backend) -> Vec<String> {
+    backend
+        .list_links()
+        .unwrap()
+        .iter()
+        .map(|link| link.link().to_owned())
+        .collect()
+}
+
+#[test]
+fn configs_entered_during_setup_are_converted() {
+    let directory = temp_dir("setup");
+    let script = concat!(env!("CARGO_MANIFEST_DIR"), "/stub/link-keeper-stub.py");
+    let store = directory.join("links.json");
+
+    let config = toml::from_str::<toml::Value>(&format!(
+        "command = \"python3\"\nargs = {:?}\ntimeout_secs = \"10\"\n",
+        format!("{} {}", script, store.display())
+    ))
+    .unwrap();
+    let backend = ExecFactory.build(&config).unwrap();
+
+    backend
+        .add_link(&Link::new("https://example.com", None))
+        .unwrap();
+    assert_eq!(urls(&*backend), vec!["https://example.com"]);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn links_are_added_listed_and_removed() {
+    let directory = temp_dir("stub");
+    let backend = stub(&directory);
+
+    assert!(urls(&*backend).is_empty());
+
+    let first = Link::new("https://example.com/first", None);
+    let second = Link::new("https://example.com/second", Some("reading"));
+    backend.add_link(&first).unwrap();
+    backend.add_link(&second).unwrap();
+    assert_eq!(
+        urls(&*backend),
+        vec!["https://example.com/first", "https://example.com/second"]
+    );
+
+    backend
+        .update_link(&second.clone().with_title(Some("Second")))
+        .unwrap();
+    let links = backend.list_links().unwrap();
+    assert_eq!(links[1].title(), Some("Second"));
+    assert_eq!(links[1].category(), Some("reading"));
+
+    backend.remove_link(&first).unwrap();
+    assert_eq!(urls(&*backend), vec!["https://example.com/second"]);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn other_protocol_versions_are_refused() {
+    let backend = backend(
+        "sh",
+        &[
+            "-c",
+            "cat > /dev/null; echo '{\"version\": 2, \"result\": []}'",
+        ],
+        10,
+    );
+
+    let err = backend.list_links().unwrap_err();
+    assert_eq!(err.to_string(), "Unsupported protocol version 2");
+}
+
+#[test]
+fn executables_that_dont_respond_are_stopped() {
+    let backend = backend("sh", &["-c", "sleep 30"], 1);
+    let started = Instant::now();
+
+    let err = backend.list_links().unwrap_err();
+    assert!(err.to_string().contains("did not respond within 1 seconds"));
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn executables_that_respond_but_dont_exit_are_stopped() {
+    // Answers and closes stdout, but keeps running
+    let backend = backend(
+        "sh",
+        &[
+            "-c",
+            "cat > /dev/null; echo '{\"version\": 1, \"result\": []}'; exec > /dev/null; sleep 30",
+        ],
+        1,
+    );
+    let started = Instant::now();
+
+    let err = backend.list_links().unwrap_err();
+    assert!(err.to_string().contains("did not respond within 1 seconds"));
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+/// A link whose request is larger than a pipe's buffer, so writing it
+/// blocks until the executable reads it
+fn large_link() -> Link {
+    Link::new("https://example.com/large", None).with_title(Some(&"x".repeat(200_000)))
+}
+
+#[test]
+fn executables_that_dont_read_large_requests_are_stopped() {
+    let backend = backend("sh", &["-c", "sleep 30"], 1);
+    let started = Instant::now();
+
+    let err = backend.add_link(&large_link()).unwrap_err();
+    assert!(err.to_string().contains("did not respond within 1 seconds"));
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn executables_writing_before_reading_large_requests_dont_block() {
+    // Fills the stdout pipe before reading the request
+    let backend = backend(
+        "sh",
+        &[
+            "-c",
+            "head -c 200000 /dev/zero | tr '\\0' ' '; cat > /dev/null; echo '{\"version\": 1, \"result\": null}'",
+        ],
+        10,
+    );
+    let started = Instant::now();
+
+    backend.add_link(&large_link()).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn large_requests_are_answered() {
+    let directory = temp_dir("stub-large");
+    let backend = stub(&directory);
+
+    backend.add_link(&large_link()).unwrap();
+
+    let links = backend.list_links().unwrap();
+    assert_eq!(links, vec![large_link()]);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
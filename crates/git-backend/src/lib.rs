@@ -1,11 +1,20 @@
-use git2::{Repository, Signature, Tree};
+use git2::{Cred, CredentialType, PushOptions, RemoteCallbacks, Repository, Signature};
 use link_keeper::{
-    backend::{AccessToken, Backend},
+    backend::{AccessToken, Backend, BackendFactory, BackendRegistry, ConfigField, FieldKind},
+    export::{Exporter, Markdown},
+    normalize::canonical_url,
     Link, LinkKeeper,
 };
 use serde::{Deserialize, Serialize};
+use std::env;
 use std::fmt;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The file in the repository the links are stored in
+const LINKS_FILE_NAME: &str = "links.json";
+/// A readable list of the links with their notes, written next to the links
+const LINKS_MARKDOWN_FILE_NAME: &str = "links.md";
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Git {
@@ -15,29 +24,48 @@ pub struct Git {
 #[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
 pub struct GitConfig {
     pub repository_path: PathBuf,
+    /// The remote pushed to
+    #[serde(default = "default_remote")]
+    pub remote: String,
+    /// Whether to push after every commit
+    #[serde(default)]
+    pub push: bool,
 }
 
-impl fmt::Display for Git {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        fmt.write_fmt(format_args!("Git"))
-    }
+fn default_remote() -> String {
+    "origin".to_owned()
 }
 
-impl Backend for Git {
-    fn add(&self, link_keeper: &mut LinkKeeper) -> Result<(), failure::Error> {
-        dbg!("Adding Git backend");
-        //link_keeper.add_backend(self);
-        Repository::init(&self.config.repository_path)?;
+impl Git {
+    fn read_links(&self) -> Result<Vec<Link>, failure::Error> {
+        let path = self.config.repository_path.join(LINKS_FILE_NAME);
 
-        Ok(())
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
     }
 
-    fn add_link(&self, link: &Link) -> Result<(), failure::Error> {
-        println!("Adding {:?} to {}", link, self);
+    /// Write the links to the repository and commit them
+    fn commit_links(&self, links: &[Link], message: &str) -> Result<(), failure::Error> {
+        fs::write(
+            self.config.repository_path.join(LINKS_FILE_NAME),
+            serde_json::to_string_pretty(links)?,
+        )?;
+        fs::write(
+            self.config.repository_path.join(LINKS_MARKDOWN_FILE_NAME),
+            Markdown.export(links)?,
+        )?;
 
         let repo = Repository::open(&self.config.repository_path)?;
         let comitter = Signature::now("Link keeper", "link_keeper@users.noreply.github.com")?;
-        let tree_id = repo.index()?.write_tree()?;
+
+        let mut index = repo.index()?;
+        index.add_path(Path::new(LINKS_FILE_NAME))?;
+        index.add_path(Path::new(LINKS_MARKDOWN_FILE_NAME))?;
+        index.write()?;
+        let tree_id = index.write_tree()?;
 
         let parents = repo
             .head()
@@ -51,22 +79,187 @@ impl Backend for Git {
             Some("HEAD"),
             &comitter,
             &comitter,
-            &format!("Adding {:?}", link),
+            message,
             &repo.find_tree(tree_id)?,
             parents.as_slice(),
         )?;
 
-        // TODO: Push after commit and let it be a setting
+        log::info!(repository = self.config.repository_path.display().to_string(), message = message; "Committed links");
+
+        if self.config.push {
+            self.push(&repo)?;
+        }
+
+        Ok(())
+    }
+
+    /// Push the current branch to the configured remote, signing in with the
+    /// SSH agent or git's credential helpers
+    fn push(&self, repo: &Repository) -> Result<(), failure::Error> {
+        let head = repo.head()?;
+        let branch = head
+            .name()
+            .ok_or_else(|| failure::format_err!("HEAD is not a branch"))?;
+
+        let git_config = repo.config()?;
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(|url, username, allowed| {
+            if allowed.contains(CredentialType::SSH_KEY) {
+                Cred::ssh_key_from_agent(username.unwrap_or("git"))
+            } else {
+                Cred::credential_helper(&git_config, url, username)
+            }
+        });
+
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+
+        repo.find_remote(&self.config.remote)?
+            .push(&[&format!("{}:{}", branch, branch)], Some(&mut options))?;
+
+        log::info!(remote = self.config.remote.as_str(), branch = branch; "Pushed links");
 
         Ok(())
     }
 
-    fn sign_in(&self, access_token: &AccessToken) -> Result<(), ()> {
-        dbg!(access_token);
+    fn position(links: &[Link], link: &Link) -> Option<usize> {
+        let canonical = canonical_url(link.link());
+
+        links
+            .iter()
+            .position(|existing| canonical_url(existing.link()) == canonical)
+    }
+}
+
+#[derive(Debug)]
+pub struct GitFactory;
+
+impl BackendFactory for GitFactory {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn config_schema(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField {
+                key: "repository_path",
+                flag: "repo",
+                prompt: "In what repository should the links be stored?",
+                kind: FieldKind::Path,
+                default: env::current_dir()
+                    .ok()
+                    .and_then(|current_dir| current_dir.to_str().map(|dir| dir.to_owned())),
+            },
+            ConfigField {
+                key: "remote",
+                flag: "remote",
+                prompt: "What remote should be pushed to?",
+                kind: FieldKind::Text,
+                default: Some(default_remote()),
+            },
+            ConfigField {
+                key: "push",
+                flag: "push",
+                prompt: "Push after every commit?",
+                kind: FieldKind::Toggle,
+                default: Some("false".to_owned()),
+            },
+        ]
+    }
+
+    fn build(&self, config: &toml::Value) -> Result<Box<dyn Backend>, failure::Error> {
+        Ok(Box::new(Git {
+            config: config.clone().try_into::<GitConfig>()?,
+        }))
+    }
+}
+
+/// Register the Git backend in the given registry
+pub fn register(registry: &mut BackendRegistry) {
+    registry.register(Box::new(GitFactory));
+}
+
+impl fmt::Display for Git {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        fmt.write_fmt(format_args!("Git"))
+    }
+}
+
+impl Backend for Git {
+    fn add(&self, _link_keeper: &mut LinkKeeper) -> Result<(), failure::Error> {
+        log::debug!(repository = self.config.repository_path.display().to_string(); "Initializing the git repository");
+        //link_keeper.add_backend(self);
+        Repository::init(&self.config.repository_path)?;
+
+        Ok(())
+    }
+
+    fn add_link(&self, link: &Link) -> Result<(), failure::Error> {
+        log::debug!(link = link.link(); "Adding link to the git repository");
+
+        let mut links = self.read_links()?;
+
+        if Self::position(&links, link).is_some() {
+            return Ok(());
+        }
+
+        links.push(link.clone());
+
+        self.commit_links(&links, &format!("Adding {}", link.link()))
+    }
+
+    fn add_links(&self, new_links: &[Link]) -> Result<(), failure::Error> {
+        let mut links = self.read_links()?;
+        let before = links.len();
+
+        for link in new_links {
+            if Self::position(&links, link).is_none() {
+                links.push(link.clone());
+            }
+        }
+
+        match links.len() - before {
+            0 => Ok(()),
+            1 => {
+                let message = format!("Adding {}", links[before].link());
+                self.commit_links(&links, &message)
+            }
+            added => self.commit_links(&links, &format!("Adding {} links", added)),
+        }
+    }
+
+    fn update_link(&self, link: &Link) -> Result<(), failure::Error> {
+        let mut links = self.read_links()?;
+
+        match Self::position(&links, link) {
+            Some(index) if links[index] != *link => links[index] = link.clone(),
+            Some(_) => return Ok(()),
+            None => links.push(link.clone()),
+        }
+
+        self.commit_links(&links, &format!("Updating {}", link.link()))
+    }
+
+    fn remove_link(&self, link: &Link) -> Result<(), failure::Error> {
+        let mut links = self.read_links()?;
+
+        match Self::position(&links, link) {
+            Some(index) => links.remove(index),
+            None => return Ok(()),
+        };
+
+        self.commit_links(&links, &format!("Removing {}", link.link()))
+    }
+
+    fn list_links(&self) -> Result<Vec<Link>, failure::Error> {
+        self.read_links()
+    }
+
+    fn sign_in(&self, _access_token: &AccessToken) -> Result<(), failure::Error> {
         Ok(())
     }
 
-    fn sign_out(&self, access_token: &AccessToken) -> Result<(), ()> {
+    fn sign_out(&self, _access_token: &AccessToken) -> Result<(), failure::Error> {
         Ok(())
     }
 
@@ -0,0 +1,141 @@
+use git2::Repository;
+use link_keeper::backend::{Backend, BackendFactory};
+use link_keeper::{Link, ReadState};
+use link_keeper_git_backend::GitFactory;
+use serde_json::json;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// An empty repository for the test, removed first if an earlier run left it
+fn temp_repository(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("link-keeper-git-{}-{}", name, process::id()));
+
+    let _ = fs::remove_dir_all(&directory);
+    Repository::init(&directory).unwrap();
+
+    directory
+}
+
+fn backend(repository: &Path) -> Box<dyn Backend> {
+    let mut config = toml::value::Table::new();
+    config.insert(
+        "repository_path".to_owned(),
+        toml::Value::String(repository.display().to_string()),
+    );
+
+    GitFactory.build(&toml::Value::Table(config)).unwrap()
+}
+
+fn link(value: serde_json::Value) -> Link {
+    serde_json::from_value(value).unwrap()
+}
+
+fn commit_messages(repository: &Path) -> Vec<String> {
+    let repo = Repository::open(repository).unwrap();
+    let mut walk = repo.revwalk().unwrap();
+    walk.push_head().unwrap();
+
+    walk.map(|oid| {
+        repo.find_commit(oid.unwrap())
+            .unwrap()
+            .message()
+            .unwrap()
+            .to_owned()
+    })
+    .collect()
+}
+
+#[test]
+fn states_and_their_timestamps_round_trip() {
+    let repository = temp_repository("states");
+    let git = backend(&repository);
+
+    git.add_link(&Link::new("https://example.com", Some("examples")))
+        .unwrap();
+
+    let read = link(json!({
+        "link": "https://example.com",
+        "category": "examples",
+        "state": "read",
+        "state_changes": {
+            "reading": "2024-01-02T10:00:00Z",
+            "read": "2024-01-03T10:00:00Z"
+        }
+    }));
+    git.update_link(&read).unwrap();
+
+    let links = git.list_links().unwrap();
+    assert_eq!(links, vec![read]);
+    assert_eq!(links[0].state(), ReadState::Read);
+    assert_eq!(
+        links[0]
+            .state_changed(ReadState::Reading)
+            .unwrap()
+            .to_rfc3339(),
+        "2024-01-02T10:00:00+00:00"
+    );
+
+    let markdown = fs::read_to_string(repository.join("links.md")).unwrap();
+    assert!(markdown.contains("- <https://example.com> in examples (read)\n"));
+
+    assert_eq!(
+        commit_messages(&repository),
+        vec!["Updating https://example.com", "Adding https://example.com"]
+    );
+
+    fs::remove_dir_all(&repository).unwrap();
+}
+
+#[test]
+fn unchanged_links_arent_committed_again() {
+    let repository = temp_repository("unchanged");
+    let git = backend(&repository);
+    let link = Link::new("https://example.com", None);
+
+    git.add_link(&link).unwrap();
+    git.add_link(&Link::new("https://EXAMPLE.com/", None))
+        .unwrap();
+    git.update_link(&link).unwrap();
+
+    assert_eq!(git.list_links().unwrap(), vec![link.clone()]);
+    assert_eq!(commit_messages(&repository).len(), 1);
+
+    git.remove_link(&link).unwrap();
+    assert_eq!(git.list_links().unwrap(), vec![]);
+    assert_eq!(
+        commit_messages(&repository)[0],
+        "Removing https://example.com"
+    );
+
+    fs::remove_dir_all(&repository).unwrap();
+}
+
+#[test]
+fn annotations_are_rendered_next_to_the_links() {
+    let repository = temp_repository("annotations");
+    let git = backend(&repository);
+
+    let annotated = link(json!({
+        "link": "https://example.com",
+        "title": "Example",
+        "annotations": [
+            { "kind": "note", "text": "A note", "created": "2024-03-01T10:00:00Z" },
+            { "kind": "highlight", "text": "A quote", "created": "2024-03-02T10:00:00Z" }
+        ]
+    }));
+    git.add_link(&annotated).unwrap();
+
+    assert_eq!(git.list_links().unwrap(), vec![annotated]);
+    assert_eq!(
+        fs::read_to_string(repository.join("links.md")).unwrap(),
+        "# Links\n\n\
+         - [Example](<https://example.com>)\n  \
+         - Note (2024-03-01): A note\n  \
+         > A quote\n  \
+         > — highlighted 2024-03-02\n"
+    );
+
+    fs::remove_dir_all(&repository).unwrap();
+}
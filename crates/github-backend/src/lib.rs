@@ -1,5 +1,5 @@
 use link_keeper::{
-    backend::{AccessToken, Backend},
+    backend::{AccessToken, Backend, BackendFactory, BackendRegistry, ConfigField, FieldKind},
     Link, LinkKeeper,
 };
 use serde::{Deserialize, Serialize};
@@ -19,6 +19,55 @@ impl fmt::Display for Github {
 #[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
 pub struct GithubConfig {
     pub access_token: AccessToken,
+    /// The repository to store the links in, as `owner/name`
+    #[serde(default)]
+    pub repository: String,
+}
+
+#[derive(Debug)]
+pub struct GithubFactory;
+
+impl BackendFactory for GithubFactory {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    fn config_schema(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField {
+                key: "access_token",
+                flag: "token",
+                prompt: "Your Github access token",
+                kind: FieldKind::Secret,
+                default: None,
+            },
+            ConfigField {
+                key: "repository",
+                flag: "repo",
+                prompt: "In what repository should the links be stored? (owner/name)",
+                kind: FieldKind::Text,
+                default: None,
+            },
+        ]
+    }
+
+    fn build(&self, config: &toml::Value) -> Result<Box<dyn Backend>, failure::Error> {
+        let config = config.clone().try_into::<GithubConfig>()?;
+
+        if !config.repository.is_empty() && config.repository.split('/').count() != 2 {
+            return Err(failure::format_err!(
+                "The repository should be given as owner/name, not {}",
+                config.repository
+            ));
+        }
+
+        Ok(Box::new(Github { config }))
+    }
+}
+
+/// Register the Github backend in the given registry
+pub fn register(registry: &mut BackendRegistry) {
+    registry.register(Box::new(GithubFactory));
 }
 
 impl Backend for Github {
@@ -33,17 +82,16 @@ impl Backend for Github {
     //Github {config: GithubConfig {access_token: AccessToken("")}
     /*}*/
 
-    fn sign_in(&self, access_token: &AccessToken) -> Result<(), ()> {
-        dbg!(access_token);
+    fn sign_in(&self, _access_token: &AccessToken) -> Result<(), failure::Error> {
         Ok(())
     }
 
-    fn sign_out(&self, _access_token: &AccessToken) -> Result<(), ()> {
+    fn sign_out(&self, _access_token: &AccessToken) -> Result<(), failure::Error> {
         Ok(())
     }
 
     fn add_link(&self, link: &Link) -> Result<(), failure::Error> {
-        println!("Adding {:?} to {}", link, self);
+        log::debug!(link = link.link(); "Adding link to GitHub");
         Ok(())
     }
 
@@ -0,0 +1,490 @@
+//! Stores links in a Markdown vault, e.g. an Obsidian vault.
+//!
+//! With the `notes` layout every link becomes its own note with the URL,
+//! category, tags and creation date in the front matter. With the `daily`
+//! layout links are appended as list items, followed by their tags, to the note
+//! of the current day.
+
+use chrono::{Local, Utc};
+use link_keeper::{
+    backend::{AccessToken, Backend, BackendFactory, BackendRegistry, ConfigField, FieldKind},
+    normalize::canonical_url,
+    Link, LinkKeeper,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Vault {
+    pub config: VaultConfig,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum VaultLayout {
+    /// One note per link
+    #[default]
+    Notes,
+    /// Links appended to a note per day
+    Daily,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct VaultConfig {
+    pub vault_path: PathBuf,
+    /// Folder inside the vault where the notes are written
+    #[serde(default = "default_folder")]
+    pub folder: String,
+    #[serde(default)]
+    pub layout: VaultLayout,
+}
+
+fn default_folder() -> String {
+    "links".to_owned()
+}
+
+impl Vault {
+    fn folder_path(&self) -> PathBuf {
+        self.config.vault_path.join(&self.config.folder)
+    }
+
+    fn note_paths(&self) -> io::Result<Vec<PathBuf>> {
+        if !self.folder_path().exists() {
+            return Ok(vec![]);
+        }
+
+        let mut paths = fs::read_dir(self.folder_path())?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<PathBuf>>>()?;
+
+        paths.retain(|path| path.extension().is_some_and(|extension| extension == "md"));
+        paths.sort();
+
+        Ok(paths)
+    }
+
+    /// A file name for the note of a link that isn't used by another note
+    fn new_note_path(&self, link: &Link) -> PathBuf {
+        let slug = slugify(&canonical_url(link.link()));
+        let slug = if slug.is_empty() {
+            "link".to_owned()
+        } else {
+            slug
+        };
+
+        (1..)
+            .map(|number| match number {
+                1 => self.folder_path().join(format!("{}.md", slug)),
+                _ => self.folder_path().join(format!("{}-{}.md", slug, number)),
+            })
+            .find(|path| !path.exists())
+            .expect("Ran out of note file names")
+    }
+
+    fn write_note(&self, link: &Link) -> io::Result<()> {
+        let contents = format!(
+            "---\n{}created: {}\n---\n\n<{}>\n",
+            front_matter(link),
+            Utc::now().to_rfc3339(),
+            link.link()
+        );
+
+        fs::write(self.new_note_path(link), contents)
+    }
+
+    fn append_to_daily_note(&self, link: &Link) -> io::Result<()> {
+        let path = self
+            .folder_path()
+            .join(format!("{}.md", Local::now().format("%Y-%m-%d")));
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        writeln!(file, "{}", daily_item(link))
+    }
+
+    /// Rewrite the notes that contain the link with its current category and
+    /// tags
+    fn rewrite_notes(&self, link: &Link) -> io::Result<()> {
+        let canonical = canonical_url(link.link());
+
+        for path in self.note_paths()? {
+            let contents = fs::read_to_string(&path)?;
+            let rewritten = if is_link_note(&contents) {
+                rewrite_front_matter(&contents, link, &canonical)
+            } else {
+                rewrite_daily_items(&contents, link, &canonical)
+            };
+
+            if rewritten != contents {
+                fs::write(&path, rewritten)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delete the note of the link, or its list item in daily notes
+    fn remove_from_notes(&self, link: &Link) -> io::Result<()> {
+        let canonical = canonical_url(link.link());
+        let is_link = |link: &Link| canonical_url(link.link()) == canonical;
+
+        for path in self.note_paths()? {
+            let contents = fs::read_to_string(&path)?;
+
+            if is_link_note(&contents) {
+                if parse_note(&contents).iter().any(is_link) {
+                    fs::remove_file(&path)?;
+                }
+
+                continue;
+            }
+
+            let remaining = contents
+                .lines()
+                .filter(|line| !parse_item(line).as_ref().is_some_and(is_link))
+                .map(|line| format!("{}\n", line))
+                .collect::<String>();
+
+            if remaining.trim() != contents.trim() {
+                fs::write(&path, remaining)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The `url`, `category` and `tags` lines of the front matter of a note
+fn front_matter(link: &Link) -> String {
+    let category = link
+        .category()
+        .map(|category| format!("category: {}\n", quote(category)))
+        .unwrap_or_default();
+
+    format!(
+        "url: {}\n{}tags: [{}]\n",
+        quote(link.link()),
+        category,
+        link.tags()
+            .iter()
+            .map(|tag| tag_name(tag))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+fn daily_item(link: &Link) -> String {
+    let tags = link
+        .tags()
+        .iter()
+        .map(|tag| format!(" #{}", tag_name(tag)))
+        .collect::<String>();
+
+    format!("- <{}>{}", link.link(), tags)
+}
+
+fn rewrite_front_matter(contents: &str, link: &Link, canonical: &str) -> String {
+    let is_link = parse_note(contents)
+        .iter()
+        .any(|existing| canonical_url(existing.link()) == canonical);
+
+    let end = match contents[3..].find("\n---") {
+        Some(end) if is_link => end + 3,
+        _ => return contents.to_owned(),
+    };
+
+    // Keep everything in the front matter except what is written again
+    let kept = contents[3..end]
+        .lines()
+        .filter(|line| {
+            let key = line.split(':').next().unwrap_or("").trim();
+            !line.trim().is_empty() && !["url", "category", "tags"].contains(&key)
+        })
+        .map(|line| format!("{}\n", line))
+        .collect::<String>();
+
+    format!(
+        "---\n{}{}{}",
+        front_matter(link),
+        kept,
+        &contents[end + 1..]
+    )
+}
+
+fn rewrite_daily_items(contents: &str, link: &Link, canonical: &str) -> String {
+    let mut rewritten = contents
+        .lines()
+        .map(|line| {
+            let is_link = parse_item(line)
+                .is_some_and(|existing| canonical_url(existing.link()) == canonical);
+
+            if is_link {
+                daily_item(link)
+            } else {
+                line.to_owned()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    if contents.ends_with('\n') {
+        rewritten.push('\n');
+    }
+
+    rewritten
+}
+
+/// Turn a URL into something that is safe to use as a file name
+fn slugify(link: &str) -> String {
+    let without_scheme = link.splitn(2, "://").last().unwrap_or(link);
+
+    let slug = without_scheme
+        .chars()
+        .map(|character| {
+            if character.is_alphanumeric() {
+                character.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>()
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-");
+
+    slug.chars().take(80).collect()
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.trim().replace(' ', "-")
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        value[1..value.len() - 1]
+            .replace("\\\"", "\"")
+            .replace("\\\\", "\\")
+    } else {
+        value.to_owned()
+    }
+}
+
+/// The front matter of a note and the rest of it, `None` if the note has no
+/// front matter
+fn split_front_matter(contents: &str) -> Option<(&str, &str)> {
+    let rest = contents
+        .strip_prefix("---\n")
+        .or_else(|| contents.strip_prefix("---\r\n"))?;
+
+    if rest.starts_with("---") {
+        return Some(("", rest.split_once('\n').map_or("", |(_, body)| body)));
+    }
+
+    let end = rest.find("\n---")?;
+    let body = rest[end + 4..]
+        .split_once('\n')
+        .map_or("", |(_, body)| body);
+
+    Some((&rest[..end], body))
+}
+
+fn front_matter_values(front_matter: &str) -> Vec<(&str, &str)> {
+    front_matter
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            Some((key.trim(), value.trim()))
+        })
+        .collect()
+}
+
+/// Whether the note holds a single link in its front matter, as written with
+/// the `notes` layout. Other notes, like daily notes made from a template
+/// with front matter, have their links in list items.
+fn is_link_note(contents: &str) -> bool {
+    split_front_matter(contents).is_some_and(|(front_matter, _)| {
+        front_matter_values(front_matter)
+            .iter()
+            .any(|(key, _)| *key == "url")
+    })
+}
+
+/// Read the links from a note, either from its front matter or from the list
+/// items of a daily note
+fn parse_note(contents: &str) -> Vec<Link> {
+    let (front_matter, body) = split_front_matter(contents).unwrap_or(("", contents));
+    let front_matter = front_matter_values(front_matter);
+
+    let value_of = |key: &str| {
+        front_matter
+            .iter()
+            .find(|(front_matter_key, _)| *front_matter_key == key)
+            .map(|(_, value)| *value)
+    };
+
+    if let Some(url) = value_of("url") {
+        let category = value_of("category").map(unquote);
+        let tags = value_of("tags")
+            .map(|tags| {
+                tags.trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .split(',')
+                    .map(unquote)
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
+
+        return vec![Link::new(&unquote(url), category.as_deref()).with_tags(tags)];
+    }
+
+    body.lines().filter_map(parse_item).collect()
+}
+
+/// The link of a list item like `- <https://example.com> #tag`
+fn parse_item(line: &str) -> Option<Link> {
+    let item = line.trim();
+    let item = item.strip_prefix("- ").unwrap_or(item).strip_prefix('<')?;
+    let end = item.find('>')?;
+    let url = &item[..end];
+
+    let (scheme, rest) = url.split_once("://")?;
+    let is_scheme = scheme
+        .chars()
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || "+-.".contains(character));
+
+    if !is_scheme || rest.is_empty() || url.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let tags = item[end + 1..]
+        .split_whitespace()
+        .filter_map(|word| word.strip_prefix('#'))
+        .map(|tag| tag.to_owned())
+        .collect();
+
+    Some(Link::new(url, None).with_tags(tags))
+}
+
+#[derive(Debug)]
+pub struct VaultFactory;
+
+impl BackendFactory for VaultFactory {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    fn config_schema(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField {
+                key: "vault_path",
+                flag: "vault",
+                prompt: "Where is the vault located?",
+                kind: FieldKind::Path,
+                default: None,
+            },
+            ConfigField {
+                key: "folder",
+                flag: "folder",
+                prompt: "In what folder of the vault should the links be stored?",
+                kind: FieldKind::Text,
+                default: Some(default_folder()),
+            },
+            ConfigField {
+                key: "layout",
+                flag: "layout",
+                prompt: "Store every link as a note or in daily notes? (notes/daily)",
+                kind: FieldKind::Text,
+                default: Some("notes".to_owned()),
+            },
+        ]
+    }
+
+    fn build(&self, config: &toml::Value) -> Result<Box<dyn Backend>, failure::Error> {
+        Ok(Box::new(Vault {
+            config: config.clone().try_into::<VaultConfig>()?,
+        }))
+    }
+}
+
+/// Register the Vault backend in the given registry
+pub fn register(registry: &mut BackendRegistry) {
+    registry.register(Box::new(VaultFactory));
+}
+
+impl fmt::Display for Vault {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        fmt.write_fmt(format_args!("Vault"))
+    }
+}
+
+impl Backend for Vault {
+    fn add(&self, _link_keeper: &mut LinkKeeper) -> Result<(), failure::Error> {
+        fs::create_dir_all(self.folder_path())?;
+
+        Ok(())
+    }
+
+    fn sign_in(&self, _access_token: &AccessToken) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn sign_out(&self, _access_token: &AccessToken) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn add_link(&self, link: &Link) -> Result<(), failure::Error> {
+        let canonical = canonical_url(link.link());
+
+        if self
+            .list_links()?
+            .iter()
+            .any(|existing| canonical_url(existing.link()) == canonical)
+        {
+            return Ok(());
+        }
+
+        fs::create_dir_all(self.folder_path())?;
+
+        match self.config.layout {
+            VaultLayout::Notes => self.write_note(link)?,
+            VaultLayout::Daily => self.append_to_daily_note(link)?,
+        }
+
+        Ok(())
+    }
+
+    fn remove_link(&self, link: &Link) -> Result<(), failure::Error> {
+        Ok(self.remove_from_notes(link)?)
+    }
+
+    fn update_link(&self, link: &Link) -> Result<(), failure::Error> {
+        Ok(self.rewrite_notes(link)?)
+    }
+
+    fn list_links(&self) -> Result<Vec<Link>, failure::Error> {
+        self.note_paths()?
+            .iter()
+            .map(|path| fs::read_to_string(path).map(|contents| parse_note(&contents)))
+            .collect::<io::Result<Vec<Vec<Link>>>>()
+            .map(|links| links.into_iter().flatten().collect())
+            .map_err(|err| err.into())
+    }
+
+    fn get_toml_config(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(&self.config)
+    }
+}
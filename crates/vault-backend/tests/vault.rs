@@ -0,0 +1,217 @@
+use chrono::Local;
+use link_keeper::backend::{Backend, BackendFactory};
+use link_keeper::Link;
+use link_keeper_vault_backend::VaultFactory;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// An empty vault for the test, removed first if an earlier run left it
+fn temp_vault(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("link-keeper-vault-{}-{}", name, process::id()));
+
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(directory.join("links")).unwrap();
+
+    directory
+}
+
+fn backend(vault: &Path, layout: &str) -> Box<dyn Backend> {
+    let mut config = toml::value::Table::new();
+    config.insert(
+        "vault_path".to_owned(),
+        toml::Value::String(vault.display().to_string()),
+    );
+    config.insert("layout".to_owned(), toml::Value::String(layout.to_owned()));
+
+    VaultFactory.build(&toml::Value::Table(config)).unwrap()
+}
+
+fn urls(backend: &dyn Backend) -> Vec<String> {
+    let mut urls = backend
+        .list_links()
+        .unwrap()
+        .iter()
+        .map(|link| link.link().to_owned())
+        .collect::<Vec<String>>();
+    urls.sort();
+
+    urls
+}
+
+fn notes(vault: &Path) -> Vec<String> {
+    let mut notes = fs::read_dir(vault.join("links"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect::<Vec<String>>();
+    notes.sort();
+
+    notes
+}
+
+fn today(vault: &Path) -> PathBuf {
+    vault
+        .join("links")
+        .join(format!("{}.md", Local::now().format("%Y-%m-%d")))
+}
+
+#[test]
+fn every_link_gets_a_note_with_front_matter() {
+    let vault = temp_vault("notes");
+    let notes_backend = backend(&vault, "notes");
+
+    let link = Link::new("https://example.com/page", Some("articles"))
+        .with_tags(vec!["lang/rust".to_owned(), "to read".to_owned()]);
+    notes_backend.add_link(&link).unwrap();
+
+    assert_eq!(notes(&vault), vec!["example-com-page.md"]);
+
+    let note = fs::read_to_string(vault.join("links").join("example-com-page.md")).unwrap();
+    assert!(note.starts_with(
+        "---\nurl: \"https://example.com/page\"\ncategory: \"articles\"\ntags: [lang/rust, to-read]\ncreated: "
+    ));
+    assert!(note.ends_with("---\n\n<https://example.com/page>\n"));
+
+    let listed = notes_backend.list_links().unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].link(), "https://example.com/page");
+    assert_eq!(listed[0].category(), Some("articles"));
+    assert_eq!(listed[0].tags(), ["lang/rust", "to-read"]);
+
+    fs::remove_dir_all(&vault).unwrap();
+}
+
+#[test]
+fn canonical_duplicates_arent_added_again() {
+    for layout in &["notes", "daily"] {
+        let vault = temp_vault(&format!("duplicates-{}", layout));
+        let vault_backend = backend(&vault, layout);
+
+        vault_backend
+            .add_link(&Link::new("https://example.com/page", None))
+            .unwrap();
+        vault_backend
+            .add_link(&Link::new(
+                "HTTPS://Example.com/page/?utm_source=feed#top",
+                None,
+            ))
+            .unwrap();
+
+        assert_eq!(
+            urls(vault_backend.as_ref()),
+            vec!["https://example.com/page"]
+        );
+        assert_eq!(notes(&vault).len(), 1);
+
+        fs::remove_dir_all(&vault).unwrap();
+    }
+}
+
+#[test]
+fn notes_are_updated_and_removed() {
+    let vault = temp_vault("notes-update");
+    let notes_backend = backend(&vault, "notes");
+
+    notes_backend
+        .add_link(&Link::new("https://example.com/a", None))
+        .unwrap();
+    notes_backend
+        .add_link(&Link::new("https://example.com/b", None))
+        .unwrap();
+
+    let path = vault.join("links").join("example-com-a.md");
+    let note = fs::read_to_string(&path).unwrap();
+    fs::write(
+        &path,
+        note.replace("created:", "aliases: [a]\ncreated:") + "My own text\n",
+    )
+    .unwrap();
+
+    notes_backend
+        .update_link(
+            &Link::new("https://example.com/a", Some("reading")).with_tags(vec!["new".to_owned()]),
+        )
+        .unwrap();
+
+    let updated = fs::read_to_string(&path).unwrap();
+    assert!(updated.starts_with(
+        "---\nurl: \"https://example.com/a\"\ncategory: \"reading\"\ntags: [new]\naliases: [a]\ncreated: "
+    ));
+    assert!(updated.ends_with("\n<https://example.com/a>\nMy own text\n"));
+
+    notes_backend
+        .remove_link(&Link::new("https://example.com/a", None))
+        .unwrap();
+
+    assert_eq!(notes(&vault), vec!["example-com-b.md"]);
+    assert_eq!(urls(notes_backend.as_ref()), vec!["https://example.com/b"]);
+
+    fs::remove_dir_all(&vault).unwrap();
+}
+
+#[test]
+fn daily_notes_with_front_matter_are_scanned_for_links() {
+    let vault = temp_vault("daily-front-matter");
+    let daily = backend(&vault, "daily");
+
+    fs::write(
+        today(&vault),
+        "---\ntags: [daily]\ncreated: 2024-01-01\n---\n\n# Today\n\n",
+    )
+    .unwrap();
+
+    let link = Link::new("https://example.com/a", None).with_tags(vec!["rust".to_owned()]);
+    daily.add_link(&link).unwrap();
+    daily.add_link(&link).unwrap();
+    daily
+        .add_link(&Link::new("https://example.com/b", None))
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(today(&vault)).unwrap(),
+        "---\ntags: [daily]\ncreated: 2024-01-01\n---\n\n# Today\n\n\
+         - <https://example.com/a> #rust\n\
+         - <https://example.com/b>\n"
+    );
+
+    let listed = daily.list_links().unwrap();
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0].tags(), ["rust"]);
+
+    daily
+        .update_link(&Link::new("https://example.com/a", None).with_tags(vec!["go".to_owned()]))
+        .unwrap();
+    daily
+        .remove_link(&Link::new("https://example.com/b", None))
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(today(&vault)).unwrap(),
+        "---\ntags: [daily]\ncreated: 2024-01-01\n---\n\n# Today\n\n\
+         - <https://example.com/a> #go\n"
+    );
+
+    fs::remove_dir_all(&vault).unwrap();
+}
+
+#[test]
+fn only_items_with_a_url_are_links() {
+    let vault = temp_vault("daily-html");
+    let daily = backend(&vault, "daily");
+
+    fs::write(
+        vault.join("links").join("2024-01-01.md"),
+        "<details>\n<summary>Links</summary>\n\
+         - <https://example.com/a>\n\
+         - <not a link>\n\
+         - <mailto:someone@example.com>\n\
+         - <://example.com>\n\
+         </details>\n",
+    )
+    .unwrap();
+
+    assert_eq!(urls(daily.as_ref()), vec!["https://example.com/a"]);
+
+    fs::remove_dir_all(&vault).unwrap();
+}
@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationKind {
+    /// Free text written about the link
+    Note,
+    /// A passage quoted from the page
+    Highlight,
+}
+
+/// A note or highlight attached to a link
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub kind: AnnotationKind,
+    pub text: String,
+    pub created: DateTime<Utc>,
+}
+
+impl Annotation {
+    pub fn note(text: &str) -> Self {
+        Annotation {
+            kind: AnnotationKind::Note,
+            text: text.trim().to_owned(),
+            created: Utc::now(),
+        }
+    }
+
+    pub fn highlight(text: &str) -> Self {
+        Annotation {
+            kind: AnnotationKind::Highlight,
+            text: text.trim().to_owned(),
+            created: Utc::now(),
+        }
+    }
+}
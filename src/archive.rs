@@ -0,0 +1,207 @@
+//! Offline snapshots of pages, stored content-addressed in an archive
+//! directory next to the store.
+
+use crate::bookmarks::{decode_entities, tokenize, Token};
+use crate::http::{self, HttpClient, Request};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Where the snapshot of a link is stored in the archive directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Hash of the raw HTML
+    pub html: String,
+    /// Hash of the extracted text
+    pub text: String,
+    pub archived: DateTime<Utc>,
+}
+
+impl Snapshot {
+    pub fn html_path(&self, directory: &Path) -> PathBuf {
+        directory.join(format!("{}.html", self.html))
+    }
+
+    pub fn text_path(&self, directory: &Path) -> PathBuf {
+        directory.join(format!("{}.txt", self.text))
+    }
+}
+
+pub fn hash(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+/// Elements whose content is never part of the readable text
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "script", "style", "noscript", "nav", "header", "footer", "aside", "form", "svg", "template",
+    "iframe", "button", "select",
+];
+
+/// Elements that start a new paragraph of text
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p",
+    "div",
+    "br",
+    "li",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "pre",
+    "blockquote",
+    "tr",
+    "section",
+    "article",
+    "main",
+    "dt",
+    "dd",
+    "figcaption",
+];
+
+/// Extract the readable text of a page. If the page has an `<article>` or a
+/// `<main>` element only its content is used, navigation, scripts and the
+/// like are left out.
+pub fn readable_text(html: &str) -> String {
+    let tokens = tokenize(html);
+
+    let content_element = ["article", "main", "body"].iter().find(|element| {
+        tokens
+            .iter()
+            .any(|token| matches!(token, Token::Open(name, _) if name == *element))
+    });
+
+    let mut paragraphs: Vec<String> = vec![];
+    let mut paragraph = String::new();
+    let mut inside_content = content_element.is_none();
+    let mut skipping: Option<&str> = None;
+
+    let end_paragraph = |paragraph: &mut String, paragraphs: &mut Vec<String>| {
+        let text = paragraph
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        if !text.is_empty() {
+            paragraphs.push(text);
+        }
+
+        paragraph.clear();
+    };
+
+    for token in &tokens {
+        match token {
+            Token::Close(name) if skipping == Some(name.as_str()) => skipping = None,
+            _ if skipping.is_some() => {}
+            Token::Open(name, _) if SKIPPED_ELEMENTS.contains(&name.as_str()) => {
+                skipping = SKIPPED_ELEMENTS
+                    .iter()
+                    .find(|element| **element == name.as_str())
+                    .copied();
+            }
+            Token::Open(name, _) if Some(&name.as_str()) == content_element => {
+                inside_content = true;
+            }
+            Token::Close(name) if Some(&name.as_str()) == content_element => {
+                end_paragraph(&mut paragraph, &mut paragraphs);
+                inside_content = false;
+            }
+            Token::Open(name, _) | Token::Close(name)
+                if BLOCK_ELEMENTS.contains(&name.as_str()) =>
+            {
+                end_paragraph(&mut paragraph, &mut paragraphs);
+            }
+            Token::Text(text) if inside_content => {
+                paragraph.push_str(&decode_entities(text));
+                paragraph.push(' ');
+            }
+            _ => {}
+        }
+    }
+
+    end_paragraph(&mut paragraph, &mut paragraphs);
+
+    paragraphs.join("\n\n")
+}
+
+/// Fetches pages and stores them in the archive directory
+#[derive(Debug)]
+pub struct Archiver {
+    client: Box<dyn HttpClient>,
+    timeout: Duration,
+    max_body_bytes: u64,
+}
+
+impl Archiver {
+    pub fn new(client: Box<dyn HttpClient>) -> Self {
+        Archiver {
+            client,
+            timeout: Duration::from_secs(30),
+            max_body_bytes: 10 * 1024 * 1024,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Pages larger than this are not archived
+    pub fn max_body_bytes(mut self, max_body_bytes: u64) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    /// Fetch the page and write its HTML and readable text to the directory
+    pub fn archive(&self, url: &str, directory: &Path) -> Result<Snapshot, failure::Error> {
+        let request = Request::get(url)
+            .timeout(self.timeout)
+            .max_body_bytes(self.max_body_bytes);
+
+        let (response, _redirects) = http::fetch(self.client.as_ref(), &request)?;
+
+        if !response.is_success() {
+            return Err(failure::format_err!(
+                "Fetching {} failed with status {}",
+                url,
+                response.status
+            ));
+        }
+
+        if response.truncated {
+            return Err(failure::format_err!(
+                "{} is larger than {} bytes",
+                url,
+                self.max_body_bytes
+            ));
+        }
+
+        let text = readable_text(&response.text());
+
+        let snapshot = Snapshot {
+            html: hash(&response.body),
+            text: hash(text.as_bytes()),
+            archived: Utc::now(),
+        };
+
+        fs::create_dir_all(directory)?;
+        write_if_missing(&snapshot.html_path(directory), &response.body)?;
+        write_if_missing(&snapshot.text_path(directory), text.as_bytes())?;
+
+        Ok(snapshot)
+    }
+}
+
+/// Content-addressed files never change, so existing ones are left alone
+fn write_if_missing(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if path.exists() {
+        Ok(())
+    } else {
+        fs::write(path, contents)
+    }
+}
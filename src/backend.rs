@@ -1,26 +1,143 @@
 use crate::{Link, LinkKeeper};
-use chrono;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(Debug)]
-pub struct Data {
-    url: String,
-    name: String,
-    date_time: chrono::DateTime<chrono::offset::Utc>,
-}
-
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
 pub struct AccessToken(pub String);
 
 pub trait Backend: fmt::Debug + fmt::Display {
-    // TODO: Better return types!
     fn add(&self, link_keeper: &mut LinkKeeper) -> Result<(), failure::Error>;
-    fn sign_in(&self, access_token: &AccessToken) -> Result<(), ()>;
-    fn sign_out(&self, access_token: &AccessToken) -> Result<(), ()>;
+    fn sign_in(&self, access_token: &AccessToken) -> Result<(), failure::Error>;
+    fn sign_out(&self, access_token: &AccessToken) -> Result<(), failure::Error>;
     fn add_link(&self, link: &Link) -> Result<(), failure::Error>;
+    /// Add many links at once, backends that can should store them in one
+    /// go, e.g. in a single commit
+    fn add_links(&self, links: &[Link]) -> Result<(), failure::Error> {
+        links.iter().try_for_each(|link| self.add_link(link))
+    }
+    /// Backends that only ever append links can leave this out, the link
+    /// is then left where it is
+    fn remove_link(&self, link: &Link) -> Result<(), failure::Error> {
+        log::debug!(backend = self.to_string(), link = link.link(); "Backend keeps removed links");
+        Ok(())
+    }
+    /// Store changes to a link that was added before, e.g. its tags.
+    /// Backends that only ever append links can leave this out.
+    fn update_link(&self, link: &Link) -> Result<(), failure::Error> {
+        log::debug!(backend = self.to_string(), link = link.link(); "Backend keeps links as they were added");
+        Ok(())
+    }
+    fn list_links(&self) -> Result<Vec<Link>, failure::Error> {
+        Err(failure::format_err!(
+            "{} does not support listing links",
+            self
+        ))
+    }
     fn get_toml_config(&self) -> Result<String, toml::ser::Error>;
-    //fn send(&self, data: &Data) -> Result<Response, ()>;
     //fn get();
     //fn get_all();
 }
+
+/// How the value of a configuration field should be asked for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldKind {
+    Text,
+    /// Input is hidden while typing, e.g. access tokens.
+    Secret,
+    Path,
+    /// A yes or no question, stored as a boolean.
+    Toggle,
+}
+
+/// A single key in a backend's `[backends.<name>]` configuration table.
+#[derive(Debug, Clone)]
+pub struct ConfigField {
+    pub key: &'static str,
+    /// The name of the command line flag that sets the field without asking,
+    /// e.g. `repo` for `--repo`. Secrets are read from the environment
+    /// variable given with `--<flag>-env` instead.
+    pub flag: &'static str,
+    pub prompt: &'static str,
+    pub kind: FieldKind,
+    pub default: Option<String>,
+}
+
+/// Knows how to describe and construct one kind of backend.
+///
+/// Backend crates implement this and register it in a `BackendRegistry`, the
+/// registry is then used to both set up new backends and to restore the
+/// activated ones from the configuration file. The CLI registers every
+/// `link-keeper-<name>-backend` crate it depends on through its `register`
+/// function.
+pub trait BackendFactory: fmt::Debug {
+    /// The name used as key in the configuration file, e.g. `git`.
+    fn name(&self) -> &'static str;
+    /// The fields that make up the backend's configuration, in the order they
+    /// should be asked for during setup.
+    fn config_schema(&self) -> Vec<ConfigField>;
+    fn build(&self, config: &toml::Value) -> Result<Box<dyn Backend>, failure::Error>;
+}
+
+#[derive(Debug, Default)]
+pub struct BackendRegistry {
+    factories: Vec<Box<dyn BackendFactory>>,
+}
+
+impl BackendRegistry {
+    pub fn new() -> Self {
+        BackendRegistry { factories: vec![] }
+    }
+
+    /// Register a factory, replacing any earlier one with the same name
+    pub fn register(&mut self, factory: Box<dyn BackendFactory>) {
+        self.factories
+            .retain(|registered| registered.name() != factory.name());
+        self.factories.push(factory);
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.factories
+            .iter()
+            .map(|factory| factory.name())
+            .collect::<Vec<&'static str>>()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn BackendFactory> {
+        self.factories
+            .iter()
+            .find(|factory| factory.name().eq_ignore_ascii_case(name))
+            .map(|factory| factory.as_ref())
+    }
+
+    pub fn create(
+        &self,
+        name: &str,
+        config: &toml::Value,
+    ) -> Result<Box<dyn Backend>, failure::Error> {
+        self.get(name)
+            .ok_or_else(|| failure::format_err!("Unknown backend: {}", name))
+            .and_then(|factory| factory.build(config))
+    }
+
+    /// Construct every backend found under the `backends` table of the
+    /// configuration. Unknown and invalid backends are logged and left out,
+    /// so the others keep working and the configuration can still be fixed.
+    pub fn load_backends(&self, toml_config: &toml::Value) -> Vec<Box<dyn Backend>> {
+        toml_config
+            .get("backends")
+            .and_then(|backends| backends.as_table())
+            .map(|backends| {
+                backends
+                    .iter()
+                    .filter_map(|(name, config)| match self.create(name, config) {
+                        Ok(backend) => Some(backend),
+                        Err(err) => {
+                            log::warn!(backend = name.as_str(), error = err.to_string(); "Skipping backend that can't be loaded");
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
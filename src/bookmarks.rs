@@ -0,0 +1,303 @@
+//! Reading and writing the Netscape bookmark file format that browsers use
+//! for their HTML bookmark import and export.
+
+use crate::Link;
+use chrono::{TimeZone, Utc};
+use std::collections::BTreeMap;
+
+/// Separator between nested folder names in a category
+pub const CATEGORY_SEPARATOR: &str = "/";
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Token<'a> {
+    /// An opening tag with its lowercased name and attributes
+    Open(String, Vec<(String, String)>),
+    /// A closing tag with its lowercased name
+    Close(String),
+    Text(&'a str),
+}
+
+pub(crate) fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        match rest.find('<') {
+            // Comments can contain `>`, they only end at `-->`
+            Some(0) if rest.starts_with("<!--") => match rest[4..].find("-->") {
+                Some(end) => rest = &rest[4 + end + 3..],
+                None => {
+                    tokens.push(Token::Text(rest));
+                    break;
+                }
+            },
+            Some(0) => {
+                // A tag that's never closed, e.g. in a truncated page, is text
+                let end = match rest.find('>') {
+                    Some(end) => end,
+                    None => {
+                        tokens.push(Token::Text(rest));
+                        break;
+                    }
+                };
+                let tag = &rest[1..end];
+
+                if let Some(closing) = tag.strip_prefix('/') {
+                    tokens.push(Token::Close(closing.trim().to_lowercase()));
+                } else if !tag.starts_with('!') {
+                    tokens.push(parse_open_tag(tag));
+                }
+
+                rest = &rest[end + 1..];
+            }
+            Some(start) => {
+                tokens.push(Token::Text(&rest[..start]));
+                rest = &rest[start..];
+            }
+            None => {
+                tokens.push(Token::Text(rest));
+                rest = "";
+            }
+        }
+    }
+
+    tokens
+}
+
+fn parse_open_tag(tag: &str) -> Token<'_> {
+    let tag = tag.trim().trim_end_matches('/');
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let mut rest = tag[name_end..].trim_start();
+    let mut attributes = vec![];
+
+    while !rest.is_empty() {
+        let key_end = rest
+            .find(|character: char| character == '=' || character.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = rest[..key_end].to_lowercase();
+        rest = rest[key_end..].trim_start();
+
+        if !rest.starts_with('=') {
+            attributes.push((key, "".to_owned()));
+            continue;
+        }
+
+        rest = rest[1..].trim_start();
+
+        let (value, remaining) = match rest.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => {
+                let end = rest[1..].find(quote).map_or(rest.len(), |end| end + 1);
+                (&rest[1..end], rest.get(end + 1..).unwrap_or(""))
+            }
+            _ => {
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+
+        attributes.push((key, decode_entities(value)));
+        rest = remaining.trim_start();
+    }
+
+    Token::Open(tag[..name_end].to_lowercase(), attributes)
+}
+
+pub(crate) fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+pub(crate) fn encode_entities(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+pub(crate) fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Collect the text up to the closing tag with the given name
+pub(crate) fn text_until<'a, I>(tokens: &mut I, name: &str) -> String
+where
+    I: Iterator<Item = &'a Token<'a>>,
+{
+    let mut text = String::new();
+
+    for token in tokens {
+        match token {
+            Token::Text(content) => text.push_str(content),
+            Token::Close(closing) if closing == name => break,
+            _ => {}
+        }
+    }
+
+    decode_entities(text.trim())
+}
+
+/// Parse the links of a Netscape bookmark file. Nested folders become the
+/// category of the links in them, joined by `CATEGORY_SEPARATOR`.
+pub fn parse(html: &str) -> Vec<Link> {
+    let tokens = tokenize(html);
+    let mut links = vec![];
+    let mut folders: Vec<Option<String>> = vec![];
+    let mut pending_folder: Option<String> = None;
+    let mut tokens = tokens.iter();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Open(name, _) if name == "h3" => {
+                pending_folder = Some(text_until(&mut tokens, "h3"));
+            }
+            Token::Open(name, _) if name == "dl" => {
+                folders.push(pending_folder.take());
+            }
+            Token::Close(name) if name == "dl" => {
+                folders.pop();
+            }
+            Token::Open(name, attributes) if name == "a" => {
+                let href = match attribute(attributes, "href") {
+                    Some(href) if !href.is_empty() => href.to_owned(),
+                    _ => continue,
+                };
+                let title = text_until(&mut tokens, "a");
+
+                let category = folders
+                    .iter()
+                    .filter_map(|folder| folder.as_deref())
+                    .collect::<Vec<&str>>()
+                    .join(CATEGORY_SEPARATOR);
+
+                let tags = attribute(attributes, "tags")
+                    .map(|tags| {
+                        tags.split(',')
+                            .map(|tag| tag.trim().to_owned())
+                            .filter(|tag| !tag.is_empty())
+                            .collect::<Vec<String>>()
+                    })
+                    .unwrap_or_default();
+
+                let added = attribute(attributes, "add_date")
+                    .and_then(|date| date.parse::<i64>().ok())
+                    .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single());
+
+                let mut link = Link::new(
+                    &href,
+                    if category.is_empty() {
+                        None
+                    } else {
+                        Some(&category)
+                    },
+                )
+                .with_tags(tags);
+
+                link.added = added;
+
+                if !title.is_empty() {
+                    link.title = Some(title);
+                }
+
+                links.push(link);
+            }
+            _ => {}
+        }
+    }
+
+    links
+}
+
+#[derive(Default)]
+struct Folder<'a> {
+    folders: BTreeMap<&'a str, Folder<'a>>,
+    links: Vec<&'a Link>,
+}
+
+impl<'a> Folder<'a> {
+    fn insert(&mut self, path: &[&'a str], link: &'a Link) {
+        match path.split_first() {
+            Some((name, rest)) => self.folders.entry(name).or_default().insert(rest, link),
+            None => self.links.push(link),
+        }
+    }
+
+    fn write(&self, html: &mut String, depth: usize) {
+        let indent = "    ".repeat(depth);
+
+        for (name, folder) in &self.folders {
+            html.push_str(&format!(
+                "{}<DT><H3>{}</H3>\n{}<DL><p>\n",
+                indent,
+                encode_entities(name),
+                indent
+            ));
+            folder.write(html, depth + 1);
+            html.push_str(&format!("{}</DL><p>\n", indent));
+        }
+
+        for link in &self.links {
+            let mut attributes = format!("HREF=\"{}\"", encode_entities(link.link()));
+
+            if let Some(added) = link.added {
+                attributes.push_str(&format!(" ADD_DATE=\"{}\"", added.timestamp()));
+            }
+
+            if !link.tags().is_empty() {
+                attributes.push_str(&format!(
+                    " TAGS=\"{}\"",
+                    encode_entities(&link.tags().join(","))
+                ));
+            }
+
+            html.push_str(&format!(
+                "{}<DT><A {}>{}</A>\n",
+                indent,
+                attributes,
+                encode_entities(link.title().unwrap_or_else(|| link.link()))
+            ));
+        }
+    }
+}
+
+/// Write links as a Netscape bookmark file with one folder per category
+pub fn write(links: &[Link]) -> String {
+    let mut root = Folder::default();
+
+    for link in links {
+        let path = link
+            .category()
+            .map(|category| {
+                category
+                    .split(CATEGORY_SEPARATOR)
+                    .map(|folder| folder.trim())
+                    .filter(|folder| !folder.is_empty())
+                    .collect::<Vec<&str>>()
+            })
+            .unwrap_or_default();
+
+        root.insert(&path, link);
+    }
+
+    let mut html = "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+                    <!-- This is an automatically generated file.\n     \
+                    It will be read and overwritten.\n     \
+                    DO NOT EDIT! -->\n\
+                    <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+                    <TITLE>Bookmarks</TITLE>\n\
+                    <H1>Bookmarks</H1>\n\
+                    <DL><p>\n"
+        .to_owned();
+
+    root.write(&mut html, 1);
+    html.push_str("</DL><p>\n");
+
+    html
+}
@@ -0,0 +1,156 @@
+//! Checking whether stored links still work.
+
+use crate::http::{self, HttpClient, HttpError, Redirect, Request};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// The result of the last check of a link
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkStatus {
+    /// The status of the final response, if there was one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<Redirect>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<HttpError>,
+    pub checked: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Ok,
+    /// Works, but only after following redirects
+    Redirected,
+    NotFound,
+    /// Any other error status
+    Broken,
+    Tls,
+    Unreachable,
+}
+
+impl LinkStatus {
+    pub fn outcome(&self) -> Outcome {
+        match (&self.error, self.status) {
+            (Some(HttpError::Tls(_)), _) => Outcome::Tls,
+            (Some(_), _) | (None, None) => Outcome::Unreachable,
+            (None, Some(404)) | (None, Some(410)) => Outcome::NotFound,
+            (None, Some(status)) if status >= 400 => Outcome::Broken,
+            (None, Some(_)) if !self.redirects.is_empty() => Outcome::Redirected,
+            (None, Some(_)) => Outcome::Ok,
+        }
+    }
+
+    /// The URL the link should be rewritten to, if every redirect on the way
+    /// there is permanent and the final page works
+    pub fn permanent_location(&self) -> Option<&str> {
+        if self.outcome() == Outcome::Redirected
+            && self
+                .redirects
+                .iter()
+                .all(|redirect| redirect.is_permanent())
+        {
+            self.final_url.as_deref()
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Checker {
+    client: Box<dyn HttpClient>,
+    parallelism: usize,
+    timeout: Duration,
+}
+
+impl Checker {
+    pub fn new(client: Box<dyn HttpClient>) -> Self {
+        Checker {
+            client,
+            parallelism: 8,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// How many links are checked at the same time
+    pub fn parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Check a single URL with a `HEAD` request, falling back to `GET` for
+    /// servers that don't answer `HEAD` properly
+    pub fn check(&self, url: &str) -> LinkStatus {
+        let head = Request::head(url).timeout(self.timeout);
+
+        let result = match http::fetch(self.client.as_ref(), &head) {
+            Ok((response, _)) if response.status >= 400 => {
+                let get = Request::get(url).timeout(self.timeout).max_body_bytes(0);
+                http::fetch(self.client.as_ref(), &get)
+            }
+            result => result,
+        };
+
+        match result {
+            Ok((response, redirects)) => LinkStatus {
+                status: Some(response.status),
+                final_url: Some(response.url),
+                redirects,
+                error: None,
+                checked: Utc::now(),
+            },
+            Err(error) => LinkStatus {
+                status: None,
+                final_url: None,
+                redirects: vec![],
+                error: Some(error),
+                checked: Utc::now(),
+            },
+        }
+    }
+
+    /// Check all URLs, at most `parallelism` at a time. The statuses are
+    /// returned in the same order as the URLs.
+    pub fn check_all(&self, urls: &[&str]) -> Vec<LinkStatus> {
+        let next = AtomicUsize::new(0);
+        let statuses = Mutex::new(vec![None; urls.len()]);
+
+        thread::scope(|scope| {
+            for _ in 0..self.parallelism.min(urls.len()) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+
+                    let url = match urls.get(index) {
+                        Some(url) => url,
+                        None => break,
+                    };
+
+                    let status = self.check(url);
+
+                    if let Ok(mut statuses) = statuses.lock() {
+                        statuses[index] = Some(status);
+                    }
+                });
+            }
+        });
+
+        statuses
+            .into_inner()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|status| status.expect("Every URL is checked"))
+            .collect()
+    }
+}
@@ -0,0 +1,678 @@
+//! Layered configuration. Every setting is looked up in these layers, each
+//! one overriding the ones before it:
+//!
+//! 1. Built-in defaults
+//! 2. The global `~/.config/link-keeper/link-keeper.toml`
+//! 3. A project-local `.link-keeper.toml`, the nearest one found walking up
+//!    from the current directory
+//! 4. `LINK_KEEPER_*` environment variables, e.g. `LINK_KEEPER_STORE_PATH`.
+//!    Nested keys are separated by `__`, e.g.
+//!    `LINK_KEEPER_BACKENDS__GIT__REPOSITORY_PATH`
+//! 5. Overrides given on the command line
+//!
+//! Keys are dotted paths into the TOML tables, e.g. `suggestions.domains`.
+
+use crate::migrate::CONFIG_VERSION;
+use dirs::{config_dir, data_dir};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use toml::value::Table;
+use toml::Value;
+use toml_edit::{DocumentMut, Item, TableLike};
+
+pub const CONFIG_DIRECTORY_NAME: &str = "link-keeper";
+pub const CONFIG_FILE_NAME: &str = "link-keeper.toml";
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".link-keeper.toml";
+pub const ENV_PREFIX: &str = "LINK_KEEPER_";
+
+/// Separates the parts of nested keys in environment variable names
+const ENV_KEY_SEPARATOR: &str = "__";
+
+/// Where links are stored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub store_path: PathBuf,
+    pub store_file_name: String,
+}
+
+impl Settings {
+    /// The full path of the store
+    pub fn store_file(&self) -> PathBuf {
+        self.store_path.join(&self.store_file_name)
+    }
+}
+
+impl Default for Settings {
+    /// Links are stored in the user's data directory, so the same store is
+    /// used no matter where the CLI is run from
+    fn default() -> Self {
+        let data_path = data_dir().unwrap_or_else(|| PathBuf::from("."));
+
+        Self {
+            store_path: data_path.join(CONFIG_DIRECTORY_NAME),
+            store_file_name: "link_keeper.json".to_owned(),
+        }
+    }
+}
+
+/// Where the value of a setting comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    Default,
+    Global(PathBuf),
+    Project(PathBuf),
+    /// The name of the environment variable
+    Environment(String),
+    /// Given as a string instead of read from a file
+    Memory,
+    CommandLine,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::Default => fmt.write_str("default"),
+            Origin::Global(path) => fmt.write_fmt(format_args!("global config {}", path.display())),
+            Origin::Project(path) => {
+                fmt.write_fmt(format_args!("project config {}", path.display()))
+            }
+            Origin::Environment(name) => fmt.write_fmt(format_args!("environment {}", name)),
+            Origin::CommandLine => fmt.write_str("command line"),
+            Origin::Memory => fmt.write_str("in memory"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Layer {
+    origin: Origin,
+    values: Table,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Lowest precedence first
+    layers: Vec<Layer>,
+    /// Not set for configurations that aren't read from files
+    global_path: Option<PathBuf>,
+    project_path: Option<PathBuf>,
+    /// The environment and overrides the configuration was loaded with
+    vars: Vec<(String, String)>,
+    overrides: Vec<(String, Value)>,
+}
+
+/// The path of the global configuration file
+pub fn global_path() -> PathBuf {
+    config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(CONFIG_DIRECTORY_NAME)
+        .join(CONFIG_FILE_NAME)
+}
+
+/// The nearest project configuration file in the directory or its parents
+pub fn find_project_path(directory: &Path) -> Option<PathBuf> {
+    directory
+        .ancestors()
+        .map(|directory| directory.join(PROJECT_CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Read a setting's value the way it's written in TOML, falling back to a
+/// plain string, so `true` and `3` aren't strings but `~/links` is
+pub fn parse_value(value: &str) -> Value {
+    toml::from_str::<Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(value.to_owned()))
+}
+
+/// Read a configuration file, a missing file has no settings
+pub fn read_file(path: &Path) -> Result<Table, failure::Error> {
+    if !path.exists() {
+        return Ok(Table::new());
+    }
+
+    toml::from_str(&fs::read_to_string(path)?)
+        .map_err(|err| failure::format_err!("Invalid configuration in {}: {}", path.display(), err))
+}
+
+/// Set a key in a configuration file, creating the file if needed. The rest
+/// of the file, comments and formatting included, is left as it is.
+pub fn write_value(path: &Path, key: &str, value: Value) -> Result<(), failure::Error> {
+    let mut contents = if path.exists() {
+        fs::read_to_string(path)?
+    } else {
+        String::new()
+    };
+
+    if contents.trim().is_empty() {
+        contents = format!("version = {}\n", CONFIG_VERSION);
+    }
+
+    let edited = set_in(&contents, key, &value)
+        .map_err(|err| failure::format_err!("Can't change {}: {}", path.display(), err))?;
+
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+
+    if edited == contents {
+        log::debug!(path = path.display().to_string(), key = key; "Configuration is unchanged");
+        return Ok(());
+    }
+
+    log::info!(path = path.display().to_string(), key = key; "Writing configuration");
+    write_atomically(path, &edited)?;
+
+    Ok(())
+}
+
+/// Set a key in the TOML document, touching only the tables and values
+/// that change
+pub fn set_in(contents: &str, key: &str, value: &Value) -> Result<String, failure::Error> {
+    let mut document = contents.parse::<DocumentMut>()?;
+    let parts = key_parts(key)?;
+    let (last, parents) = parts.split_last().expect("a key has at least one part");
+    let mut table: &mut dyn TableLike = document.as_table_mut();
+
+    for part in parents {
+        table = table
+            .entry(part)
+            .or_insert_with(|| {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(true);
+
+                Item::Table(table)
+            })
+            .as_table_like_mut()
+            .ok_or_else(|| failure::format_err!("{} is not a table", part))?;
+    }
+
+    match table.get_mut(last) {
+        Some(item) => set_item(item, value),
+        None => {
+            table.insert(last, to_item(value));
+        }
+    }
+
+    Ok(document.to_string())
+}
+
+/// Replace the item with the value. Tables are updated key by key and values
+/// that don't change are kept as they are written.
+fn set_item(item: &mut Item, value: &Value) {
+    if let (Some(table), Value::Table(values)) = (item.as_table_like_mut(), value) {
+        let removed = table
+            .iter()
+            .map(|(key, _)| key.to_owned())
+            .filter(|key| !values.contains_key(key))
+            .collect::<Vec<String>>();
+
+        for key in removed {
+            table.remove(&key);
+        }
+
+        for (key, value) in values {
+            match table.get_mut(key) {
+                Some(item) => set_item(item, value),
+                None => {
+                    table.insert(key, to_item(value));
+                }
+            }
+        }
+
+        return;
+    }
+
+    let mut new = to_item(value);
+
+    if let (Some(old), Some(new)) = (item.as_value(), new.as_value_mut()) {
+        let mut bare = old.clone();
+        bare.decor_mut().clear();
+
+        if bare.to_string() == new.to_string() {
+            return;
+        }
+
+        // Keep comments and spacing around the value
+        *new.decor_mut() = old.decor().clone();
+    }
+
+    *item = new;
+}
+
+fn to_item(value: &Value) -> Item {
+    match value {
+        Value::Table(values) => {
+            let mut table = toml_edit::Table::new();
+
+            for (key, value) in values {
+                table.insert(key, to_item(value));
+            }
+
+            Item::Table(table)
+        }
+        value => Item::Value(to_value(value)),
+    }
+}
+
+fn to_value(value: &Value) -> toml_edit::Value {
+    match value {
+        Value::String(string) => string.as_str().into(),
+        Value::Integer(integer) => (*integer).into(),
+        Value::Float(float) => (*float).into(),
+        Value::Boolean(boolean) => (*boolean).into(),
+        Value::Datetime(datetime) => datetime
+            .to_string()
+            .parse()
+            .unwrap_or_else(|_| datetime.to_string().into()),
+        Value::Array(values) => values
+            .iter()
+            .map(to_value)
+            .collect::<toml_edit::Array>()
+            .into(),
+        Value::Table(values) => values
+            .iter()
+            .map(|(key, value)| (key.to_owned(), to_value(value)))
+            .collect::<toml_edit::InlineTable>()
+            .into(),
+    }
+}
+
+/// Write the file next to the old one and move it into place, so the old
+/// configuration is kept if writing fails halfway. Only the user can read
+/// the file, it can hold tokens.
+pub(crate) fn write_atomically(path: &Path, contents: &str) -> Result<(), io::Error> {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temporary_path = path.with_file_name(format!(".{}.tmp", file_name));
+
+    // A file left behind by an earlier write could be readable by others
+    let _ = fs::remove_file(&temporary_path);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(&temporary_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+
+    fs::rename(&temporary_path, path)
+}
+
+/// The parts of a dotted key, parts containing dots are quoted, e.g.
+/// `suggestions.domains."arxiv.org"`
+fn key_parts(key: &str) -> Result<Vec<String>, failure::Error> {
+    let mut parts = vec![];
+    let mut part = String::new();
+    let mut quoted = false;
+
+    for character in key.chars() {
+        match character {
+            '"' => quoted = !quoted,
+            '.' if !quoted => parts.push(std::mem::take(&mut part)),
+            character => part.push(character),
+        }
+    }
+
+    parts.push(part);
+
+    if quoted || parts.iter().any(|part| part.trim().is_empty()) {
+        return Err(failure::format_err!("Invalid key: {}", key));
+    }
+
+    Ok(parts)
+}
+
+fn lookup<'a>(values: &'a Table, key: &str) -> Option<&'a Value> {
+    let parts = key_parts(key).ok()?;
+    let (first, rest) = parts.split_first()?;
+    let mut value = values.get(first)?;
+
+    for part in rest {
+        value = value.as_table()?.get(part)?;
+    }
+
+    Some(value)
+}
+
+fn insert(values: &mut Table, key: &str, value: Value) -> Result<(), failure::Error> {
+    let parts = key_parts(key)?;
+    let (last, parents) = parts.split_last().expect("a key has at least one part");
+    let mut table = values;
+
+    for part in parents {
+        table = match table
+            .entry(part.to_owned())
+            .or_insert_with(|| Value::Table(Table::new()))
+        {
+            Value::Table(table) => table,
+            _ => return Err(failure::format_err!("{} is not a table", part)),
+        };
+    }
+
+    table.insert(last.to_owned(), value);
+
+    Ok(())
+}
+
+/// Merge the tables, values in `over` replace the ones in `under` except
+/// for tables, which are merged
+fn merge(under: &mut Table, over: &Table) {
+    for (key, value) in over {
+        match (under.get_mut(key), value) {
+            (Some(Value::Table(under)), Value::Table(over)) => merge(under, over),
+            _ => {
+                under.insert(key.to_owned(), value.clone());
+            }
+        }
+    }
+}
+
+/// Every key holding a value that isn't a table, with the value
+fn flatten(values: &Table, prefix: &str, keys: &mut Vec<(String, Value)>) {
+    for (key, value) in values {
+        let part = if key.contains('.') {
+            format!("\"{}\"", key)
+        } else {
+            key.to_owned()
+        };
+        let key = if prefix.is_empty() {
+            part
+        } else {
+            format!("{}.{}", prefix, part)
+        };
+
+        match value {
+            Value::Table(table) => flatten(table, &key, keys),
+            value => keys.push((key, value.clone())),
+        }
+    }
+}
+
+/// Paths in configuration files are relative to the file, so a project
+/// config can keep its store next to it
+fn resolve_paths(values: &mut Table, directory: &Path) {
+    for (key, value) in values.iter_mut() {
+        match value {
+            Value::Table(table) => resolve_paths(table, directory),
+            Value::String(path) if key.ends_with("_path") && Path::new(path).is_relative() => {
+                *path = directory.join(&path).to_string_lossy().into_owned();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration for the current directory and environment
+    pub fn load(overrides: &[(String, Value)]) -> Result<Config, failure::Error> {
+        Self::load_from(
+            global_path(),
+            find_project_path(&env::current_dir()?),
+            env::vars(),
+            overrides,
+        )
+    }
+
+    pub fn load_from(
+        global_path: PathBuf,
+        project_path: Option<PathBuf>,
+        vars: impl IntoIterator<Item = (String, String)>,
+        overrides: &[(String, Value)],
+    ) -> Result<Config, failure::Error> {
+        let defaults = match Value::try_from(Settings::default())? {
+            Value::Table(table) => table,
+            _ => Table::new(),
+        };
+
+        let mut layers = vec![Layer {
+            origin: Origin::Default,
+            values: defaults,
+        }];
+
+        let mut files = vec![Origin::Global(global_path.clone())];
+        files.extend(project_path.iter().cloned().map(Origin::Project));
+
+        for origin in files {
+            let path = match &origin {
+                Origin::Global(path) | Origin::Project(path) => path.clone(),
+                _ => continue,
+            };
+            let mut values = read_file(&path)?;
+
+            if let Some(directory) = path.parent() {
+                resolve_paths(&mut values, directory);
+            }
+
+            layers.push(Layer { origin, values });
+        }
+
+        let mut vars = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect::<Vec<(String, String)>>();
+        vars.sort();
+
+        for (name, value) in vars.iter().cloned() {
+            let key = name[ENV_PREFIX.len()..]
+                .to_lowercase()
+                .replace(ENV_KEY_SEPARATOR, ".");
+            let mut values = Table::new();
+            insert(&mut values, &key, parse_value(&value))?;
+
+            layers.push(Layer {
+                origin: Origin::Environment(name),
+                values,
+            });
+        }
+
+        let mut values = Table::new();
+
+        for (key, value) in overrides {
+            insert(&mut values, key, value.clone())?;
+        }
+
+        layers.push(Layer {
+            origin: Origin::CommandLine,
+            values,
+        });
+
+        Ok(Config {
+            layers,
+            global_path: Some(global_path),
+            project_path,
+            vars,
+            overrides: overrides.to_vec(),
+        })
+    }
+
+    /// A configuration that isn't read from any file or the environment,
+    /// only the defaults and the given TOML
+    pub fn from_toml(contents: &str) -> Result<Config, failure::Error> {
+        let mut config = Self::load_from(PathBuf::new(), None, vec![], &[])?;
+        config
+            .layers
+            .retain(|layer| layer.origin == Origin::Default);
+        config.layers.push(Layer {
+            origin: Origin::Memory,
+            values: toml::from_str(contents)?,
+        });
+        config.global_path = None;
+
+        Ok(config)
+    }
+
+    /// Read the configuration files again, e.g. after they were migrated
+    pub fn reload(&mut self) -> Result<(), failure::Error> {
+        let global_path = match &self.global_path {
+            Some(global_path) => global_path.clone(),
+            None => return Ok(()),
+        };
+
+        *self = Self::load_from(
+            global_path,
+            self.project_path.clone(),
+            self.vars.clone(),
+            &self.overrides,
+        )?;
+
+        Ok(())
+    }
+
+    /// The global configuration file, if the configuration is read from files
+    pub fn global_path(&self) -> Option<&Path> {
+        self.global_path.as_deref()
+    }
+
+    /// The project configuration file in use, if any
+    pub fn project_path(&self) -> Option<&Path> {
+        self.project_path.as_deref()
+    }
+
+    /// All layers merged into one table
+    pub fn merged(&self) -> Value {
+        let mut values = Table::new();
+
+        for layer in &self.layers {
+            merge(&mut values, &layer.values);
+        }
+
+        Value::Table(values)
+    }
+
+    /// The effective value of a key and which layer it comes from
+    pub fn get(&self, key: &str) -> Option<(Value, &Origin)> {
+        let origin = self
+            .layers
+            .iter()
+            .rev()
+            .find(|layer| lookup(&layer.values, key).is_some())
+            .map(|layer| &layer.origin)?;
+
+        match self.merged() {
+            Value::Table(values) => lookup(&values, key).map(|value| (value.clone(), origin)),
+            _ => None,
+        }
+    }
+
+    /// Every effective setting that isn't a table, with where it comes from
+    pub fn show(&self) -> Vec<(String, Value, &Origin)> {
+        let mut keys = vec![];
+
+        if let Value::Table(values) = self.merged() {
+            flatten(&values, "", &mut keys);
+        }
+
+        keys.into_iter()
+            .filter_map(|(key, value)| {
+                let (_, origin) = self.get(&key)?;
+
+                Some((key, value, origin))
+            })
+            .collect()
+    }
+
+    pub fn settings(&self) -> Result<Settings, failure::Error> {
+        Ok(self.merged().try_into::<Settings>()?)
+    }
+
+    /// Read a table of the configuration, e.g. `suggestions`
+    pub fn section<T>(&self, key: &str) -> Result<Option<T>, failure::Error>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        match self.get(key) {
+            Some((value, _)) => Ok(Some(value.try_into::<T>()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Set a key in the global configuration file, or the project one with
+    /// `project`. Without a project configuration file one is created in the
+    /// current directory. Returns the path of the changed file.
+    pub fn set(
+        &mut self,
+        key: &str,
+        value: Value,
+        project: bool,
+    ) -> Result<PathBuf, failure::Error> {
+        let (path, origin) = if project {
+            let path = match &self.project_path {
+                Some(path) => path.clone(),
+                None => env::current_dir()?.join(PROJECT_CONFIG_FILE_NAME),
+            };
+
+            (path.clone(), Origin::Project(path))
+        } else {
+            let path = self
+                .global_path
+                .clone()
+                .ok_or_else(|| failure::format_err!("The configuration isn't read from a file"))?;
+
+            (path.clone(), Origin::Global(path))
+        };
+
+        let mut changed = self.clone();
+        let position = match changed
+            .layers
+            .iter()
+            .position(|layer| layer.origin == origin)
+        {
+            Some(position) => position,
+            None => {
+                // The project layer goes right after the global one
+                let position = changed
+                    .layers
+                    .iter()
+                    .rposition(|layer| {
+                        matches!(
+                            layer.origin,
+                            Origin::Default | Origin::Global(_) | Origin::Memory
+                        )
+                    })
+                    .map_or(0, |position| position + 1);
+
+                changed.layers.insert(
+                    position,
+                    Layer {
+                        origin,
+                        values: Table::new(),
+                    },
+                );
+                position
+            }
+        };
+
+        let mut values = Table::new();
+        insert(&mut values, key, value.clone())?;
+
+        if let Some(directory) = path.parent() {
+            resolve_paths(&mut values, directory);
+        }
+
+        merge(&mut changed.layers[position].values, &values);
+
+        // Don't write a value that would make the configuration unusable
+        changed.settings()?;
+
+        write_value(&path, key, value)?;
+
+        if project {
+            changed.project_path = Some(path.clone());
+        }
+
+        *self = changed;
+
+        Ok(path)
+    }
+}
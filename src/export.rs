@@ -0,0 +1,337 @@
+//! Exporters that write stored links to other formats.
+
+use crate::bookmarks::{self, encode_entities};
+use crate::markdown;
+use crate::query::domain_of;
+use crate::Link;
+use chrono::Utc;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+/// How many links the Atom feed contains
+const FEED_LENGTH: usize = 50;
+
+/// The URL schemes that are made into links on published pages, anything
+/// else, like a `javascript:` bookmarklet, is shown as text
+const LINKABLE_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+pub trait Exporter {
+    /// The name used to select the exporter, e.g. `csv`
+    fn name(&self) -> &'static str;
+    fn export(&self, links: &[Link]) -> Result<String, failure::Error>;
+}
+
+/// The Netscape bookmark file that browsers can import
+#[derive(Debug)]
+pub struct Netscape;
+
+impl Exporter for Netscape {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn export(&self, links: &[Link]) -> Result<String, failure::Error> {
+        Ok(bookmarks::write(links))
+    }
+}
+
+#[derive(Debug)]
+pub struct Csv;
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+impl Exporter for Csv {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn export(&self, links: &[Link]) -> Result<String, failure::Error> {
+        let mut csv = "url,title,category,tags,added,state\n".to_owned();
+
+        for link in links {
+            let fields = [
+                link.link().to_owned(),
+                link.title().unwrap_or("").to_owned(),
+                link.category().unwrap_or("").to_owned(),
+                link.tags().join(","),
+                link.added()
+                    .map(|added| added.to_rfc3339())
+                    .unwrap_or_default(),
+                serde_json::to_value(link.state())?
+                    .as_str()
+                    .unwrap_or("")
+                    .to_owned(),
+            ];
+
+            csv.push_str(
+                &fields
+                    .iter()
+                    .map(|field| csv_field(field))
+                    .collect::<Vec<String>>()
+                    .join(","),
+            );
+            csv.push('\n');
+        }
+
+        Ok(csv)
+    }
+}
+
+/// Links grouped by their category, uncategorized links under `None`
+fn by_category(links: &[Link]) -> BTreeMap<Option<&str>, Vec<&Link>> {
+    let mut categories: BTreeMap<Option<&str>, Vec<&Link>> = BTreeMap::new();
+
+    for link in links {
+        categories.entry(link.category()).or_default().push(link);
+    }
+
+    categories
+}
+
+fn display_title(link: &Link) -> &str {
+    link.title().unwrap_or_else(|| link.link())
+}
+
+fn is_linkable(link: &str) -> bool {
+    link.split_once(':').is_some_and(|(scheme, _)| {
+        LINKABLE_SCHEMES
+            .iter()
+            .any(|linkable| scheme.trim().eq_ignore_ascii_case(linkable))
+    })
+}
+
+#[derive(Debug)]
+pub struct Opml;
+
+impl Exporter for Opml {
+    fn name(&self) -> &'static str {
+        "opml"
+    }
+
+    fn export(&self, links: &[Link]) -> Result<String, failure::Error> {
+        let outline = |link: &Link, indent: &str| {
+            format!(
+                "{}<outline text=\"{}\" type=\"link\" url=\"{}\"/>\n",
+                indent,
+                encode_entities(display_title(link)),
+                encode_entities(link.link())
+            )
+        };
+
+        let mut opml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <opml version=\"2.0\">\n  \
+             <head>\n    \
+             <title>Links</title>\n    \
+             <dateCreated>{}</dateCreated>\n  \
+             </head>\n  \
+             <body>\n",
+            Utc::now().to_rfc2822()
+        );
+
+        for (category, links) in by_category(links) {
+            match category {
+                Some(category) => {
+                    opml.push_str(&format!(
+                        "    <outline text=\"{}\">\n",
+                        encode_entities(category)
+                    ));
+                    for link in links {
+                        opml.push_str(&outline(link, "      "));
+                    }
+                    opml.push_str("    </outline>\n");
+                }
+                None => {
+                    for link in links {
+                        opml.push_str(&outline(link, "    "));
+                    }
+                }
+            }
+        }
+
+        opml.push_str("  </body>\n</opml>\n");
+
+        Ok(opml)
+    }
+}
+
+/// An Atom feed of the most recently added links
+#[derive(Debug)]
+pub struct Atom;
+
+impl Exporter for Atom {
+    fn name(&self) -> &'static str {
+        "atom"
+    }
+
+    fn export(&self, links: &[Link]) -> Result<String, failure::Error> {
+        let mut recent = links.iter().collect::<Vec<&Link>>();
+        recent.sort_by_key(|link| Reverse(link.added()));
+        recent.truncate(FEED_LENGTH);
+
+        let updated = recent
+            .iter()
+            .filter_map(|link| link.added())
+            .max()
+            .unwrap_or_else(Utc::now);
+
+        let mut feed = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <feed xmlns=\"http://www.w3.org/2005/Atom\">\n  \
+             <title>Links</title>\n  \
+             <id>urn:link-keeper:links</id>\n  \
+             <updated>{}</updated>\n  \
+             <author><name>link-keeper</name></author>\n",
+            updated.to_rfc3339()
+        );
+
+        for link in recent {
+            let categories = link
+                .category()
+                .into_iter()
+                .chain(link.tags().iter().map(|tag| tag.as_str()))
+                .map(|term| format!("    <category term=\"{}\"/>\n", encode_entities(term)))
+                .collect::<String>();
+
+            feed.push_str(&format!(
+                "  <entry>\n    \
+                 <title>{}</title>\n    \
+                 <link href=\"{}\"/>\n    \
+                 <id>{}</id>\n    \
+                 <updated>{}</updated>\n\
+                 {}  \
+                 </entry>\n",
+                encode_entities(display_title(link)),
+                encode_entities(link.link()),
+                encode_entities(link.link()),
+                link.added().unwrap_or(updated).to_rfc3339(),
+                categories
+            ));
+        }
+
+        feed.push_str("</feed>\n");
+
+        Ok(feed)
+    }
+}
+
+/// A self-contained HTML page with the links grouped by category and a search
+/// field that filters them
+#[derive(Debug)]
+pub struct StaticPage;
+
+const PAGE_STYLE: &str =
+    "body{font-family:sans-serif;max-width:50em;margin:2em auto;padding:0 1em}\
+input{width:100%;font-size:1.2em;padding:.3em}\
+li{margin:.3em 0}.domain{color:#777;font-size:.8em;margin-left:.5em}";
+
+const PAGE_SCRIPT: &str =
+    "document.getElementById('search').addEventListener('input',function(event){\
+var terms=event.target.value.toLowerCase().split(/\\s+/);\
+document.querySelectorAll('section').forEach(function(section){\
+var visible=0;\
+section.querySelectorAll('li').forEach(function(item){\
+var text=item.textContent.toLowerCase()+' '+item.dataset.tags;\
+var show=terms.every(function(term){return text.indexOf(term)!==-1});\
+item.hidden=!show;if(show){visible++}});\
+section.hidden=visible===0})});";
+
+impl Exporter for StaticPage {
+    fn name(&self) -> &'static str {
+        "page"
+    }
+
+    fn export(&self, links: &[Link]) -> Result<String, failure::Error> {
+        let mut page = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n\
+             <meta charset=\"utf-8\">\n\
+             <title>Links</title>\n\
+             <style>{}</style>\n\
+             </head>\n<body>\n\
+             <h1>Links</h1>\n\
+             <input id=\"search\" type=\"search\" placeholder=\"Search\" autofocus>\n",
+            PAGE_STYLE
+        );
+
+        for (category, links) in by_category(links) {
+            page.push_str(&format!(
+                "<section>\n<h2>{}</h2>\n<ul>\n",
+                encode_entities(category.unwrap_or("Uncategorized"))
+            ));
+
+            for link in links {
+                let title = if is_linkable(link.link()) {
+                    format!(
+                        "<a href=\"{}\">{}</a>",
+                        encode_entities(link.link()),
+                        encode_entities(display_title(link))
+                    )
+                } else {
+                    encode_entities(display_title(link))
+                };
+
+                page.push_str(&format!(
+                    "<li data-tags=\"{}\">{}<span class=\"domain\">{}</span></li>\n",
+                    encode_entities(&link.tags().join(" ")),
+                    title,
+                    encode_entities(&domain_of(link.link()).unwrap_or_default())
+                ));
+            }
+
+            page.push_str("</ul>\n</section>\n");
+        }
+
+        page.push_str(&format!(
+            "<script>{}</script>\n</body>\n</html>\n",
+            PAGE_SCRIPT
+        ));
+
+        Ok(page)
+    }
+}
+
+/// A Markdown list of the links with their notes and highlights
+#[derive(Debug)]
+pub struct Markdown;
+
+impl Exporter for Markdown {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn export(&self, links: &[Link]) -> Result<String, failure::Error> {
+        let mut list = "# Links\n\n".to_owned();
+
+        for link in links {
+            list.push_str(&markdown::item(link));
+            list.push('\n');
+            list.push_str(&markdown::annotations(link));
+        }
+
+        Ok(list)
+    }
+}
+
+/// All the available exporters
+pub fn exporters() -> Vec<Box<dyn Exporter>> {
+    vec![
+        Box::new(Netscape),
+        Box::new(Csv),
+        Box::new(Opml),
+        Box::new(Atom),
+        Box::new(StaticPage),
+        Box::new(Markdown),
+    ]
+}
+
+pub fn exporter(name: &str) -> Option<Box<dyn Exporter>> {
+    exporters()
+        .into_iter()
+        .find(|exporter| exporter.name().eq_ignore_ascii_case(name))
+}
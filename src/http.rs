@@ -0,0 +1,290 @@
+//! A small HTTP abstraction so that fetching pages can be swapped out, e.g.
+//! for a client talking to a local fixture server.
+
+use crate::normalize::resolve_url;
+use encoding_rs::{Encoding, UTF_8};
+use failure::Fail;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Read;
+use std::time::Duration;
+
+/// How many redirects `fetch` follows before giving up
+pub const MAX_REDIRECTS: usize = 10;
+
+/// How far into a page a `<meta charset>` is looked for
+const CHARSET_SNIFF_BYTES: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    Get,
+    Head,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: Method,
+    pub url: String,
+    pub timeout: Duration,
+    /// Bodies longer than this are cut off
+    pub max_body_bytes: u64,
+}
+
+impl Request {
+    pub fn get(url: &str) -> Self {
+        Request {
+            method: Method::Get,
+            url: url.to_owned(),
+            timeout: Duration::from_secs(10),
+            max_body_bytes: 1024 * 1024,
+        }
+    }
+
+    pub fn head(url: &str) -> Self {
+        Request {
+            method: Method::Head,
+            ..Request::get(url)
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn max_body_bytes(mut self, max_body_bytes: u64) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    /// The URL that was requested
+    pub url: String,
+    /// The `Location` header of redirects
+    pub location: Option<String>,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+    /// Whether the body was cut off at `Request::max_body_bytes`
+    pub truncated: bool,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.status) && self.location.is_some()
+    }
+
+    pub fn is_html(&self) -> bool {
+        self.content_type
+            .as_ref()
+            .is_none_or(|content_type| content_type.contains("html"))
+    }
+
+    /// The body decoded with the charset of the `Content-Type` header or of
+    /// a `<meta>` tag at the start of the page, UTF-8 if neither names one
+    pub fn text(&self) -> String {
+        let encoding = self
+            .content_type
+            .as_ref()
+            .and_then(|content_type| charset(&content_type.to_lowercase()))
+            .or_else(|| {
+                let start = &self.body[..self.body.len().min(CHARSET_SNIFF_BYTES)];
+                charset(&String::from_utf8_lossy(start).to_lowercase())
+            })
+            .unwrap_or(UTF_8);
+
+        encoding.decode(&self.body).0.into_owned()
+    }
+}
+
+/// The encoding named by a `charset=` in a header or tag
+fn charset(text: &str) -> Option<&'static Encoding> {
+    let start = text.find("charset=")? + "charset=".len();
+    let label = text[start..]
+        .trim_start_matches(['"', '\''])
+        .split(|character: char| {
+            !(character.is_ascii_alphanumeric() || character == '-' || character == '_')
+        })
+        .next()?;
+
+    Encoding::for_label(label.as_bytes())
+}
+
+/// One hop when following redirects
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Redirect {
+    pub status: u16,
+    pub from: String,
+    pub to: String,
+}
+
+impl Redirect {
+    /// Whether the redirect is permanent, i.e. the link should be updated
+    pub fn is_permanent(&self) -> bool {
+        self.status == 301 || self.status == 308
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind", content = "message")]
+pub enum HttpError {
+    Timeout,
+    Tls(String),
+    Connection(String),
+    InvalidUrl(String),
+    TooManyRedirects,
+    Other(String),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            HttpError::Timeout => fmt.write_fmt(format_args!("Timed out")),
+            HttpError::Tls(error) => fmt.write_fmt(format_args!("TLS error: {}", error)),
+            HttpError::Connection(error) => {
+                fmt.write_fmt(format_args!("Connection failed: {}", error))
+            }
+            HttpError::InvalidUrl(url) => fmt.write_fmt(format_args!("Invalid URL: {}", url)),
+            HttpError::TooManyRedirects => {
+                fmt.write_fmt(format_args!("More than {} redirects", MAX_REDIRECTS))
+            }
+            HttpError::Other(error) => fmt.write_fmt(format_args!("{}", error)),
+        }
+    }
+}
+
+impl Fail for HttpError {}
+
+pub trait HttpClient: fmt::Debug + Send + Sync {
+    /// Send a single request without following any redirects
+    fn send(&self, request: &Request) -> Result<Response, HttpError>;
+}
+
+/// Send the request and follow any redirects. Returns the final response
+/// together with the redirects that led to it.
+pub fn fetch(
+    client: &dyn HttpClient,
+    request: &Request,
+) -> Result<(Response, Vec<Redirect>), HttpError> {
+    let mut request = request.clone();
+    let mut redirects = vec![];
+
+    loop {
+        let response = client.send(&request)?;
+
+        let location = match (response.is_redirect(), &response.location) {
+            (true, Some(location)) => resolve_url(&request.url, location),
+            _ => return Ok((response, redirects)),
+        };
+
+        if redirects.len() == MAX_REDIRECTS {
+            return Err(HttpError::TooManyRedirects);
+        }
+
+        redirects.push(Redirect {
+            status: response.status,
+            from: request.url.to_owned(),
+            to: location.to_owned(),
+        });
+
+        request.url = location;
+    }
+}
+
+/// The default client, built on `ureq`
+#[derive(Debug)]
+pub struct UreqClient {
+    agent: ureq::Agent,
+}
+
+impl UreqClient {
+    pub fn new() -> Self {
+        UreqClient {
+            agent: ureq::AgentBuilder::new()
+                .redirects(0)
+                .user_agent(concat!("link-keeper/", env!("CARGO_PKG_VERSION")))
+                .build(),
+        }
+    }
+}
+
+impl Default for UreqClient {
+    fn default() -> Self {
+        UreqClient::new()
+    }
+}
+
+fn transport_error(error: ureq::Transport) -> HttpError {
+    let message = error.to_string();
+    let lowercase = message.to_lowercase();
+
+    match error.kind() {
+        ureq::ErrorKind::InvalidUrl | ureq::ErrorKind::UnknownScheme => {
+            HttpError::InvalidUrl(message)
+        }
+        _ if lowercase.contains("timed out") || lowercase.contains("timeout") => HttpError::Timeout,
+        _ if lowercase.contains("certificate")
+            || lowercase.contains("tls")
+            || lowercase.contains("handshake") =>
+        {
+            HttpError::Tls(message)
+        }
+        ureq::ErrorKind::Dns | ureq::ErrorKind::ConnectionFailed | ureq::ErrorKind::Io => {
+            HttpError::Connection(message)
+        }
+        _ => HttpError::Other(message),
+    }
+}
+
+impl HttpClient for UreqClient {
+    fn send(&self, request: &Request) -> Result<Response, HttpError> {
+        let ureq_request = match request.method {
+            Method::Get => self.agent.get(&request.url),
+            Method::Head => self.agent.head(&request.url),
+        }
+        .timeout(request.timeout);
+
+        let response = match ureq_request.call() {
+            Ok(response) => response,
+            // Error statuses are still responses to us
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(ureq::Error::Transport(error)) => return Err(transport_error(error)),
+        };
+
+        let status = response.status();
+        let location = response
+            .header("location")
+            .map(|location| location.to_owned());
+        let content_type = response
+            .header("content-type")
+            .map(|content_type| content_type.to_lowercase());
+
+        let mut body = vec![];
+
+        if request.method == Method::Get {
+            response
+                .into_reader()
+                .take(request.max_body_bytes + 1)
+                .read_to_end(&mut body)
+                .map_err(|err| HttpError::Other(err.to_string()))?;
+        }
+
+        let truncated = body.len() as u64 > request.max_body_bytes;
+        body.truncate(request.max_body_bytes as usize);
+
+        Ok(Response {
+            status,
+            url: request.url.to_owned(),
+            location,
+            content_type,
+            body,
+            truncated,
+        })
+    }
+}
@@ -0,0 +1,84 @@
+//! Importers that read the export formats of browsers and other bookmarking
+//! services into links.
+
+use crate::bookmarks;
+use crate::{Link, ReadState};
+use chrono::{DateTime, TimeZone, Utc};
+
+mod browser;
+mod pinboard;
+mod pocket;
+mod raindrop;
+
+pub use self::browser::BrowserJson;
+pub use self::pinboard::Pinboard;
+pub use self::pocket::Pocket;
+pub use self::raindrop::Raindrop;
+
+pub trait Importer {
+    /// The name used to select the importer, e.g. `pocket`
+    fn name(&self) -> &'static str;
+    fn parse(&self, contents: &str) -> Result<Vec<Link>, failure::Error>;
+}
+
+/// The Netscape bookmark file that browsers export
+#[derive(Debug)]
+pub struct Netscape;
+
+impl Importer for Netscape {
+    fn name(&self) -> &'static str {
+        "netscape"
+    }
+
+    fn parse(&self, contents: &str) -> Result<Vec<Link>, failure::Error> {
+        Ok(bookmarks::parse(contents))
+    }
+}
+
+/// All the available importers
+pub fn importers() -> Vec<Box<dyn Importer>> {
+    vec![
+        Box::new(Netscape),
+        Box::new(Pocket),
+        Box::new(Pinboard),
+        Box::new(Raindrop),
+        Box::new(BrowserJson),
+    ]
+}
+
+pub fn importer(name: &str) -> Option<Box<dyn Importer>> {
+    importers()
+        .into_iter()
+        .find(|importer| importer.name().eq_ignore_ascii_case(name))
+}
+
+fn from_timestamp(seconds: i64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(seconds, 0).single()
+}
+
+fn split_tags(tags: &str, separator: char) -> Vec<String> {
+    tags.split(separator)
+        .map(|tag| tag.trim().to_owned())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.trim();
+
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_owned())
+    }
+}
+
+/// Put the link in the state it has in the export. The export doesn't say
+/// when it got there, so the time of the import is recorded instead.
+fn set_state(link: &mut Link, state: ReadState, imported: DateTime<Utc>) {
+    link.state = state;
+
+    if state != ReadState::Unread {
+        link.state_changes.insert(state, imported);
+    }
+}
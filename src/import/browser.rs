@@ -0,0 +1,118 @@
+use super::{from_timestamp, non_empty, split_tags, Importer};
+use crate::bookmarks::CATEGORY_SEPARATOR;
+use crate::Link;
+use serde_json::Value;
+
+/// Seconds between 1601-01-01, which Chrome counts from, and the Unix epoch
+const CHROME_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+/// The JSON bookmark backup of Firefox or the `Bookmarks` file of Chrome
+#[derive(Debug)]
+pub struct BrowserJson;
+
+fn folder_path(folders: &[String]) -> Option<String> {
+    non_empty(&folders.join(CATEGORY_SEPARATOR))
+}
+
+fn walk_firefox(node: &Value, folders: &mut Vec<String>, links: &mut Vec<Link>) {
+    let title = node["title"].as_str().unwrap_or("");
+
+    match node["type"].as_str() {
+        Some("text/x-moz-place-container") => {
+            let named = !title.is_empty();
+
+            if named {
+                folders.push(title.to_owned());
+            }
+
+            for child in node["children"].as_array().into_iter().flatten() {
+                walk_firefox(child, folders, links);
+            }
+
+            if named {
+                folders.pop();
+            }
+        }
+        Some("text/x-moz-place") => {
+            let uri = match node["uri"].as_str() {
+                // Skip smart bookmarks like `place:sort=8`
+                Some(uri) if uri.contains("://") => uri,
+                _ => return,
+            };
+
+            let category = folder_path(folders);
+            let mut link = Link::new(uri, category.as_deref()).with_tags(
+                node["tags"]
+                    .as_str()
+                    .map(|tags| split_tags(tags, ','))
+                    .unwrap_or_default(),
+            );
+
+            link.title = non_empty(title);
+            link.added = node["dateAdded"]
+                .as_i64()
+                .and_then(|microseconds| from_timestamp(microseconds / 1_000_000));
+
+            links.push(link);
+        }
+        _ => {}
+    }
+}
+
+fn walk_chrome(node: &Value, folders: &mut Vec<String>, links: &mut Vec<Link>) {
+    let name = node["name"].as_str().unwrap_or("");
+
+    match node["type"].as_str() {
+        Some("folder") => {
+            folders.push(name.to_owned());
+
+            for child in node["children"].as_array().into_iter().flatten() {
+                walk_chrome(child, folders, links);
+            }
+
+            folders.pop();
+        }
+        Some("url") => {
+            let url = match node["url"].as_str() {
+                Some(url) => url,
+                None => return,
+            };
+
+            let category = folder_path(folders);
+            let mut link = Link::new(url, category.as_deref());
+
+            link.title = non_empty(name);
+            link.added = node["date_added"]
+                .as_str()
+                .and_then(|date| date.parse::<i64>().ok())
+                .and_then(|microseconds| {
+                    from_timestamp(microseconds / 1_000_000 - CHROME_EPOCH_OFFSET)
+                });
+
+            links.push(link);
+        }
+        _ => {}
+    }
+}
+
+impl Importer for BrowserJson {
+    fn name(&self) -> &'static str {
+        "browser-json"
+    }
+
+    fn parse(&self, contents: &str) -> Result<Vec<Link>, failure::Error> {
+        let root = serde_json::from_str::<Value>(contents)?;
+        let mut links = vec![];
+
+        match root["roots"].as_object() {
+            Some(roots) => {
+                for root in roots.values() {
+                    walk_chrome(root, &mut vec![], &mut links);
+                }
+            }
+            None => walk_firefox(&root, &mut vec![], &mut links),
+        }
+
+        Ok(links)
+    }
+}
@@ -0,0 +1,49 @@
+use super::{non_empty, set_state, split_tags, Importer};
+use crate::{Link, ReadState};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// The JSON export of Pinboard
+#[derive(Debug)]
+pub struct Pinboard;
+
+#[derive(Debug, Deserialize)]
+struct Post {
+    href: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    tags: String,
+    time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    toread: String,
+}
+
+impl Importer for Pinboard {
+    fn name(&self) -> &'static str {
+        "pinboard"
+    }
+
+    fn parse(&self, contents: &str) -> Result<Vec<Link>, failure::Error> {
+        let posts = serde_json::from_str::<Vec<Post>>(contents)?;
+        let imported = Utc::now();
+
+        Ok(posts
+            .into_iter()
+            .map(|post| {
+                let mut link = Link::new(&post.href, None).with_tags(split_tags(&post.tags, ' '));
+
+                link.title = non_empty(&post.description);
+                link.added = post.time;
+                let state = if post.toread == "yes" {
+                    ReadState::Unread
+                } else {
+                    ReadState::Read
+                };
+                set_state(&mut link, state, imported);
+
+                link
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,57 @@
+use super::{from_timestamp, non_empty, set_state, split_tags, Importer};
+use crate::bookmarks::{attribute, text_until, tokenize, Token};
+use crate::{Link, ReadState};
+use chrono::Utc;
+
+/// The HTML export of Pocket, with an `Unread` and a `Read Archive` list
+#[derive(Debug)]
+pub struct Pocket;
+
+impl Importer for Pocket {
+    fn name(&self) -> &'static str {
+        "pocket"
+    }
+
+    fn parse(&self, contents: &str) -> Result<Vec<Link>, failure::Error> {
+        let tokens = tokenize(contents);
+        let mut tokens = tokens.iter();
+        let mut state = ReadState::Unread;
+        let imported = Utc::now();
+        let mut links = vec![];
+
+        while let Some(token) = tokens.next() {
+            match token {
+                Token::Open(name, _) if name == "h1" => {
+                    state = match text_until(&mut tokens, "h1").to_lowercase().as_str() {
+                        "read archive" => ReadState::Archived,
+                        _ => ReadState::Unread,
+                    };
+                }
+                Token::Open(name, attributes) if name == "a" => {
+                    let href = match attribute(attributes, "href") {
+                        Some(href) if !href.is_empty() => href.to_owned(),
+                        _ => continue,
+                    };
+
+                    let mut link = Link::new(&href, None).with_tags(
+                        attribute(attributes, "tags")
+                            .map(|tags| split_tags(tags, ','))
+                            .unwrap_or_default(),
+                    );
+
+                    link.added = attribute(attributes, "time_added")
+                        .and_then(|time| time.parse::<i64>().ok())
+                        .and_then(from_timestamp);
+                    link.title =
+                        non_empty(&text_until(&mut tokens, "a")).filter(|title| *title != href);
+                    set_state(&mut link, state, imported);
+
+                    links.push(link);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(links)
+    }
+}
@@ -0,0 +1,100 @@
+use super::{non_empty, set_state, split_tags, Importer};
+use crate::{Link, ReadState};
+use chrono::{DateTime, Utc};
+
+/// The CSV export of Raindrop.io. Links are read when their `read` column is
+/// `true`, exports without the column have only unread links.
+#[derive(Debug)]
+pub struct Raindrop;
+
+/// Split CSV into records, handling quoted fields with escaped quotes and
+/// line breaks
+fn parse_csv(contents: &str) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut characters = contents.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        match character {
+            '"' if in_quotes && characters.peek() == Some(&'"') => {
+                field.push('"');
+                characters.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => record.push(field.split_off(0)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                record.push(field.split_off(0));
+                records.push(record.split_off(0));
+            }
+            _ => field.push(character),
+        }
+    }
+
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    records
+        .into_iter()
+        .filter(|record| record.iter().any(|field| !field.is_empty()))
+        .collect()
+}
+
+impl Importer for Raindrop {
+    fn name(&self) -> &'static str {
+        "raindrop"
+    }
+
+    fn parse(&self, contents: &str) -> Result<Vec<Link>, failure::Error> {
+        let mut records = parse_csv(contents.trim_start_matches('\u{feff}')).into_iter();
+
+        let header = records
+            .next()
+            .ok_or_else(|| failure::format_err!("The CSV file is empty"))?;
+
+        let column = |name: &str| header.iter().position(|column| column.trim() == name);
+        let url_column =
+            column("url").ok_or_else(|| failure::format_err!("The CSV file has no url column"))?;
+        let (title_column, folder_column, tags_column, created_column, read_column) = (
+            column("title"),
+            column("folder"),
+            column("tags"),
+            column("created"),
+            column("read"),
+        );
+        let imported = Utc::now();
+
+        Ok(records
+            .filter_map(|record| {
+                let field = |column: Option<usize>| {
+                    column
+                        .and_then(|column| record.get(column))
+                        .and_then(|field| non_empty(field))
+                };
+
+                let url = field(Some(url_column))?;
+                let folder = field(folder_column);
+
+                let mut link = Link::new(&url, folder.as_deref()).with_tags(
+                    field(tags_column)
+                        .map(|tags| split_tags(&tags, ','))
+                        .unwrap_or_default(),
+                );
+
+                link.title = field(title_column);
+                link.added =
+                    field(created_column).and_then(|created| created.parse::<DateTime<Utc>>().ok());
+
+                if field(read_column).is_some_and(|read| read.eq_ignore_ascii_case("true")) {
+                    set_state(&mut link, ReadState::Read, imported);
+                }
+
+                Some(link)
+            })
+            .collect())
+    }
+}
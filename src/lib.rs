@@ -1,272 +1,934 @@
-use crate::backend::Backend;
-use dirs::config_dir;
+use crate::annotation::Annotation;
+use crate::archive::{Archiver, Snapshot};
+use crate::backend::{Backend, BackendRegistry};
+use crate::check::{Checker, LinkStatus};
+use crate::config::Config;
+use crate::export::Exporter;
+use crate::import::Importer;
+use crate::metadata::{Enricher, Metadata};
+use crate::migrate::{Migration, STORE_VERSION};
+use crate::normalize::canonical_url;
+use crate::query::Query;
+use crate::reading::{Pick, ReadingStats};
+use crate::search::{Index, Search, SearchResult};
+use crate::suggest::{SuggestionRules, Suggestions};
+use chrono::{DateTime, Utc};
+use failure::Fail;
 use serde::{Deserialize, Serialize};
-use std::env;
-use std::fs::{self, File, OpenOptions};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions, TryLockError};
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+pub mod annotation;
+pub mod archive;
 pub mod backend;
+pub mod bookmarks;
+pub mod check;
+pub mod config;
+pub mod export;
+pub mod http;
+pub mod import;
+pub mod markdown;
+pub mod metadata;
+pub mod migrate;
+pub mod normalize;
+pub mod query;
+pub mod reading;
+pub mod search;
+pub mod suggest;
+pub mod tags;
+
+/// Name of the directory next to the store where pages are archived
+const ARCHIVE_DIRECTORY_NAME: &str = "link_keeper_archive";
+
+/// Name of the search index file next to the store
+const INDEX_FILE_NAME: &str = "link_keeper_index.json";
+
+/// Added to the name of the store for its lock file
+const LOCK_FILE_SUFFIX: &str = ".lock";
 
-pub struct MyQuery;
+#[derive(Debug)]
+pub struct LinkKeeper<'a> {
+    activated_backends: Vec<Box<dyn Backend>>,
+    backend_registry: BackendRegistry,
+    config: Config,
+    migrations: Vec<Migration>,
+    store: Store<'a>,
+    enricher: Option<Enricher>,
+    archiver: Option<Archiver>,
+}
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Settings {
-    config_path: PathBuf,
-    config_file_name: String,
-    store_path: PathBuf,
-    store_file_name: String,
+/// Where the configuration of a link keeper comes from
+#[derive(Debug)]
+enum ConfigSource {
+    /// The current directory and environment
+    Environment,
+    /// Only this configuration file
+    File(PathBuf),
+    Toml(String),
+    Loaded(Config),
 }
 
-impl Default for Settings {
-    fn default() -> Self {
-        let config_path = config_dir().expect("Failed to retrieve configuration directory");
-        let current_dir = env::current_dir().expect("Failed to retrieve the current directory");
+/// Builds a link keeper. Nothing is written to disk when building, unless
+/// migrations are asked for, only when the link keeper changes something.
+#[derive(Debug)]
+pub struct LinkKeeperBuilder {
+    config: ConfigSource,
+    store: Option<PathBuf>,
+    registry: Option<BackendRegistry>,
+    migrate: bool,
+}
 
-        Self {
-            config_path: config_path.join("link-keeper"),
-            config_file_name: "link-keeper.toml".to_owned(),
-            store_path: current_dir,
-            store_file_name: "link_keeper.json".to_owned(),
+impl Default for LinkKeeperBuilder {
+    fn default() -> Self {
+        LinkKeeperBuilder {
+            config: ConfigSource::Environment,
+            store: None,
+            registry: None,
+            migrate: false,
         }
     }
 }
 
-#[derive(Debug)]
-pub struct LinkKeeper<'a> {
-    activated_backends: Vec<Box<dyn Backend>>,
-    settings: Settings,
-    store: Store<'a>,
-}
+impl LinkKeeperBuilder {
+    /// Use the configuration of the current directory and environment
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-impl<'a> LinkKeeper<'a> {
-    pub fn register_backends<F>(&mut self, get_backends: F) -> Result<(), failure::Error>
-    where
-        F: FnOnce(&toml::Value) -> Option<Vec<Box<dyn Backend>>>,
-    {
-        let default_settings = Settings::default();
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = ConfigSource::Loaded(config);
+        self
+    }
 
-        let old_toml_config = Self::get_old_toml_config(
-            &default_settings
-                .config_path
-                .join(&default_settings.config_file_name),
-        )?;
+    /// Read the configuration from this file only, without project
+    /// configuration files or the environment
+    pub fn config_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.config = ConfigSource::File(path.into());
+        self
+    }
 
-        let backends = get_backends(&old_toml_config);
+    /// Use this TOML as the configuration, without reading any files
+    pub fn config_toml(mut self, toml: &str) -> Self {
+        self.config = ConfigSource::Toml(toml.to_owned());
+        self
+    }
 
-        self.activated_backends
-            .append(&mut backends.unwrap_or_else(|| vec![]));
+    /// Keep the links in this file instead of the configured store, Markdown
+    /// if it ends with `.md`
+    pub fn store<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.store = Some(path.into());
+        self
+    }
 
-        Ok(())
+    /// Make the backends in the registry available and activate the ones found
+    /// in the configuration
+    pub fn backends(mut self, registry: BackendRegistry) -> Self {
+        self.registry = Some(registry);
+        self
     }
 
-    pub fn new() -> Self {
-        let default_settings = Settings::default();
+    /// Migrate configuration files and the store written by older versions
+    /// when building
+    pub fn migrate(mut self, migrate: bool) -> Self {
+        self.migrate = migrate;
+        self
+    }
 
-        let old_toml_config = Self::get_old_toml_config(
-            &default_settings
-                .config_path
-                .join(&default_settings.config_file_name),
-        );
+    pub fn build<'a>(self) -> Result<LinkKeeper<'a>, failure::Error> {
+        let mut config = match self.config {
+            ConfigSource::Environment => Config::load(&[])?,
+            ConfigSource::File(path) => Config::load_from(path, None, vec![], &[])?,
+            ConfigSource::Toml(toml) => Config::from_toml(&toml)?,
+            ConfigSource::Loaded(config) => config,
+        };
 
-        let old_settings = old_toml_config
-            .and_then(|config| config.try_into::<Settings>().map_err(|err| err.into()));
+        let store_file = match self.store {
+            Some(store) => store,
+            None => config.settings()?.store_file(),
+        };
 
-        dbg!(&old_settings);
+        let migrations = if self.migrate {
+            migrate::migrate(&mut config, &store_file, false)?
+        } else {
+            vec![]
+        };
 
-        let (store_path, store_file_name) = old_settings
-            .map(|settings| {
-                (
-                    settings.store_path.to_owned(),
-                    settings.store_file_name.to_owned(),
-                )
-            })
-            .unwrap_or_else(|_| {
-                (
-                    default_settings.store_path.to_owned(),
-                    default_settings.store_file_name.to_owned(),
-                )
-            });
+        for migration in &migrations {
+            log::info!(path = migration.path.display().to_string(), from = migration.from, to = migration.to; "Migrated {}", migration);
+        }
+
+        let format = if store_file
+            .extension()
+            .and_then(|extension| extension.to_str())
+            == Some("md")
+        {
+            &Format::Markdown
+        } else {
+            &Format::Json
+        };
 
-        let store = Store::new(store_path, store_file_name, &Format::Json);
+        let store = Store::new(
+            store_file
+                .parent()
+                .map(|parent| parent.to_owned())
+                .unwrap_or_default(),
+            store_file
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            format,
+        );
 
-        let link_keeper = LinkKeeper {
-            activated_backends: vec![],
-            settings: default_settings,
-            store,
+        let (activated_backends, backend_registry) = match self.registry {
+            Some(registry) => (registry.load_backends(&config.merged()), registry),
+            None => (vec![], BackendRegistry::new()),
         };
 
-        let full_config_path = link_keeper.full_config_path();
+        log::debug!(store = store_file.display().to_string(), backends = activated_backends.len(); "Built link keeper");
 
-        if !Path::new(&link_keeper.settings.config_path).exists() {
-            link_keeper.create_config_directory().expect(&format!(
-                "Failed to create link keeper confugration directory at: {:?}",
-                link_keeper.settings.config_path
-            ));
-        }
+        Ok(LinkKeeper {
+            activated_backends,
+            backend_registry,
+            config,
+            migrations,
+            store,
+            enricher: None,
+            archiver: None,
+        })
+    }
+}
 
-        if !full_config_path.exists() {
-            link_keeper.create_config_file().expect(&format!(
-                "Failed to create configuration file at: {:?}",
-                full_config_path
-            ))
-        }
+impl<'a> LinkKeeper<'a> {
+    /// Create a link keeper with the configuration of the current directory
+    /// and environment, see `LinkKeeperBuilder` for more options
+    pub fn new() -> Result<Self, failure::Error> {
+        LinkKeeperBuilder::new().build()
+    }
 
-        link_keeper
+    /// The layered configuration in use
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The files that were migrated when the link keeper was built
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    /// Lock the store until the returned lock is dropped, so other link
+    /// keepers, e.g. the CLI and `link-keeper serve`, wait instead of
+    /// overwriting each other's changes. The methods changing the store take
+    /// the lock themselves, holding it keeps several calls together.
+    pub fn lock_store(&self) -> Result<StoreLock<'_>, io::Error> {
+        self.store.lock()
     }
 
     pub fn link_already_exists(&self, link: &str) -> Result<bool, io::Error> {
-        if self.store.file_exists() {
-            let old_contents = self.store.read_data_from_file()?;
+        Ok(Self::find_link(&self.get_links()?, link).is_some())
+    }
 
-            Ok(self.contains_link(&link, &old_contents))
-        } else {
-            Ok(false)
+    /// Get all the links in the store
+    pub fn get_links(&self) -> Result<Vec<Link>, io::Error> {
+        if !self.store.file_exists() || self.store.file_is_empty()? {
+            return Ok(vec![]);
         }
+
+        let contents = self.store.read_data_from_file()?;
+
+        Ok(self.store.to_orginal_format(&contents)?)
     }
 
     // TODO: Should probably use failure and return Result<(), OwnError> instead
-    pub fn add(&self, link: &'a str, category: Option<&'a str>) -> Result<(), io::Error> {
-        let new_link = Link { link, category };
+    pub fn add(
+        &self,
+        link: &str,
+        category: Option<&str>,
+        tags: &[String],
+    ) -> Result<Vec<failure::Error>, io::Error> {
+        self.add_link(self.prepare_link(link, category, tags))
+    }
+
+    /// Create a link to be added, fetching its metadata and archiving it if
+    /// an enricher or archiver is set
+    pub fn prepare_link(&self, link: &str, category: Option<&str>, tags: &[String]) -> Link {
+        let mut new_link = Link::new(link, category).with_tags(tags.to_vec());
+        new_link.added = Some(Utc::now());
+
+        if let Some(enricher) = &self.enricher {
+            // Metadata is nice to have, the link is stored regardless
+            if let Err(err) = enricher.enrich(&mut new_link) {
+                log::info!(link = link, error = err.to_string(); "Could not fetch the link's metadata");
+            }
+        }
+
+        if let Some(archiver) = &self.archiver {
+            new_link.snapshot = match archiver.archive(link, &self.archive_path()) {
+                Ok(snapshot) => Some(snapshot),
+                Err(err) => {
+                    log::info!(link = link, error = err.to_string(); "Could not archive the link");
+                    None
+                }
+            };
+        }
+
+        new_link
+    }
 
-        println!("Adding link to backends...");
+    /// Store a prepared link and add it to every activated backend. Returns
+    /// the errors of the backends that failed, the link is stored anyway.
+    ///
+    /// A link that's already stored is merged into the stored one instead,
+    /// its id follows its URL so it can't be stored twice.
+    pub fn add_link(&self, new_link: Link) -> Result<Vec<failure::Error>, io::Error> {
+        let _lock = self.store.lock()?;
+        let mut links = self.get_links()?;
 
-        dbg!(&self.activated_backends);
+        if let Some(index) = Self::find_link(&links, new_link.link()) {
+            if !links[index].merge(&new_link) {
+                return Ok(vec![]);
+            }
+
+            log::info!(link = new_link.link(), id = new_link.id(); "Merging link into the stored one");
+            self.write_links(&links)?;
+
+            return Ok(self.update_at_backends(&links[index]));
+        }
 
         // TODO: Fail on every?
         // Option to abort on fail for any?
-        let errors = self
-            .activated_backends
-            .iter()
-            .map(|backend| backend.add_link(&new_link))
-            .filter(|result| result.is_err())
-            .collect::<Vec<Result<(), failure::Error>>>();
-
-        dbg!(errors);
+        let errors = self.at_backends("add", new_link.link(), |backend| {
+            backend.add_link(&new_link)
+        });
 
         // Always add to raw!
-        self.add_to_raw(new_link)?;
+        log::info!(link = new_link.link(), id = new_link.id(); "Adding link");
+        links.push(new_link);
+        self.write_links(&links)?;
 
-        Ok(())
+        Ok(errors)
     }
 
-    fn add_to_raw(&self, new_link: Link) -> Result<(), io::Error> {
+    /// Store many prepared links at once, leaving out the ones that are
+    /// already stored or given more than once. Every activated backend gets
+    /// all the new links in a single batch.
+    pub fn add_links(&self, new_links: Vec<Link>) -> Result<AddReport, io::Error> {
+        let _lock = self.store.lock()?;
+        let mut links = self.get_links()?;
+        let mut report = AddReport::default();
+
+        for link in new_links {
+            if Self::find_link(&links, link.link()).is_some() {
+                report.duplicates.push(link);
+            } else {
+                links.push(link.clone());
+                report.added.push(link);
+            }
+        }
+
+        if report.added.is_empty() {
+            return Ok(report);
+        }
+
+        let subject = format!("{} links", report.added.len());
+        report.backend_errors =
+            self.at_backends("add", &subject, |backend| backend.add_links(&report.added));
+
+        log::info!(added = report.added.len(), duplicates = report.duplicates.len(); "Adding links");
+        self.write_links(&links)?;
+
+        Ok(report)
+    }
+
+    /// Prepare and store many links at once, see `add_links`. The links that
+    /// are already stored or given more than once are left out before
+    /// anything is fetched for them.
+    pub fn add_urls(
+        &self,
+        urls: &[String],
+        category: Option<&str>,
+        tags: &[String],
+    ) -> Result<AddReport, io::Error> {
+        let stored = self.get_links()?;
+        let mut new_links: Vec<Link> = vec![];
+        let mut duplicates = vec![];
+
+        for url in urls {
+            let link = Link::new(url, category).with_tags(tags.to_vec());
+
+            if Self::find_link(&stored, url).is_some() || Self::find_link(&new_links, url).is_some()
+            {
+                duplicates.push(link);
+            } else {
+                new_links.push(link);
+            }
+        }
+
+        let prepared = new_links
+            .iter()
+            .map(|link| self.prepare_link(link.link(), category, tags))
+            .collect();
+
+        // Links added in the meantime are left out by `add_links`
+        let mut report = self.add_links(prepared)?;
+        duplicates.append(&mut report.duplicates);
+        report.duplicates = duplicates;
+
+        Ok(report)
+    }
+
+    fn write_links(&self, links: &[Link]) -> Result<(), io::Error> {
+        let _lock = self.store.lock()?;
         self.store.create_file()?;
 
-        let formatted_data = self.store.format_data(&vec![new_link])?;
+        let formatted_data = self.store.format_data(links)?;
+        log::debug!(path = self.store.path.join(&self.store.file_name).display().to_string(), links = links.len(); "Writing store");
 
-        let formatted_data = if self.store.file_is_empty()? {
-            formatted_data
+        self.store.write_to_file(formatted_data.as_bytes())
+    }
+
+    /// Import the links read by the importer from the given file. Links that
+    /// are already stored are merged with the imported ones instead of added
+    /// again. With `dry_run` the store and backends are left untouched.
+    pub fn import(
+        &self,
+        importer: &dyn Importer,
+        path: &Path,
+        dry_run: bool,
+    ) -> Result<ImportReport, failure::Error> {
+        let imported = importer.parse(&fs::read_to_string(path)?)?;
+
+        let _lock = if dry_run {
+            None
         } else {
-            let old_contents = self.store.read_data_from_file()?;
+            Some(self.store.lock()?)
+        };
+        let mut links = self.get_links()?;
+        let mut report = ImportReport::default();
+        let mut added = vec![];
+        let mut merged = vec![];
+
+        for link in imported {
+            let action = match Self::find_link(&links, link.link()) {
+                Some(index) => {
+                    if links[index].merge(&link) {
+                        merged.push(index);
+                        report.merged += 1;
+                        ImportAction::Merged
+                    } else {
+                        report.skipped += 1;
+                        ImportAction::Skipped
+                    }
+                }
+                None => {
+                    added.push(link.clone());
+                    links.push(link.clone());
+                    report.added += 1;
+                    ImportAction::Added
+                }
+            };
+
+            report.entries.push((action, link));
+        }
 
-            let mut old_contents_as_orginal = self.store.to_orginal_format(&old_contents)?;
+        if dry_run {
+            return Ok(report);
+        }
 
-            old_contents_as_orginal.append(&mut self.store.to_orginal_format(&formatted_data)?);
+        self.write_links(&links)?;
 
-            self.store.format_data(&old_contents_as_orginal)?
-        };
+        if !added.is_empty() {
+            let subject = format!("{} links", added.len());
+            report
+                .backend_errors
+                .extend(self.at_backends("add", &subject, |backend| backend.add_links(&added)));
+        }
 
-        self.store.write_to_file(formatted_data.as_bytes())?;
+        for index in merged {
+            report
+                .backend_errors
+                .extend(self.update_at_backends(&links[index]));
+        }
 
-        Ok(())
+        Ok(report)
     }
 
-    pub fn get_available_backends(&self) -> Vec<String> {
-        vec![
-            "Git".to_owned(),
-            "Github".to_owned(),
-            "GoogleDrive".to_owned(),
-        ]
+    /// Fetch metadata of links when they are added
+    pub fn set_enricher(&mut self, enricher: Enricher) {
+        self.enricher = Some(enricher);
     }
 
-    /// Get all the activated backends
-    pub fn get_activated_backends(&self) -> Vec<&Box<dyn Backend>> {
-        self.activated_backends
+    /// Archive a copy of the page of links when they are added
+    pub fn set_archiver(&mut self, archiver: Archiver) {
+        self.archiver = Some(archiver);
+    }
+
+    /// The directory where archived pages are stored, next to the store
+    pub fn archive_path(&self) -> PathBuf {
+        self.store.path.join(ARCHIVE_DIRECTORY_NAME)
+    }
+
+    /// Get the stored link with the given id, or the only one whose id starts
+    /// with it
+    pub fn get_link(&self, id: &str) -> Result<Link, failure::Error> {
+        let mut matching = self
+            .get_links()?
+            .into_iter()
+            .filter(|link| !id.is_empty() && link.id().starts_with(id))
+            .collect::<Vec<Link>>();
+
+        match matching.len() {
+            0 => Err(LinkError::NotFound(id.to_owned()).into()),
+            1 => Ok(matching.remove(0)),
+            _ => Err(LinkError::Ambiguous(id.to_owned()).into()),
+        }
+    }
+
+    /// Fetch the metadata again for the stored links matching the query
+    pub fn refresh(&self, query: &Query) -> Result<RefreshReport, failure::Error> {
+        let enricher = self
+            .enricher
+            .as_ref()
+            .ok_or_else(|| failure::format_err!("No metadata enricher is set"))?;
+
+        let mut report = RefreshReport::default();
+        let mut fetched = vec![];
+
+        // The pages are fetched without the lock, the store can take a while
+        for link in self.get_links()?.iter().filter(|link| query.matches(link)) {
+            match enricher.fetch(link.link()) {
+                Ok(metadata) => fetched.push((link.id(), metadata)),
+                Err(err) => {
+                    log::debug!(link = link.link(), error = err.to_string(); "Could not refresh the link's metadata");
+                    report
+                        .failed
+                        .push((link.link().to_owned(), err.to_string()))
+                }
+            }
+        }
+
+        let _lock = self.store.lock()?;
+        let mut links = self.get_links()?;
+
+        for (id, metadata) in fetched {
+            // Removed while the pages were fetched
+            if let Some(link) = links.iter_mut().find(|link| link.id() == id) {
+                metadata::apply(link, metadata);
+                report.refreshed += 1;
+            }
+        }
+
+        self.write_links(&links)?;
+
+        Ok(report)
+    }
+
+    /// Suggest tags and a category for a link, using the rules in the
+    /// `[suggestions]` table of the configuration
+    pub fn suggest(&self, link: &Link) -> Result<Suggestions, failure::Error> {
+        let rules = self
+            .config
+            .section::<SuggestionRules>("suggestions")?
+            .unwrap_or_default();
+
+        Ok(suggest::suggest(link, &self.get_links()?, &rules))
+    }
+
+    /// Every tag in use with how many links have it
+    pub fn tag_counts(&self) -> Result<Vec<(String, usize)>, io::Error> {
+        Ok(tags::counts(&self.get_links()?))
+    }
+
+    /// Rename a tag and the tags nested in it
+    pub fn rename_tag(&self, from: &str, to: &str) -> Result<TagReport, failure::Error> {
+        self.merge_tags(&[from], to)
+    }
+
+    /// Replace the tags, and the tags nested in them, with `into`
+    pub fn merge_tags(&self, from: &[&str], into: &str) -> Result<TagReport, failure::Error> {
+        let from = tags::normalize_all(from);
+        let into = tags::normalize(into);
+
+        if into.is_empty() {
+            return Err(failure::format_err!("The new tag can't be empty"));
+        }
+
+        self.retag(|tag| {
+            Some(
+                from.iter()
+                    .find_map(|from| tags::rename(tag, from, &into))
+                    .unwrap_or_else(|| tag.to_owned()),
+            )
+        })
+    }
+
+    /// Remove a tag, and the tags nested in it, from every link
+    pub fn delete_tag(&self, tag: &str) -> Result<TagReport, failure::Error> {
+        let deleted = tags::normalize(tag);
+
+        self.retag(|tag| {
+            if tags::is_within(tag, &deleted) {
+                None
+            } else {
+                Some(tag.to_owned())
+            }
+        })
+    }
+
+    /// Replace every tag of the stored links with what `retag` returns for it,
+    /// or remove it on `None`. Links whose tags changed are rewritten in the
+    /// store and updated at every activated backend.
+    fn retag<F>(&self, retag: F) -> Result<TagReport, failure::Error>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let _lock = self.store.lock()?;
+        let mut links = self.get_links()?;
+        let mut report = TagReport::default();
+        let mut changed = vec![];
+
+        for (index, link) in links.iter_mut().enumerate() {
+            let tags = tags::normalize_all(link.tags.iter().filter_map(|tag| retag(tag)));
+
+            if tags != link.tags {
+                link.tags = tags;
+                changed.push(index);
+            }
+        }
+
+        report.updated = changed.len();
+
+        if changed.is_empty() {
+            return Ok(report);
+        }
+
+        self.write_links(&links)?;
+
+        for index in changed {
+            for err in self.update_at_backends(&links[index]) {
+                report
+                    .failed
+                    .push((links[index].link().to_owned(), err.to_string()));
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Replace the stored link that has the same id and update it at every
+    /// activated backend. Returns the errors of the backends that failed.
+    pub fn update(&self, link: Link) -> Result<Vec<failure::Error>, failure::Error> {
+        let _lock = self.store.lock()?;
+        let mut links = self.get_links()?;
+        let id = link.id();
+
+        let index = links
             .iter()
-            .collect::<Vec<&Box<dyn Backend>>>()
+            .position(|existing| existing.id() == id)
+            .ok_or_else(|| LinkError::NotFound(id.to_owned()))?;
+
+        log::info!(link = link.link(), id = id; "Updating link");
+        links[index] = link;
+        self.write_links(&links)?;
+
+        Ok(self.update_at_backends(&links[index]))
     }
 
-    pub fn add_backend(&mut self, backend: Box<dyn Backend>) -> Result<(), failure::Error> {
-        backend.add(self)?;
-        self.activated_backends.push(backend);
+    /// Set the reading state of the link with the given id and record when
+    /// it changed
+    pub fn set_state(
+        &self,
+        id: &str,
+        state: ReadState,
+    ) -> Result<Vec<failure::Error>, failure::Error> {
+        let _lock = self.store.lock()?;
+        let mut link = self.get_link(id)?;
+        link.state = state;
+        link.state_changes.insert(state, Utc::now());
+
+        self.update(link)
+    }
 
-        self.create_toml_string()
-            .map(|toml_string| self.write_to_config(&toml_string))??;
+    /// Attach a note or highlight to the link with the given id
+    pub fn annotate(
+        &self,
+        id: &str,
+        annotation: Annotation,
+    ) -> Result<Vec<failure::Error>, failure::Error> {
+        if annotation.text.is_empty() {
+            return Err(failure::format_err!("The annotation is empty"));
+        }
 
-        Ok(())
+        let _lock = self.store.lock()?;
+        let mut link = self.get_link(id)?;
+        link.annotations.push(annotation);
+
+        self.update(link)
     }
 
-    fn contains_link(&self, new_link: &str, old_contents: &str) -> bool {
-        old_contents.contains(new_link)
+    /// The next unread link to read among the links matching the query
+    pub fn next(&self, query: &Query, pick: Pick) -> Result<Option<Link>, io::Error> {
+        Ok(reading::next(&self.get_links()?, query, pick))
     }
 
-    /// Convience function to get the full path to the configuration file
-    fn full_config_path(&self) -> PathBuf {
-        self.settings
-            .config_path
-            .join(&self.settings.config_file_name)
+    pub fn reading_stats(&self) -> Result<ReadingStats, io::Error> {
+        Ok(reading::stats(&self.get_links()?, Utc::now()))
     }
 
-    fn create_config_directory(&self) -> io::Result<()> {
-        fs::create_dir(&self.settings.config_path)?;
+    /// Remove the link with the given id from the store and every activated
+    /// backend. Returns the errors of the backends that failed.
+    pub fn remove(&self, id: &str) -> Result<Vec<failure::Error>, failure::Error> {
+        let _lock = self.store.lock()?;
+        let link = self.get_link(id)?;
+        let mut links = self.get_links()?;
 
-        Ok(())
+        links.retain(|existing| existing.id() != link.id());
+        log::info!(link = link.link(), id = link.id(); "Removing link");
+        self.write_links(&links)?;
+
+        Ok(self.at_backends("remove", link.link(), |backend| backend.remove_link(&link)))
     }
 
-    fn create_config_file(&self) -> io::Result<()> {
-        File::create(
-            &self
-                .settings
-                .config_path
-                .join(&self.settings.config_file_name),
-        )?;
+    fn update_at_backends(&self, link: &Link) -> Vec<failure::Error> {
+        self.at_backends("update", link.link(), |backend| backend.update_link(link))
+    }
 
-        Ok(())
+    /// Apply an operation to every activated backend, logging and returning
+    /// the errors of the backends that failed
+    fn at_backends<F>(&self, operation: &str, subject: &str, apply: F) -> Vec<failure::Error>
+    where
+        F: Fn(&dyn Backend) -> Result<(), failure::Error>,
+    {
+        self.activated_backends
+            .iter()
+            .filter_map(|backend| {
+                let err = apply(backend.as_ref()).err()?;
+                log::debug!(backend = backend.to_string(), operation = operation, subject = subject, error = err.to_string(); "Backend failed");
+
+                Some(err)
+            })
+            .collect()
     }
 
-    fn get_old_toml_config(path: &Path) -> Result<toml::Value, failure::Error> {
-        let mut file = File::open(path)?;
+    /// Check whether the stored links matching the query still work and
+    /// record the result on each link. With `rewrite_redirects` links that
+    /// are permanently redirected are replaced with where they lead, also at
+    /// the backends since the id of a link follows its URL.
+    pub fn check(
+        &self,
+        checker: &Checker,
+        query: &Query,
+        rewrite_redirects: bool,
+    ) -> Result<CheckReport, failure::Error> {
+        // The links are checked without the lock, checking can take a while
+        let urls = self
+            .get_links()?
+            .into_iter()
+            .filter(|link| query.matches(link))
+            .map(|link| link.link)
+            .collect::<Vec<String>>();
+        let statuses =
+            checker.check_all(&urls.iter().map(|url| url.as_str()).collect::<Vec<&str>>());
+
+        let _lock = self.store.lock()?;
+        let mut links = self.get_links()?;
+        let mut report = CheckReport::default();
+        let mut rewritten = vec![];
+
+        for (url, status) in urls.into_iter().zip(statuses) {
+            // Removed while the links were checked
+            let index = match links.iter().position(|link| link.link == url) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let rewritten_to = status
+                .permanent_location()
+                .filter(|_| rewrite_redirects)
+                .filter(|location| Self::find_link(&links, location).is_none())
+                .map(|location| location.to_owned());
+
+            let link = &mut links[index];
+            let original = link.link.to_owned();
+
+            if let Some(location) = &rewritten_to {
+                rewritten.push((link.clone(), index));
+                link.link = location.to_owned();
+            }
+
+            link.last_check = Some(status.clone());
+
+            report.results.push(CheckResult {
+                link: original,
+                status,
+                rewritten_to,
+            });
+        }
 
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        Ok(toml::from_str::<toml::Value>(&contents)?)
-    }
-
-    fn create_toml_string(&self) -> Result<String, toml::ser::Error> {
-        let merge_tomls = |config: Result<String, toml::ser::Error>, backend: &Box<dyn Backend>| {
-            config
-                .map(|toml_string| {
-                    format!(
-                        "\n[backends.{}]\n{}",
-                        backend.to_string().to_lowercase().replace(" ", "_"),
-                        toml_string
-                    )
+        self.write_links(&links)?;
+
+        for (original, index) in rewritten {
+            let errors = self
+                .at_backends("remove", original.link(), |backend| {
+                    backend.remove_link(&original)
                 })
-                .unwrap_or_else(|_| "".to_owned())
-        };
-        dbg!(&self.activated_backends);
+                .into_iter()
+                .chain(self.at_backends("add", links[index].link(), |backend| {
+                    backend.add_link(&links[index])
+                }));
+
+            report
+                .backend_errors
+                .extend(errors.map(|err| (original.link().to_owned(), err.to_string())));
+        }
+
+        Ok(report)
+    }
+
+    /// Get the stored links matching the query
+    pub fn query(&self, query: &Query) -> Result<Vec<Link>, io::Error> {
+        Ok(query.apply(&self.get_links()?))
+    }
 
-        let backend_config_string = self
-            .activated_backends
+    /// Search the stored links and their archived content, the index is
+    /// brought up to date first
+    pub fn search(&self, search: &Search) -> Result<Vec<SearchResult>, failure::Error> {
+        // The index is written next to the store, whoever changes the store
+        // could be writing it too
+        let _lock = self.store.lock()?;
+        let links = self.get_links()?;
+        let index_path = self.store.path.join(INDEX_FILE_NAME);
+        let mut index = Index::load(&index_path);
+
+        if index.update(&links, &self.archive_path()) {
+            log::debug!(path = index_path.display().to_string(); "Saving the search index");
+            index.save(&index_path)?;
+        }
+
+        Ok(search.run(&index, &links))
+    }
+
+    /// Export the stored links matching the query
+    pub fn export(&self, exporter: &dyn Exporter, query: &Query) -> Result<String, failure::Error> {
+        exporter.export(&self.query(query)?)
+    }
+
+    /// Get the names of all registered backends
+    pub fn get_available_backends(&self) -> Vec<String> {
+        self.backend_registry
+            .names()
             .iter()
-            .map(|backend| merge_tomls(backend.get_toml_config(), backend))
-            .fold("".to_owned(), |prev, curr| format!("{}{}", prev, curr));
+            .map(|name| name.to_string())
+            .collect::<Vec<String>>()
+    }
 
-        toml::to_string(&self.settings)
-            .map(|toml_string| format!("{}{}", toml_string, backend_config_string))
+    pub fn backend_registry(&self) -> &BackendRegistry {
+        &self.backend_registry
     }
 
-    fn write_to_config(&self, toml_string: &str) -> Result<(), io::Error> {
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(self.full_config_path())?;
+    /// Get all the activated backends
+    pub fn get_activated_backends(&self) -> Vec<&dyn Backend> {
+        self.activated_backends
+            .iter()
+            .map(|backend| backend.as_ref())
+            .collect::<Vec<&dyn Backend>>()
+    }
 
-        file.write_all(toml_string.as_bytes())?;
+    pub fn add_backend(&mut self, backend: Box<dyn Backend>) -> Result<(), failure::Error> {
+        backend.add(self)?;
+
+        let name = backend.to_string().to_lowercase().replace(" ", "_");
+        let backend_config = toml::from_str::<toml::Value>(&backend.get_toml_config()?)?;
+
+        let path = self
+            .config
+            .set(&format!("backends.{}", name), backend_config, false)?;
+        log::info!(backend = name, config = path.display().to_string(); "Activated backend");
+        self.activated_backends.push(backend);
 
         Ok(())
     }
+
+    /// Find the position of a link among the given links, comparing their
+    /// canonical URLs
+    fn find_link(links: &[Link], link: &str) -> Option<usize> {
+        let canonical = canonical_url(link);
+
+        links
+            .iter()
+            .position(|existing| canonical_url(existing.link()) == canonical)
+    }
+}
+
+/// The outcome of checking links
+#[derive(Debug, Default, PartialEq)]
+pub struct CheckReport {
+    pub results: Vec<CheckResult>,
+    /// The rewritten links that could not be replaced at a backend and why,
+    /// they were rewritten in the store anyway
+    pub backend_errors: Vec<(String, String)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CheckResult {
+    /// The link as it was before the check
+    pub link: String,
+    pub status: LinkStatus,
+    /// Where the link was rewritten to because of permanent redirects
+    pub rewritten_to: Option<String>,
+}
+
+/// The outcome of refreshing metadata
+#[derive(Debug, Default, PartialEq)]
+pub struct RefreshReport {
+    pub refreshed: usize,
+    /// The links that could not be fetched and why
+    pub failed: Vec<(String, String)>,
+}
+
+/// The outcome of renaming, merging or deleting a tag
+#[derive(Debug, Default, PartialEq)]
+pub struct TagReport {
+    /// How many links had their tags changed
+    pub updated: usize,
+    /// The links that could not be updated at a backend and why
+    pub failed: Vec<(String, String)>,
+}
+
+/// The outcome of adding many links at once
+#[derive(Debug, Default)]
+pub struct AddReport {
+    pub added: Vec<Link>,
+    /// The links left out because they're already stored or were given
+    /// before in the same batch
+    pub duplicates: Vec<Link>,
+    /// The errors of the backends that failed, the links were stored anyway
+    pub backend_errors: Vec<failure::Error>,
+}
+
+/// The outcome of an import
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub added: usize,
+    pub skipped: usize,
+    pub merged: usize,
+    /// What happened to every imported link, in the order they were read
+    pub entries: Vec<(ImportAction, Link)>,
+    /// The errors of the backends that failed, the links were stored anyway
+    pub backend_errors: Vec<failure::Error>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportAction {
+    Added,
+    /// Already stored and nothing new to merge
+    Skipped,
+    /// Already stored, missing details were filled in
+    Merged,
 }
 
 #[derive(Debug, Serialize)]
@@ -275,18 +937,284 @@ enum Format {
     Markdown,
 }
 
+/// The JSON store, links are kept in a document with the store's version
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct StoreDocument<L> {
+    pub(crate) version: i64,
+    pub(crate) links: L,
+}
+
 #[derive(Debug, Serialize)]
 struct Store<'a> {
     path: PathBuf,
     file_name: String,
     format: &'a Format,
+    /// How many times the lock is held, it's only released by the last one
+    #[serde(skip)]
+    lock_depth: Cell<usize>,
+    /// The locked lock file while the lock is held
+    #[serde(skip)]
+    lock_file: RefCell<Option<File>>,
+}
+
+/// The lock of the store, see `LinkKeeper::lock_store`. Released when
+/// dropped.
+#[derive(Debug)]
+pub struct StoreLock<'s> {
+    depth: &'s Cell<usize>,
+    file: &'s RefCell<Option<File>>,
+}
+
+impl Drop for StoreLock<'_> {
+    fn drop(&mut self) {
+        self.depth.set(self.depth.get() - 1);
+
+        if self.depth.get() == 0 {
+            // Closing the file releases the lock
+            self.file.borrow_mut().take();
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Link<'a> {
-    link: &'a str,
+/// How many characters of the hash are used as the id of a link
+const LINK_ID_LENGTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Link {
+    link: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    category: Option<&'a str>,
+    category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    added: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "ReadState::is_unread")]
+    state: ReadState,
+    /// When the link last entered each state
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    state_changes: BTreeMap<ReadState, DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_check: Option<LinkStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    snapshot: Option<Snapshot>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadState {
+    #[default]
+    Unread,
+    Reading,
+    Read,
+    Archived,
+}
+
+impl fmt::Display for ReadState {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let state = match self {
+            ReadState::Unread => "unread",
+            ReadState::Reading => "reading",
+            ReadState::Read => "read",
+            ReadState::Archived => "archived",
+        };
+
+        fmt.write_str(state)
+    }
+}
+
+impl FromStr for ReadState {
+    type Err = failure::Error;
+
+    fn from_str(state: &str) -> Result<Self, Self::Err> {
+        match state.to_lowercase().as_str() {
+            "unread" => Ok(ReadState::Unread),
+            "reading" => Ok(ReadState::Reading),
+            "read" => Ok(ReadState::Read),
+            "archived" => Ok(ReadState::Archived),
+            _ => Err(failure::format_err!("Unknown state: {}", state)),
+        }
+    }
+}
+
+impl ReadState {
+    fn is_unread(&self) -> bool {
+        *self == ReadState::Unread
+    }
+}
+
+/// Why a link couldn't be found by its id
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkError {
+    NotFound(String),
+    /// More than one link has an id starting with the given one
+    Ambiguous(String),
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            LinkError::NotFound(id) => fmt.write_fmt(format_args!("No link with id {}", id)),
+            LinkError::Ambiguous(id) => fmt.write_fmt(format_args!(
+                "More than one link has an id starting with {}",
+                id
+            )),
+        }
+    }
+}
+
+impl Fail for LinkError {}
+
+impl Link {
+    pub fn new(link: &str, category: Option<&str>) -> Self {
+        Link {
+            link: link.to_owned(),
+            category: category.map(|category| category.to_owned()),
+            title: None,
+            tags: vec![],
+            added: None,
+            state: ReadState::Unread,
+            state_changes: BTreeMap::new(),
+            metadata: None,
+            last_check: None,
+            snapshot: None,
+            annotations: vec![],
+        }
+    }
+
+    pub fn with_title(mut self, title: Option<&str>) -> Self {
+        self.title = title.map(|title| title.to_owned());
+        self
+    }
+
+    pub fn with_category(mut self, category: Option<&str>) -> Self {
+        self.category = category.map(|category| category.to_owned());
+        self
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags::normalize_all(tags);
+        self
+    }
+
+    pub fn link(&self) -> &str {
+        &self.link
+    }
+
+    /// A short identifier derived from the canonical URL
+    pub fn id(&self) -> String {
+        archive::hash(canonical_url(&self.link).as_bytes())[..LINK_ID_LENGTH].to_owned()
+    }
+
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// When the link was added, if known
+    pub fn added(&self) -> Option<DateTime<Utc>> {
+        self.added
+    }
+
+    pub fn state(&self) -> ReadState {
+        self.state
+    }
+
+    /// When the link last entered the state
+    pub fn state_changed(&self, state: ReadState) -> Option<DateTime<Utc>> {
+        self.state_changes.get(&state).copied()
+    }
+
+    /// Metadata fetched from the page, if any
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// The result of the last time the link was checked
+    pub fn last_check(&self) -> Option<&LinkStatus> {
+        self.last_check.as_ref()
+    }
+
+    /// Notes and highlights, oldest first
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// The archived copy of the page, if any
+    pub fn snapshot(&self) -> Option<&Snapshot> {
+        self.snapshot.as_ref()
+    }
+
+    /// Fill in what this link is missing from another link to the same URL.
+    /// Returns whether anything changed.
+    fn merge(&mut self, other: &Link) -> bool {
+        let mut changed = false;
+
+        if self.category.is_none() && other.category.is_some() {
+            self.category = other.category.clone();
+            changed = true;
+        }
+
+        if self.title.is_none() && other.title.is_some() {
+            self.title = other.title.clone();
+            changed = true;
+        }
+
+        if self.added.is_none() && other.added.is_some() {
+            self.added = other.added;
+            changed = true;
+        }
+
+        if self.snapshot.is_none() && other.snapshot.is_some() {
+            self.snapshot = other.snapshot.clone();
+            changed = true;
+        }
+
+        if self.metadata.is_none() && other.metadata.is_some() {
+            self.metadata = other.metadata.clone();
+            changed = true;
+        }
+
+        if self.state.is_unread() && !other.state.is_unread() {
+            self.state = other.state;
+            changed = true;
+        }
+
+        for annotation in &other.annotations {
+            if !self.annotations.contains(annotation) {
+                self.annotations.push(annotation.clone());
+                changed = true;
+            }
+        }
+
+        for (state, changed_at) in &other.state_changes {
+            if !self.state_changes.contains_key(state) {
+                self.state_changes.insert(*state, *changed_at);
+                changed = true;
+            }
+        }
+
+        for tag in &other.tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.to_owned());
+                changed = true;
+            }
+        }
+
+        changed
+    }
 }
 
 impl<'a> Store<'a> {
@@ -295,22 +1223,73 @@ impl<'a> Store<'a> {
             path,
             file_name,
             format,
+            lock_depth: Cell::new(0),
+            lock_file: RefCell::new(None),
         }
     }
 
-    fn format_data(&self, links: &'a Vec<Link>) -> Result<String, serde_json::error::Error> {
+    /// Lock the lock file next to the store, waiting for other processes
+    /// holding it. Taking the lock again while it's held only counts it.
+    fn lock(&self) -> Result<StoreLock<'_>, io::Error> {
+        if self.lock_depth.get() == 0 {
+            fs::create_dir_all(&self.path)?;
+
+            let path = self
+                .path
+                .join(format!("{}{}", self.file_name, LOCK_FILE_SUFFIX));
+            let file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&path)?;
+
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => {
+                    log::info!(path = path.display().to_string(); "Waiting for the store to be unlocked");
+                    file.lock()?;
+                }
+                Err(TryLockError::Error(err)) => return Err(err),
+            }
+
+            *self.lock_file.borrow_mut() = Some(file);
+        }
+
+        self.lock_depth.set(self.lock_depth.get() + 1);
+
+        Ok(StoreLock {
+            depth: &self.lock_depth,
+            file: &self.lock_file,
+        })
+    }
+
+    fn format_data(&self, links: &[Link]) -> Result<String, serde_json::error::Error> {
         let formatted = match self.format {
-            Format::Json => serde_json::to_string(links)?,
-            Format::Markdown => "".to_owned(),
+            Format::Json => serde_json::to_string(&StoreDocument {
+                version: STORE_VERSION,
+                links,
+            })?,
+            Format::Markdown => markdown::write(links)?,
         };
 
         Ok(formatted)
     }
 
-    fn to_orginal_format(&self, contents: &'a str) -> Result<Vec<Link>, serde_json::error::Error> {
+    fn to_orginal_format(&self, contents: &str) -> Result<Vec<Link>, serde_json::error::Error> {
         let formatted = match self.format {
-            Format::Json => serde_json::from_str::<_>(contents)?,
-            Format::Markdown => unimplemented!(),
+            Format::Json => {
+                let document = serde_json::from_str::<StoreDocument<Vec<Link>>>(contents)?;
+
+                if document.version != STORE_VERSION {
+                    return Err(serde::de::Error::custom(format!(
+                        "The store has version {} instead of {}, run `link-keeper migrate`",
+                        document.version, STORE_VERSION
+                    )));
+                }
+
+                document.links
+            }
+            Format::Markdown => markdown::parse(contents)?,
         };
 
         Ok(formatted)
@@ -324,8 +1303,7 @@ impl<'a> Store<'a> {
 
     fn create_file(&self) -> Result<(), io::Error> {
         if !self.file_exists() {
-            // TODO: Real logging
-            dbg!("Creating file...");
+            fs::create_dir_all(&self.path)?;
             fs::File::create(self.joined())?;
         }
 
@@ -349,8 +1327,10 @@ impl<'a> Store<'a> {
     fn write_to_file(&self, contents: &[u8]) -> Result<(), io::Error> {
         let full_path = self.joined();
 
-        // TODO: Improvement, use .append(true) instead of write.
-        let mut file = OpenOptions::new().write(true).open(full_path)?;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(full_path)?;
 
         file.write_all(contents)?;
 
@@ -0,0 +1,174 @@
+//! The Markdown store format. Every link is a list item that reads well on
+//! its own, followed by a comment with the complete link so nothing is lost
+//! when the store is read back.
+
+use crate::annotation::AnnotationKind;
+use crate::migrate::STORE_VERSION;
+use crate::Link;
+
+const COMMENT_START: &str = "<!-- link-keeper ";
+const COMMENT_END: &str = "-->";
+const VERSION_START: &str = "<!-- link-keeper version ";
+
+/// The text on a single line, a line break would end the list item
+fn one_line(text: &str) -> String {
+    text.split(['\r', '\n'])
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// The list item showing the link, without the trailing comment
+pub(crate) fn item(link: &Link) -> String {
+    let url = one_line(link.link());
+    let mut item = match link.title() {
+        Some(title) => format!(
+            "- [{}](<{}>)",
+            one_line(title).replace('[', "\\[").replace(']', "\\]"),
+            url
+        ),
+        None => format!("- <{}>", url),
+    };
+
+    if let Some(category) = link.category() {
+        item.push_str(&format!(" in {}", one_line(category)));
+    }
+
+    for tag in link.tags() {
+        item.push_str(&format!(" #{}", one_line(tag)));
+    }
+
+    if !link.state().is_unread() {
+        item.push_str(&format!(" ({})", link.state()));
+    }
+
+    item
+}
+
+/// The link's notes as a nested list and its highlights as quotes, one
+/// line each and indented below the item
+pub(crate) fn annotations(link: &Link) -> String {
+    let mut lines = vec![];
+
+    for annotation in link.annotations() {
+        let date = annotation.created.format("%Y-%m-%d");
+        let mut text = annotation.text.lines();
+        let first = text.next().unwrap_or("");
+
+        match annotation.kind {
+            AnnotationKind::Note => {
+                lines.push(format!("  - Note ({}): {}", date, first));
+                lines.extend(text.map(|line| format!("    {}", line)));
+            }
+            AnnotationKind::Highlight => {
+                lines.push(format!("  > {}", first));
+                lines.extend(text.map(|line| format!("  > {}", line)));
+                lines.push(format!("  > — highlighted {}", date));
+            }
+        }
+    }
+
+    lines
+        .iter()
+        .map(|line| format!("{}\n", line.trim_end()))
+        .collect()
+}
+
+pub fn write(links: &[Link]) -> Result<String, serde_json::Error> {
+    let mut markdown = format!(
+        "# Links\n\n{}{} {}\n\n",
+        VERSION_START, STORE_VERSION, COMMENT_END
+    );
+
+    for link in links {
+        // `<` and `>` only occur inside JSON strings, escaping them keeps
+        // `-->` and the start of another comment out
+        let json = serde_json::to_string(link)?
+            .replace('<', "\\u003c")
+            .replace('>', "\\u003e");
+
+        markdown.push_str(&format!(
+            "{} {}{} {}\n",
+            item(link),
+            COMMENT_START,
+            json,
+            COMMENT_END
+        ));
+        markdown.push_str(&annotations(link));
+    }
+
+    Ok(markdown)
+}
+
+/// The version of the store, stores written before it was versioned are
+/// version 0
+pub fn version(markdown: &str) -> i64 {
+    markdown
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(VERSION_START))
+        .and_then(|version| version.trim_end_matches(COMMENT_END).trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Read the links of a Markdown store. List items without a comment, e.g.
+/// added by hand, are read from their Markdown link or URL. Indented lines
+/// belong to the item above and are already part of its comment.
+pub fn parse(markdown: &str) -> Result<Vec<Link>, serde_json::Error> {
+    markdown
+        .lines()
+        .filter_map(|line| parse_item(line.trim_end()))
+        .collect()
+}
+
+fn parse_item(line: &str) -> Option<Result<Link, serde_json::Error>> {
+    let item = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))?
+        .trim();
+
+    // The title before the comment may contain its start too, but the JSON
+    // in the comment can't
+    if let Some(start) = item.rfind(COMMENT_START) {
+        let json = item[start + COMMENT_START.len()..]
+            .trim_end()
+            .trim_end_matches(COMMENT_END);
+
+        return Some(serde_json::from_str(json.trim()));
+    }
+
+    let (title, url, rest) = if item.starts_with('[') {
+        let end = item.find("](")?;
+        let rest = &item[end + 2..];
+        let url_end = if rest.starts_with('<') {
+            rest.find(">)")? + 1
+        } else {
+            rest.find(')')?
+        };
+
+        (
+            Some(item[1..end].replace("\\[", "[").replace("\\]", "]")),
+            &rest[..url_end],
+            &rest[url_end + 1..],
+        )
+    } else {
+        let url = item.split_whitespace().next()?;
+
+        (None, url, &item[url.len()..])
+    };
+
+    let url = url.trim_start_matches('<').trim_end_matches('>');
+    let tags = rest
+        .split_whitespace()
+        .filter_map(|word| word.strip_prefix('#'))
+        .map(|tag| tag.to_owned())
+        .collect();
+
+    if url.contains("://") {
+        Some(Ok(Link::new(url, None)
+            .with_title(title.as_deref())
+            .with_tags(tags)))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,167 @@
+//! Fetching pages and extracting their title, description, canonical URL and
+//! favicon.
+
+use crate::bookmarks::{attribute, text_until, tokenize, Token};
+use crate::http::{self, HttpClient, Request};
+use crate::normalize::resolve_url;
+use crate::Link;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetched: Option<DateTime<Utc>>,
+}
+
+fn first_of(candidates: &[&Option<String>]) -> Option<String> {
+    candidates
+        .iter()
+        .find_map(|candidate| candidate.as_ref())
+        .map(|value| value.trim().to_owned())
+        .filter(|value| !value.is_empty())
+}
+
+/// Extract the metadata from the `<head>` of a page found at `url`
+pub fn extract(html: &str, url: &str) -> Metadata {
+    let tokens = tokenize(html);
+    let mut tokens = tokens.iter();
+
+    let mut title = None;
+    let mut properties: Vec<(String, String)> = vec![];
+    let mut canonical_url = None;
+    let mut favicon = None;
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Open(name, _) if name == "title" && title.is_none() => {
+                title = Some(text_until(&mut tokens, "title"));
+            }
+            Token::Open(name, attributes) if name == "meta" => {
+                let key =
+                    attribute(attributes, "property").or_else(|| attribute(attributes, "name"));
+
+                if let (Some(key), Some(content)) = (key, attribute(attributes, "content")) {
+                    properties.push((key.to_lowercase(), content.to_owned()));
+                }
+            }
+            Token::Open(name, attributes) if name == "link" => {
+                let rel = attribute(attributes, "rel").unwrap_or("").to_lowercase();
+                let href = attribute(attributes, "href").map(|href| resolve_url(url, href));
+
+                if rel.split_whitespace().any(|rel| rel == "canonical") {
+                    canonical_url = canonical_url.or(href);
+                } else if rel.split_whitespace().any(|rel| rel == "icon") {
+                    favicon = favicon.or(href);
+                }
+            }
+            Token::Open(name, _) if name == "body" => break,
+            Token::Close(name) if name == "head" => break,
+            _ => {}
+        }
+    }
+
+    let property = |key: &str| {
+        properties
+            .iter()
+            .find(|(property_key, _)| property_key == key)
+            .map(|(_, content)| content.to_owned())
+    };
+
+    Metadata {
+        title: first_of(&[&title, &property("og:title"), &property("twitter:title")]),
+        description: first_of(&[
+            &property("og:description"),
+            &property("description"),
+            &property("twitter:description"),
+        ]),
+        canonical_url: canonical_url.or_else(|| property("og:url")),
+        favicon: favicon.or_else(|| Some(resolve_url(url, "/favicon.ico"))),
+        image: first_of(&[&property("og:image"), &property("twitter:image")])
+            .map(|image| resolve_url(url, &image)),
+        fetched: Some(Utc::now()),
+    }
+}
+
+/// Fetches pages and attaches their metadata to links
+#[derive(Debug)]
+pub struct Enricher {
+    client: Box<dyn HttpClient>,
+    timeout: Duration,
+    max_body_bytes: u64,
+}
+
+impl Enricher {
+    pub fn new(client: Box<dyn HttpClient>) -> Self {
+        Enricher {
+            client,
+            timeout: Duration::from_secs(10),
+            max_body_bytes: 1024 * 1024,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Pages are only read up to this size, the `<head>` is normally well
+    /// within it
+    pub fn max_body_bytes(mut self, max_body_bytes: u64) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    pub fn fetch(&self, url: &str) -> Result<Metadata, failure::Error> {
+        let request = Request::get(url)
+            .timeout(self.timeout)
+            .max_body_bytes(self.max_body_bytes);
+
+        let (response, _redirects) = http::fetch(self.client.as_ref(), &request)?;
+
+        if !response.is_success() {
+            return Err(failure::format_err!(
+                "Fetching {} failed with status {}",
+                url,
+                response.status
+            ));
+        }
+
+        if !response.is_html() {
+            return Ok(Metadata {
+                fetched: Some(Utc::now()),
+                ..Metadata::default()
+            });
+        }
+
+        Ok(extract(&response.text(), &response.url))
+    }
+
+    /// Fetch the metadata of the link and store it on the link. The title of
+    /// the page is used if the link has none.
+    pub fn enrich(&self, link: &mut Link) -> Result<(), failure::Error> {
+        apply(link, self.fetch(link.link())?);
+
+        Ok(())
+    }
+}
+
+/// Store fetched metadata on the link, see `Enricher::enrich`
+pub(crate) fn apply(link: &mut Link, metadata: Metadata) {
+    if link.title.is_none() {
+        link.title = metadata.title.clone();
+    }
+
+    link.metadata = Some(metadata);
+}
@@ -0,0 +1,331 @@
+//! Upgrading configuration files and stores written by older versions.
+//!
+//! Both carry a `version`, files without one are version 0. Every step
+//! upgrades a file from one version to the next, so a file of any older
+//! version is brought up to date by running the steps after its version in
+//! order. The old file is kept as a backup next to it.
+
+use crate::config::{self, Config};
+use crate::{markdown, Link, StoreDocument};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::DocumentMut;
+
+/// The version of the configuration files written by this version
+pub const CONFIG_VERSION: i64 = 1;
+
+/// The version of the stores written by this version
+pub const STORE_VERSION: i64 = 1;
+
+/// Upgrades a document from the version before `to`
+struct Step<T> {
+    to: i64,
+    description: &'static str,
+    apply: fn(&mut T) -> Result<(), failure::Error>,
+}
+
+fn config_steps() -> Vec<Step<DocumentMut>> {
+    vec![Step {
+        to: 1,
+        description: "Remove config_path and config_file_name, the configuration's location is no longer a setting",
+        apply: |document| {
+            remove_key(document, "config_path");
+            remove_key(document, "config_file_name");
+
+            Ok(())
+        },
+    }]
+}
+
+/// Remove a key, moving the comments above it to the key after it
+fn remove_key(table: &mut toml_edit::Table, key: &str) {
+    let position = table.iter().position(|(existing, _)| existing == key);
+    let comments = table
+        .key(key)
+        .and_then(|key| key.leaf_decor().prefix())
+        .and_then(|prefix| prefix.as_str())
+        .filter(|prefix| prefix.contains('#'))
+        .map(|prefix| prefix.to_owned());
+
+    table.remove(key);
+
+    if let (Some(position), Some(comments)) = (position, comments) {
+        if let Some((mut next, _)) = table.iter_mut().nth(position) {
+            let prefix = next
+                .leaf_decor()
+                .prefix()
+                .and_then(|prefix| prefix.as_str())
+                .unwrap_or("")
+                .to_owned();
+
+            next.leaf_decor_mut()
+                .set_prefix(format!("{}{}", comments, prefix));
+        }
+    }
+}
+
+/// Stores are migrated in their JSON shape, version 0 is a bare list of links
+fn store_steps() -> Vec<Step<serde_json::Value>> {
+    vec![Step {
+        to: 1,
+        description: "Record the store's version, JSON stores keep their links in a `links` field",
+        apply: |document| {
+            let links = document.take();
+            *document = serde_json::json!({ "version": 1, "links": links });
+
+            Ok(())
+        },
+    }]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Config,
+    Store,
+}
+
+/// A file that is, or has been, upgraded from one version to another
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    pub kind: Kind,
+    pub path: PathBuf,
+    pub from: i64,
+    pub to: i64,
+    /// What every step changes
+    pub changes: Vec<&'static str>,
+    /// Where the file was copied to before it was changed
+    pub backup: Option<PathBuf>,
+}
+
+impl fmt::Display for Migration {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            Kind::Config => "config",
+            Kind::Store => "store",
+        };
+
+        fmt.write_fmt(format_args!(
+            "{} {} from version {} to {}",
+            kind,
+            self.path.display(),
+            self.from,
+            self.to
+        ))
+    }
+}
+
+/// Where the file is copied to before it's migrated from `version`
+fn backup_path(path: &Path, version: i64) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    path.with_file_name(format!("{}.v{}.bak", file_name, version))
+}
+
+fn check_version(path: &Path, version: i64, current: i64) -> Result<(), failure::Error> {
+    if version > current {
+        return Err(failure::format_err!(
+            "{} has version {}, this version of link keeper only knows up to version {}",
+            path.display(),
+            version,
+            current
+        ));
+    }
+
+    Ok(())
+}
+
+fn steps_after<T>(steps: Vec<Step<T>>, version: i64) -> Vec<Step<T>> {
+    steps.into_iter().filter(|step| step.to > version).collect()
+}
+
+/// Read a file for migration, `None` if it's missing or empty and so has
+/// nothing to migrate
+fn read(path: &Path) -> Result<Option<String>, failure::Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let contents = fs::read_to_string(path)?;
+
+    if contents.trim().is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(contents))
+    }
+}
+
+fn config_version(document: &DocumentMut) -> i64 {
+    document
+        .get("version")
+        .and_then(|version| version.as_integer())
+        .unwrap_or(0)
+}
+
+/// Migrate a configuration file, or with `check` only tell what would change
+pub fn migrate_config(path: &Path, check: bool) -> Result<Option<Migration>, failure::Error> {
+    let contents = match read(path)? {
+        Some(contents) => contents,
+        None => return Ok(None),
+    };
+
+    let mut document = contents.parse::<DocumentMut>()?;
+    let version = config_version(&document);
+    check_version(path, version, CONFIG_VERSION)?;
+
+    let steps = steps_after(config_steps(), version);
+
+    if steps.is_empty() {
+        return Ok(None);
+    }
+
+    let mut migration = Migration {
+        kind: Kind::Config,
+        path: path.to_owned(),
+        from: version,
+        to: CONFIG_VERSION,
+        changes: steps.iter().map(|step| step.description).collect(),
+        backup: None,
+    };
+
+    if check {
+        return Ok(Some(migration));
+    }
+
+    for step in steps {
+        (step.apply)(&mut document)?;
+    }
+
+    let backup = backup_path(path, version);
+    fs::copy(path, &backup)?;
+    migration.backup = Some(backup);
+
+    // The version goes first, where it's seen
+    document.insert("version", toml_edit::value(CONFIG_VERSION));
+    document.sort_values_by(|first, _, second, _| {
+        (first.get() != "version").cmp(&(second.get() != "version"))
+    });
+
+    config::write_atomically(path, &document.to_string())?;
+
+    Ok(Some(migration))
+}
+
+/// The version of a store and its links in the JSON shape of that version
+fn store_document(
+    contents: &str,
+    markdown: bool,
+) -> Result<(i64, serde_json::Value), failure::Error> {
+    if markdown {
+        let version = markdown::version(contents);
+        let links = serde_json::to_value(markdown::parse(contents)?)?;
+
+        let document = if version == 0 {
+            links
+        } else {
+            serde_json::json!({ "version": version, "links": links })
+        };
+
+        return Ok((version, document));
+    }
+
+    let document = serde_json::from_str::<serde_json::Value>(contents)?;
+    let version = document
+        .get("version")
+        .and_then(|version| version.as_i64())
+        .unwrap_or(0);
+
+    Ok((version, document))
+}
+
+/// Migrate a store, or with `check` only tell what would change. Markdown
+/// stores are read as the links they list and written again.
+pub fn migrate_store(
+    path: &Path,
+    markdown: bool,
+    check: bool,
+) -> Result<Option<Migration>, failure::Error> {
+    let contents = match read(path)? {
+        Some(contents) => contents,
+        None => return Ok(None),
+    };
+
+    let (version, mut document) = store_document(&contents, markdown)?;
+    check_version(path, version, STORE_VERSION)?;
+
+    let steps = steps_after(store_steps(), version);
+
+    if steps.is_empty() {
+        return Ok(None);
+    }
+
+    let mut migration = Migration {
+        kind: Kind::Store,
+        path: path.to_owned(),
+        from: version,
+        to: STORE_VERSION,
+        changes: steps.iter().map(|step| step.description).collect(),
+        backup: None,
+    };
+
+    if check {
+        return Ok(Some(migration));
+    }
+
+    for step in steps {
+        (step.apply)(&mut document)?;
+    }
+
+    let backup = backup_path(path, version);
+    fs::copy(path, &backup)?;
+    migration.backup = Some(backup);
+
+    let links = serde_json::from_value::<Vec<Link>>(document["links"].take())?;
+
+    let migrated = if markdown {
+        markdown::write(&links)?
+    } else {
+        serde_json::to_string(&StoreDocument {
+            version: STORE_VERSION,
+            links,
+        })?
+    };
+
+    config::write_atomically(path, &migrated)?;
+
+    Ok(Some(migration))
+}
+
+/// Migrate the configuration files and the store, or with `check` only tell
+/// what would change. The configuration is read again after its files
+/// changed.
+pub fn migrate(
+    config: &mut Config,
+    store: &Path,
+    check: bool,
+) -> Result<Vec<Migration>, failure::Error> {
+    let mut migrations = vec![];
+
+    let config_paths = config
+        .global_path()
+        .into_iter()
+        .chain(config.project_path())
+        .map(|path| path.to_owned())
+        .collect::<Vec<PathBuf>>();
+
+    for path in config_paths {
+        migrations.extend(migrate_config(&path, check)?);
+    }
+
+    if !check && !migrations.is_empty() {
+        config.reload()?;
+    }
+
+    let markdown = store.extension().and_then(|extension| extension.to_str()) == Some("md");
+    migrations.extend(migrate_store(store, markdown, check)?);
+
+    Ok(migrations)
+}
@@ -0,0 +1,147 @@
+/// Query parameters that only track where a link came from
+const TRACKING_PARAMETERS: &[&str] = &["utm_", "fbclid", "gclid"];
+
+/// Normalize a link so that different spellings of the same URL compare
+/// equal: the scheme and host are lowercased and the fragment, tracking query
+/// parameters and any trailing slash are removed.
+pub fn canonical_url(link: &str) -> String {
+    let link = link.trim();
+    let link = link.split('#').next().unwrap_or(link);
+
+    let (scheme, rest) = match link.find("://") {
+        Some(index) => (link[..index].to_lowercase(), &link[index + 3..]),
+        None => return link.trim_end_matches('/').to_owned(),
+    };
+
+    let (authority, path) = rest.split_at(rest.find(['/', '?']).unwrap_or(rest.len()));
+
+    let (path, query) = match path.find('?') {
+        Some(index) => (&path[..index], Some(&path[index + 1..])),
+        None => (path, None),
+    };
+
+    let query = query
+        .map(|query| {
+            query
+                .split('&')
+                .filter(|parameter| !parameter.is_empty())
+                .filter(|parameter| {
+                    !TRACKING_PARAMETERS
+                        .iter()
+                        .any(|tracking| parameter.starts_with(tracking))
+                })
+                .collect::<Vec<&str>>()
+                .join("&")
+        })
+        .unwrap_or_default();
+
+    format!(
+        "{}://{}{}{}{}",
+        scheme,
+        authority.to_lowercase(),
+        path.trim_end_matches('/'),
+        if query.is_empty() { "" } else { "?" },
+        query
+    )
+}
+
+/// Resolve a possibly relative reference, e.g. from a `href` attribute,
+/// against the URL of the page it was found on
+pub fn resolve_url(base: &str, reference: &str) -> String {
+    let reference = reference.trim();
+
+    if reference.contains("://") || reference.starts_with("data:") {
+        return reference.to_owned();
+    }
+
+    let scheme_end = match base.find("://") {
+        Some(index) => index,
+        None => return reference.to_owned(),
+    };
+
+    if reference.starts_with("//") {
+        return format!("{}:{}", &base[..scheme_end], reference);
+    }
+
+    let rest = &base[scheme_end + 3..];
+    let origin_end = scheme_end + 3 + rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let without_fragment = base.split('#').next().unwrap_or(base);
+    let without_query = without_fragment
+        .split('?')
+        .next()
+        .unwrap_or(without_fragment);
+
+    if reference.starts_with('/') {
+        format!("{}{}", &base[..origin_end], reference)
+    } else if reference.starts_with('?') {
+        format!("{}{}", without_query, reference)
+    } else if reference.starts_with('#') || reference.is_empty() {
+        format!("{}{}", without_fragment, reference)
+    } else {
+        let directory_end = without_query[origin_end..]
+            .rfind('/')
+            .map_or(origin_end, |index| origin_end + index);
+
+        format!("{}/{}", &without_query[..directory_end], reference)
+    }
+}
+
+/// Whether the character can't be part of a URL written in text
+fn ends_url(character: char) -> bool {
+    character.is_whitespace() || ['<', '>', '"', '`'].contains(&character)
+}
+
+/// Remove the punctuation that ends the sentence a URL is written in, and
+/// closing brackets that weren't opened in the URL, e.g. in `(see
+/// https://example.com).`
+fn trim_url(url: &str) -> &str {
+    let mut url = url;
+
+    loop {
+        let opened =
+            |open: char, close: char| url.matches(open).count() >= url.matches(close).count();
+
+        url = match url.chars().last() {
+            Some('.') | Some(',') | Some(';') | Some(':') | Some('!') | Some('?') | Some('\'')
+            | Some('*') => &url[..url.len() - 1],
+            Some(')') if !opened('(', ')') => &url[..url.len() - 1],
+            Some(']') if !opened('[', ']') => &url[..url.len() - 1],
+            Some('}') if !opened('{', '}') => &url[..url.len() - 1],
+            _ => return url,
+        };
+    }
+}
+
+/// Find the http and https URLs in free text, e.g. a chat log or a list of
+/// links with one on every line. The scheme is matched in any case and
+/// written in lowercase.
+pub fn extract_urls(text: &str) -> Vec<String> {
+    // Lowercasing only ASCII keeps the positions the same as in the text
+    let lowercase = text.to_ascii_lowercase();
+    let mut urls = vec![];
+    let mut position = 0;
+
+    while let Some(found) = lowercase[position..].find("http") {
+        let start = position + found;
+        let candidate = &text[start..];
+        let end = candidate.find(ends_url).unwrap_or(candidate.len());
+        let url = trim_url(&candidate[..end]);
+
+        let scheme = ["https://", "http://"]
+            .iter()
+            .find(|scheme| lowercase[start..].starts_with(**scheme));
+        let host = scheme
+            .and_then(|scheme| url.get(scheme.len()..))
+            .unwrap_or("");
+
+        match scheme {
+            Some(scheme) if !host.is_empty() && !host.starts_with('/') => {
+                urls.push(format!("{}{}", scheme, host));
+                position = start + end;
+            }
+            _ => position = start + "http".len(),
+        }
+    }
+
+    urls
+}
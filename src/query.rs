@@ -0,0 +1,148 @@
+use crate::bookmarks::CATEGORY_SEPARATOR;
+use crate::tags;
+use crate::{Link, ReadState};
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+
+/// Filters for selecting stored links. An empty query matches every link.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Query {
+    text: Option<String>,
+    category: Option<String>,
+    tags: Vec<String>,
+    domain: Option<String>,
+    state: Option<ReadState>,
+    added_after: Option<DateTime<Utc>>,
+    newest_first: bool,
+    limit: Option<usize>,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Query::default()
+    }
+
+    /// Only links whose URL or title contains the text, ignoring case
+    pub fn text(mut self, text: &str) -> Self {
+        self.text = Some(text.to_lowercase());
+        self
+    }
+
+    /// Only links in the category or any of its subcategories
+    pub fn category(mut self, category: &str) -> Self {
+        self.category = Some(category.to_owned());
+        self
+    }
+
+    /// Only links with the tag or any tag nested in it, can be given
+    /// multiple times
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tags::normalize(tag));
+        self
+    }
+
+    /// Only links on the domain or any of its subdomains
+    pub fn domain(mut self, domain: &str) -> Self {
+        self.domain = Some(domain.to_lowercase());
+        self
+    }
+
+    pub fn state(mut self, state: ReadState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    pub fn added_after(mut self, added_after: DateTime<Utc>) -> Self {
+        self.added_after = Some(added_after);
+        self
+    }
+
+    /// Sort the links by when they were added, newest first
+    pub fn newest_first(mut self) -> Self {
+        self.newest_first = true;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// The maximum number of links the query selects
+    pub(crate) fn max_links(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub fn matches(&self, link: &Link) -> bool {
+        let text_matches = self.text.as_ref().is_none_or(|text| {
+            link.link().to_lowercase().contains(text)
+                || link
+                    .title()
+                    .is_some_and(|title| title.to_lowercase().contains(text))
+        });
+
+        let category_matches = self.category.as_ref().is_none_or(|category| {
+            link.category().is_some_and(|link_category| {
+                link_category == category
+                    || link_category.starts_with(&format!("{}{}", category, CATEGORY_SEPARATOR))
+            })
+        });
+
+        let tags_match = self.tags.iter().all(|tag| {
+            link.tags()
+                .iter()
+                .any(|link_tag| tags::is_within(link_tag, tag))
+        });
+
+        let domain_matches = self.domain.as_ref().is_none_or(|domain| {
+            domain_of(link.link()).is_some_and(|link_domain| {
+                link_domain == *domain || link_domain.ends_with(&format!(".{}", domain))
+            })
+        });
+
+        let state_matches = self.state.is_none_or(|state| link.state() == state);
+
+        let added_matches = self
+            .added_after
+            .is_none_or(|added_after| link.added().is_some_and(|added| added > added_after));
+
+        text_matches
+            && category_matches
+            && tags_match
+            && domain_matches
+            && state_matches
+            && added_matches
+    }
+
+    /// Select the matching links
+    pub fn apply(&self, links: &[Link]) -> Vec<Link> {
+        let mut matching = links
+            .iter()
+            .filter(|link| self.matches(link))
+            .cloned()
+            .collect::<Vec<Link>>();
+
+        if self.newest_first {
+            matching.sort_by_key(|link| Reverse(link.added()));
+        }
+
+        if let Some(limit) = self.limit {
+            matching.truncate(limit);
+        }
+
+        matching
+    }
+}
+
+/// The lowercased host of a URL, without any `www.` prefix
+pub fn domain_of(link: &str) -> Option<String> {
+    let (_, without_scheme) = link.split_once("://")?;
+    let authority = without_scheme.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.trim_start_matches("www.").to_lowercase())
+    }
+}
@@ -0,0 +1,99 @@
+//! Using the stored links as a reading list.
+
+use crate::query::Query;
+use crate::{Link, ReadState};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Throughput is averaged over this many weeks
+const AVERAGE_WEEKS: i64 = 12;
+
+/// How the next link to read is picked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pick {
+    /// The unread link that was added first
+    Oldest,
+    Random,
+}
+
+/// The next unread link matching the query
+pub fn next(links: &[Link], query: &Query, pick: Pick) -> Option<Link> {
+    let unread = query.clone().state(ReadState::Unread);
+    let candidates = links
+        .iter()
+        .filter(|link| unread.matches(link))
+        .collect::<Vec<&Link>>();
+
+    match pick {
+        // Links without a date were imported and are older than the rest
+        Pick::Oldest => candidates.into_iter().min_by_key(|link| link.added()),
+        Pick::Random => {
+            let random = RandomState::new().build_hasher().finish() as usize;
+
+            candidates.get(random % candidates.len().max(1)).copied()
+        }
+    }
+    .cloned()
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReadingStats {
+    pub unread: usize,
+    pub reading: usize,
+    pub read: usize,
+    pub archived: usize,
+    pub read_last_week: usize,
+    pub read_last_month: usize,
+    /// Links read per week over the last twelve weeks
+    pub weekly_average: f64,
+    /// The median time from adding a link until it was read
+    pub median_days_to_read: Option<f64>,
+}
+
+pub fn stats(links: &[Link], now: DateTime<Utc>) -> ReadingStats {
+    let mut stats = ReadingStats::default();
+
+    for link in links {
+        match link.state() {
+            ReadState::Unread => stats.unread += 1,
+            ReadState::Reading => stats.reading += 1,
+            ReadState::Read => stats.read += 1,
+            ReadState::Archived => stats.archived += 1,
+        }
+    }
+
+    let read_times = links
+        .iter()
+        .filter_map(|link| link.state_changed(ReadState::Read))
+        .collect::<Vec<DateTime<Utc>>>();
+
+    let read_since = |duration: Duration| {
+        read_times
+            .iter()
+            .filter(|read| **read > now - duration)
+            .count()
+    };
+
+    stats.read_last_week = read_since(Duration::weeks(1));
+    stats.read_last_month = read_since(Duration::days(30));
+    stats.weekly_average = read_since(Duration::weeks(AVERAGE_WEEKS)) as f64 / AVERAGE_WEEKS as f64;
+
+    let mut days_to_read = links
+        .iter()
+        .filter_map(|link| Some((link.added()?, link.state_changed(ReadState::Read)?)))
+        .map(|(added, read)| (read - added).num_seconds() as f64 / 86_400.0)
+        .collect::<Vec<f64>>();
+
+    days_to_read.sort_by(|first, second| first.total_cmp(second));
+
+    stats.median_days_to_read = match days_to_read.len() {
+        0 => None,
+        count if count % 2 == 0 => {
+            Some((days_to_read[count / 2 - 1] + days_to_read[count / 2]) / 2.0)
+        }
+        count => Some(days_to_read[count / 2]),
+    };
+
+    stats
+}
@@ -0,0 +1,403 @@
+//! Full-text search over the stored links, their titles and their archived
+//! page content, backed by an inverted index stored beside the link store.
+
+use crate::archive;
+use crate::config;
+use crate::query::Query;
+use crate::{Link, ReadState};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// BM25 term frequency saturation
+const K1: f64 = 1.2;
+/// BM25 document length normalization
+const B: f64 = 0.75;
+
+/// The indexed parts of a link
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Url,
+    Title,
+    Description,
+    Notes,
+    Text,
+}
+
+impl Field {
+    /// How much a match in the field counts towards the score
+    fn weight(self) -> f64 {
+        match self {
+            Field::Title => 3.0,
+            Field::Description | Field::Notes => 2.0,
+            Field::Url | Field::Text => 1.0,
+        }
+    }
+}
+
+/// Split text into lowercased words
+pub fn tokens(text: &str) -> Vec<String> {
+    text.split(|character: char| !character.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Document {
+    /// Changes whenever the indexed content of the link changes
+    fingerprint: String,
+    length: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Occurrence {
+    field: Field,
+    positions: Vec<usize>,
+}
+
+/// Maps every word to the links, and the positions in them, it occurs in
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Index {
+    documents: BTreeMap<String, Document>,
+    postings: BTreeMap<String, BTreeMap<String, Vec<Occurrence>>>,
+}
+
+/// The text of every indexed field of a link
+fn fields(link: &Link, archive_path: &Path) -> Vec<(Field, String)> {
+    let metadata = link.metadata();
+
+    let mut fields = vec![(Field::Url, link.link().to_owned())];
+
+    if let Some(title) = link
+        .title()
+        .or_else(|| metadata.and_then(|metadata| metadata.title.as_deref()))
+    {
+        fields.push((Field::Title, title.to_owned()));
+    }
+
+    if let Some(description) = metadata.and_then(|metadata| metadata.description.as_ref()) {
+        fields.push((Field::Description, description.to_owned()));
+    }
+
+    if !link.annotations().is_empty() {
+        let notes = link
+            .annotations()
+            .iter()
+            .map(|annotation| annotation.text.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        fields.push((Field::Notes, notes));
+    }
+
+    if let Some(text) = link
+        .snapshot()
+        .and_then(|snapshot| fs::read_to_string(snapshot.text_path(archive_path)).ok())
+    {
+        fields.push((Field::Text, text));
+    }
+
+    fields
+}
+
+fn fingerprint(fields: &[(Field, String)]) -> String {
+    let contents = fields
+        .iter()
+        .map(|(field, text)| format!("{:?}\u{0}{}", field, text))
+        .collect::<Vec<String>>()
+        .join("\u{0}");
+
+    archive::hash(contents.as_bytes())
+}
+
+impl Index {
+    /// Read the index from a file, an unreadable or missing index is empty
+    pub fn load(path: &Path) -> Index {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the index to a file, a search reading it meanwhile sees either
+    /// the old or the new index
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        config::write_atomically(path, &serde_json::to_string(self)?)
+    }
+
+    /// Bring the index up to date with the links, only links that changed
+    /// since they were indexed are indexed again. Returns whether anything
+    /// changed.
+    pub fn update(&mut self, links: &[Link], archive_path: &Path) -> bool {
+        let ids = links
+            .iter()
+            .map(|link| link.id())
+            .collect::<HashSet<String>>();
+
+        let removed = self
+            .documents
+            .keys()
+            .filter(|id| !ids.contains(*id))
+            .cloned()
+            .collect::<Vec<String>>();
+
+        let mut changed = !removed.is_empty();
+
+        for id in removed {
+            self.remove(&id);
+        }
+
+        let mut indexed = HashSet::new();
+
+        for link in links {
+            let id = link.id();
+
+            // Links stored more than once share an id
+            if !indexed.insert(id.clone()) {
+                continue;
+            }
+
+            let fields = fields(link, archive_path);
+            let fingerprint = fingerprint(&fields);
+
+            if self
+                .documents
+                .get(&id)
+                .is_some_and(|document| document.fingerprint == fingerprint)
+            {
+                continue;
+            }
+
+            self.remove(&id);
+            self.insert(&id, fingerprint, &fields);
+            changed = true;
+        }
+
+        changed
+    }
+
+    fn remove(&mut self, id: &str) {
+        if self.documents.remove(id).is_none() {
+            return;
+        }
+
+        for documents in self.postings.values_mut() {
+            documents.remove(id);
+        }
+
+        self.postings.retain(|_, documents| !documents.is_empty());
+    }
+
+    fn insert(&mut self, id: &str, fingerprint: String, fields: &[(Field, String)]) {
+        let mut length = 0;
+
+        for (field, text) in fields {
+            let words = tokens(text);
+            length += words.len();
+
+            for (position, word) in words.into_iter().enumerate() {
+                let occurrences = self
+                    .postings
+                    .entry(word)
+                    .or_default()
+                    .entry(id.to_owned())
+                    .or_default();
+
+                match occurrences
+                    .iter_mut()
+                    .find(|occurrence| occurrence.field == *field)
+                {
+                    Some(occurrence) => occurrence.positions.push(position),
+                    None => occurrences.push(Occurrence {
+                        field: *field,
+                        positions: vec![position],
+                    }),
+                }
+            }
+        }
+
+        self.documents.insert(
+            id.to_owned(),
+            Document {
+                fingerprint,
+                length,
+            },
+        );
+    }
+
+    fn occurrences(&self, word: &str, id: &str) -> &[Occurrence] {
+        self.postings
+            .get(word)
+            .and_then(|documents| documents.get(id))
+            .map(|occurrences| occurrences.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Whether the words occur right after each other in one field
+    fn contains_phrase(&self, phrase: &[String], id: &str) -> bool {
+        let (first, rest) = match phrase.split_first() {
+            Some(split) => split,
+            None => return true,
+        };
+
+        self.occurrences(first, id).iter().any(|start| {
+            start.positions.iter().any(|position| {
+                rest.iter().enumerate().all(|(offset, word)| {
+                    self.occurrences(word, id).iter().any(|occurrence| {
+                        occurrence.field == start.field
+                            && occurrence.positions.contains(&(position + offset + 1))
+                    })
+                })
+            })
+        })
+    }
+
+    /// Average number of words of the indexed links
+    fn average_length(&self) -> f64 {
+        self.documents
+            .values()
+            .map(|document| document.length as f64)
+            .sum::<f64>()
+            / self.documents.len() as f64
+    }
+
+    /// BM25 score of the link for the words, `None` if any word is missing.
+    /// `average_length` is the same for every link, so it's passed in.
+    fn score(&self, words: &[String], id: &str, average_length: f64) -> Option<f64> {
+        let document = self.documents.get(id)?;
+        let count = self.documents.len() as f64;
+
+        words.iter().try_fold(0.0, |score, word| {
+            let occurrences = self.occurrences(word, id);
+
+            if occurrences.is_empty() {
+                return None;
+            }
+
+            let frequency = occurrences
+                .iter()
+                .map(|occurrence| occurrence.field.weight() * occurrence.positions.len() as f64)
+                .sum::<f64>();
+
+            let matching = self
+                .postings
+                .get(word)
+                .map_or(0, |documents| documents.len()) as f64;
+            let idf = ((count - matching + 0.5) / (matching + 0.5) + 1.0).ln();
+            let normalization = 1.0 - B + B * document.length as f64 / average_length.max(1.0);
+
+            Some(score + idf * frequency * (K1 + 1.0) / (frequency + K1 * normalization))
+        })
+    }
+}
+
+/// A parsed search, e.g. `borrow "memory safety" tag:rust domain:github.com`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Search {
+    words: Vec<String>,
+    phrases: Vec<Vec<String>>,
+    query: Query,
+}
+
+impl Search {
+    /// Parse a search. Quoted text is matched as a phrase, `tag:`, `category:`,
+    /// `domain:` and `state:` are added as filters to the query.
+    pub fn parse(search: &str, query: Query) -> Result<Search, failure::Error> {
+        let mut parsed = Search {
+            query,
+            ..Search::default()
+        };
+
+        for (part, quoted) in split_quoted(search) {
+            let words = tokens(&part);
+
+            if quoted {
+                if words.len() > 1 {
+                    parsed.phrases.push(words.clone());
+                }
+
+                parsed.words.extend(words);
+                continue;
+            }
+
+            let query = parsed.query.clone();
+
+            parsed.query = match part.split_once(':') {
+                Some(("tag", tag)) => query.tag(tag),
+                Some(("category", category)) => query.category(category),
+                Some(("domain", domain)) => query.domain(domain),
+                Some(("state", state)) => query.state(state.parse::<ReadState>()?),
+                _ => {
+                    parsed.words.extend(words);
+                    query
+                }
+            };
+        }
+
+        Ok(parsed)
+    }
+
+    /// Rank the links matching the search, best match first
+    pub fn run(&self, index: &Index, links: &[Link]) -> Vec<SearchResult> {
+        let average_length = index.average_length();
+
+        let mut results = links
+            .iter()
+            .filter(|link| self.query.matches(link))
+            .filter_map(|link| {
+                let id = link.id();
+
+                if !self
+                    .phrases
+                    .iter()
+                    .all(|phrase| index.contains_phrase(phrase, &id))
+                {
+                    return None;
+                }
+
+                index
+                    .score(&self.words, &id, average_length)
+                    .map(|score| SearchResult {
+                        link: link.clone(),
+                        score,
+                    })
+            })
+            .collect::<Vec<SearchResult>>();
+
+        results.sort_by(|first, second| second.score.total_cmp(&first.score));
+
+        if let Some(limit) = self.query.max_links() {
+            results.truncate(limit);
+        }
+
+        results
+    }
+}
+
+/// Split on whitespace, keeping quoted text together
+fn split_quoted(search: &str) -> Vec<(String, bool)> {
+    search
+        .split('"')
+        .enumerate()
+        .flat_map(|(number, part)| {
+            if number % 2 == 1 {
+                vec![(part.to_owned(), true)]
+            } else {
+                part.split_whitespace()
+                    .map(|word| (word.to_owned(), false))
+                    .collect()
+            }
+        })
+        .filter(|(part, _)| !part.trim().is_empty())
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub link: Link,
+    pub score: f64,
+}
@@ -0,0 +1,154 @@
+//! Suggesting tags and a category for a new link from its domain, URL, title
+//! and how the stored links are tagged.
+
+use crate::query::domain_of;
+use crate::search::tokens;
+use crate::tags::{self, TAG_SEPARATOR};
+use crate::Link;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// At most this many tags are suggested
+const MAX_SUGGESTIONS: usize = 8;
+
+/// Words in URLs and titles too common to say anything about a link
+const STOP_WORDS: &[&str] = &[
+    "and", "com", "for", "from", "html", "http", "https", "index", "net", "org", "php", "the",
+    "with", "www",
+];
+
+/// Tags that are always suggested for links on a domain or its subdomains,
+/// configured in the `[suggestions.domains]` table, e.g.
+///
+/// ```toml
+/// [suggestions.domains]
+/// "arxiv.org" = ["paper"]
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SuggestionRules {
+    #[serde(default)]
+    pub domains: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Suggestions {
+    /// Best suggestion first, never includes tags the link already has
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+}
+
+fn on_domain(domain: &str, rule_domain: &str) -> bool {
+    let rule_domain = rule_domain.trim_start_matches("www.").to_lowercase();
+
+    domain == rule_domain || domain.ends_with(&format!(".{}", rule_domain))
+}
+
+/// The words of the link's domain, path and title worth matching to tags
+fn words(link: &Link) -> Vec<String> {
+    let without_query = link.link().split(['?', '#']).next().unwrap_or("");
+    let title = link
+        .title()
+        .or_else(|| {
+            link.metadata()
+                .and_then(|metadata| metadata.title.as_deref())
+        })
+        .unwrap_or("");
+
+    let mut words = tokens(without_query);
+    words.extend(tokens(title));
+    words.retain(|word| word.len() > 2 && !STOP_WORDS.contains(&word.as_str()));
+    words.sort_unstable();
+    words.dedup();
+
+    words
+}
+
+/// Suggest tags and a category for a link based on the stored links
+pub fn suggest(link: &Link, history: &[Link], rules: &SuggestionRules) -> Suggestions {
+    let domain = domain_of(link.link()).unwrap_or_default();
+    let mut scores: HashMap<String, f64> = HashMap::new();
+
+    for tag in rules
+        .domains
+        .iter()
+        .filter(|(rule_domain, _)| on_domain(&domain, rule_domain))
+        .flat_map(|(_, tags)| tags)
+    {
+        *scores.entry(tags::normalize(tag)).or_default() += 10.0;
+    }
+
+    // Tags used for other links on the same domain, by how often they are used
+    let same_domain = history
+        .iter()
+        .filter(|existing| domain_of(existing.link()).as_deref() == Some(domain.as_str()))
+        .collect::<Vec<&Link>>();
+
+    for existing in &same_domain {
+        for tag in existing.tags() {
+            *scores.entry(tag.to_owned()).or_default() += 4.0 / same_domain.len() as f64;
+        }
+    }
+
+    // Tags whose name, or the last part of it, is a word of the link
+    let words = words(link);
+
+    for (tag, _) in tags::counts(history) {
+        let name = tag.rsplit(TAG_SEPARATOR).next().unwrap_or(&tag);
+
+        if words.iter().any(|word| word == name) {
+            *scores.entry(tag.to_owned()).or_default() += 3.0;
+        }
+    }
+
+    // Tags often used together with the ones found so far
+    let found = scores
+        .keys()
+        .cloned()
+        .chain(link.tags().iter().cloned())
+        .collect::<Vec<String>>();
+
+    for tag in &found {
+        let together = history
+            .iter()
+            .filter(|existing| existing.tags().contains(tag))
+            .collect::<Vec<&Link>>();
+
+        for existing in &together {
+            for other in existing.tags().iter().filter(|other| *other != tag) {
+                *scores.entry(other.to_owned()).or_default() += 1.0 / together.len() as f64;
+            }
+        }
+    }
+
+    let mut scores = scores
+        .into_iter()
+        .filter(|(tag, _)| !tag.is_empty() && !link.tags().contains(tag))
+        .collect::<Vec<(String, f64)>>();
+
+    scores.sort_by(|(first_tag, first), (second_tag, second)| {
+        second
+            .total_cmp(first)
+            .then_with(|| first_tag.cmp(second_tag))
+    });
+
+    let mut categories: BTreeMap<&str, usize> = BTreeMap::new();
+
+    for category in same_domain
+        .iter()
+        .filter_map(|existing| existing.category())
+    {
+        *categories.entry(category).or_default() += 1;
+    }
+
+    Suggestions {
+        tags: scores
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(tag, _)| tag)
+            .collect(),
+        category: categories
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(category, _)| category.to_owned()),
+    }
+}
@@ -0,0 +1,93 @@
+//! Tags can be nested by separating their parts with `TAG_SEPARATOR`, e.g.
+//! `lang/rust`. A link tagged `lang/rust` is also counted and found under
+//! `lang`.
+
+use crate::Link;
+use std::collections::BTreeMap;
+
+/// Separator between the parts of a nested tag
+pub const TAG_SEPARATOR: &str = "/";
+
+/// Trim whitespace, leading `#` and empty parts from a tag
+pub fn normalize(tag: &str) -> String {
+    tag.trim()
+        .trim_start_matches('#')
+        .split(TAG_SEPARATOR)
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join(TAG_SEPARATOR)
+}
+
+/// Normalize the tags and remove duplicates, keeping their order
+pub fn normalize_all<I, S>(tags: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut normalized: Vec<String> = vec![];
+
+    for tag in tags {
+        let tag = normalize(tag.as_ref());
+
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+
+    normalized
+}
+
+/// Whether the tag is `parent` or nested below it
+pub fn is_within(tag: &str, parent: &str) -> bool {
+    tag == parent
+        || tag
+            .strip_prefix(parent)
+            .is_some_and(|rest| rest.starts_with(TAG_SEPARATOR))
+}
+
+/// The tag and every tag it is nested in, e.g. `lang/rust` and `lang`
+pub fn with_parents(tag: &str) -> Vec<&str> {
+    tag.match_indices(TAG_SEPARATOR)
+        .map(|(index, _)| &tag[..index])
+        .chain(Some(tag))
+        .collect()
+}
+
+/// Replace `from` with `to` in the tag, nested tags keep their nesting, e.g.
+/// renaming `lang` to `languages` turns `lang/rust` into `languages/rust`
+pub fn rename(tag: &str, from: &str, to: &str) -> Option<String> {
+    if is_within(tag, from) {
+        Some(format!("{}{}", to, &tag[from.len()..]))
+    } else {
+        None
+    }
+}
+
+/// How many links have every tag, links with nested tags count towards the
+/// tags they are nested in. Sorted so nested tags follow their parent.
+pub fn counts(links: &[Link]) -> Vec<(String, usize)> {
+    let mut counts: BTreeMap<Vec<&str>, usize> = BTreeMap::new();
+
+    for link in links {
+        let mut tags = link
+            .tags()
+            .iter()
+            .flat_map(|tag| with_parents(tag))
+            .collect::<Vec<&str>>();
+
+        tags.sort_unstable();
+        tags.dedup();
+
+        for tag in tags {
+            *counts
+                .entry(tag.split(TAG_SEPARATOR).collect())
+                .or_default() += 1;
+        }
+    }
+
+    counts
+        .into_iter()
+        .map(|(parts, count)| (parts.join(TAG_SEPARATOR), count))
+        .collect()
+}
@@ -0,0 +1,101 @@
+mod fixture;
+
+use fixture::temp_dir;
+use link_keeper::annotation::{Annotation, AnnotationKind};
+use link_keeper::export::{Exporter, Markdown};
+use link_keeper::{Link, LinkError, LinkKeeper, LinkKeeperBuilder};
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+fn keeper<'a>(directory: &Path) -> LinkKeeper<'a> {
+    LinkKeeperBuilder::new()
+        .config_path(directory.join("link-keeper.toml"))
+        .store(directory.join("links.json"))
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn annotations_are_kept_in_the_order_they_were_added() {
+    let directory = temp_dir("annotate");
+    let keeper = keeper(&directory);
+
+    keeper.add("https://example.com", None, &[]).unwrap();
+    let id = keeper.get_links().unwrap()[0].id();
+
+    let errors = keeper
+        .annotate(&id[..4], Annotation::note("  First thoughts \n"))
+        .unwrap();
+    assert!(errors.is_empty());
+    keeper
+        .annotate(&id, Annotation::highlight("A quote"))
+        .unwrap();
+
+    let link = keeper.get_link(&id).unwrap();
+    let annotations = link.annotations();
+
+    assert_eq!(annotations.len(), 2);
+    assert_eq!(annotations[0].kind, AnnotationKind::Note);
+    assert_eq!(annotations[0].text, "First thoughts");
+    assert_eq!(annotations[1].kind, AnnotationKind::Highlight);
+    assert_eq!(annotations[1].text, "A quote");
+    assert!(annotations[0].created <= annotations[1].created);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn empty_annotations_and_unknown_links_are_errors() {
+    let directory = temp_dir("annotate-errors");
+    let keeper = keeper(&directory);
+
+    keeper.add("https://example.com", None, &[]).unwrap();
+    let id = keeper.get_links().unwrap()[0].id();
+
+    assert!(keeper.annotate(&id, Annotation::note(" \n ")).is_err());
+
+    let err = keeper
+        .annotate("ffffffff", Annotation::note("A note"))
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<LinkError>(),
+        Some(&LinkError::NotFound("ffffffff".to_owned()))
+    );
+
+    assert!(keeper.get_links().unwrap()[0].annotations().is_empty());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn annotations_are_rendered_below_their_link() {
+    let link: Link = serde_json::from_value(json!({
+        "link": "https://example.com",
+        "title": "Example",
+        "annotations": [
+            {
+                "kind": "note",
+                "text": "Two\nlines",
+                "created": "2024-03-01T10:00:00Z"
+            },
+            {
+                "kind": "highlight",
+                "text": "Quoted\npassage",
+                "created": "2024-03-02T10:00:00Z"
+            }
+        ]
+    }))
+    .unwrap();
+
+    assert_eq!(
+        Markdown.export(&[link]).unwrap(),
+        "# Links\n\n\
+         - [Example](<https://example.com>)\n  \
+         - Note (2024-03-01): Two\n    \
+         lines\n  \
+         > Quoted\n  \
+         > passage\n  \
+         > — highlighted 2024-03-02\n"
+    );
+}
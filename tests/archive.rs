@@ -0,0 +1,124 @@
+mod fixture;
+
+use fixture::{temp_dir, Fixture, Page};
+use link_keeper::archive::{self, Archiver};
+use link_keeper::http::UreqClient;
+use std::fs;
+use std::path::Path;
+
+fn entries(directory: &Path) -> Vec<String> {
+    let mut entries = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect::<Vec<String>>();
+    entries.sort();
+
+    entries
+}
+
+const ARTICLE: &str = "<html><body><nav>Home</nav>\
+                       <article><h1>Title</h1><p>Some text.</p></article></body></html>";
+
+#[test]
+fn pages_are_stored_by_the_hash_of_their_content() {
+    let fixture = Fixture::start(&[("/article", Page::html(ARTICLE))]);
+    let directory = temp_dir("archive-stored");
+
+    let archiver = Archiver::new(Box::new(UreqClient::new()));
+    let snapshot = archiver
+        .archive(&fixture.url("/article"), &directory)
+        .unwrap();
+
+    assert_eq!(snapshot.html, archive::hash(ARTICLE.as_bytes()));
+    assert_eq!(snapshot.text, archive::hash(b"Title\n\nSome text."));
+    assert_eq!(
+        snapshot.html_path(&directory),
+        directory.join(format!("{}.html", snapshot.html))
+    );
+    assert_eq!(
+        fs::read_to_string(snapshot.html_path(&directory)).unwrap(),
+        ARTICLE
+    );
+    assert_eq!(
+        fs::read_to_string(snapshot.text_path(&directory)).unwrap(),
+        "Title\n\nSome text."
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn the_same_content_is_stored_once() {
+    let fixture = Fixture::start(&[
+        ("/article", Page::html(ARTICLE)),
+        ("/copy", Page::html(ARTICLE)),
+        // Only the navigation differs, so only the text is shared
+        (
+            "/renavigated",
+            Page::html(&ARTICLE.replace("Home", "Start")),
+        ),
+    ]);
+    let directory = temp_dir("archive-deduplicated");
+    let archiver = Archiver::new(Box::new(UreqClient::new()));
+
+    let first = archiver
+        .archive(&fixture.url("/article"), &directory)
+        .unwrap();
+    let again = archiver
+        .archive(&fixture.url("/article"), &directory)
+        .unwrap();
+    let copy = archiver.archive(&fixture.url("/copy"), &directory).unwrap();
+
+    assert_eq!((&again.html, &again.text), (&first.html, &first.text));
+    assert_eq!((&copy.html, &copy.text), (&first.html, &first.text));
+    assert_eq!(entries(&directory).len(), 2);
+
+    let renavigated = archiver
+        .archive(&fixture.url("/renavigated"), &directory)
+        .unwrap();
+
+    assert_ne!(renavigated.html, first.html);
+    assert_eq!(renavigated.text, first.text);
+    assert_eq!(entries(&directory).len(), 3);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn failed_fetches_store_nothing() {
+    let fixture = Fixture::start(&[]);
+    let directory = temp_dir("archive-failed");
+
+    assert!(Archiver::new(Box::new(UreqClient::new()))
+        .archive(&fixture.url("/missing"), &directory)
+        .is_err());
+    assert!(entries(&directory).is_empty());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn only_the_article_is_kept_as_text() {
+    let text = archive::readable_text(
+        "<html><head><title>Page</title><style>p { color: red }</style></head><body>\
+         <nav><a href=\"/\">Home</a></nav>\
+         <article><h1>The   title</h1><p>First &amp; foremost,\n  a paragraph.</p>\
+         <script>alert(1)</script><p>Second<br>line</p></article>\
+         <footer>Copyright</footer></body></html>",
+    );
+
+    assert_eq!(
+        text,
+        "The title\n\nFirst & foremost, a paragraph.\n\nSecond\n\nline"
+    );
+}
+
+#[test]
+fn pages_without_an_article_keep_their_body() {
+    let text = archive::readable_text(
+        "<html><head><title>Left out</title></head>\
+         <body><header>Menu</header><div>Some text</div><aside>Ads</aside></body></html>",
+    );
+
+    assert_eq!(text, "Some text");
+}
@@ -0,0 +1,272 @@
+mod fixture;
+
+use fixture::{temp_dir, Fixture, Page};
+use link_keeper::backend::{AccessToken, Backend, BackendFactory, BackendRegistry, ConfigField};
+use link_keeper::check::{Checker, LinkStatus, Outcome};
+use link_keeper::http::{HttpError, UreqClient};
+use link_keeper::query::Query;
+use link_keeper::{Link, LinkKeeper, LinkKeeperBuilder};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn checker() -> Checker {
+    Checker::new(Box::new(UreqClient::new())).timeout(Duration::from_secs(5))
+}
+
+/// Writes every link added and removed to a file
+#[derive(Debug)]
+struct Recording {
+    path: PathBuf,
+}
+
+impl Recording {
+    fn record(&self, line: String) -> Result<(), failure::Error> {
+        let mut recorded = fs::read_to_string(&self.path).unwrap_or_default();
+        recorded.push_str(&line);
+        recorded.push('\n');
+
+        Ok(fs::write(&self.path, recorded)?)
+    }
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("Recording")
+    }
+}
+
+impl Backend for Recording {
+    fn add(&self, _link_keeper: &mut LinkKeeper) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn sign_in(&self, _access_token: &AccessToken) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn sign_out(&self, _access_token: &AccessToken) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn add_link(&self, link: &Link) -> Result<(), failure::Error> {
+        self.record(format!("add {}", link.link()))
+    }
+
+    fn remove_link(&self, link: &Link) -> Result<(), failure::Error> {
+        self.record(format!("remove {}", link.link()))
+    }
+
+    fn get_toml_config(&self) -> Result<String, toml::ser::Error> {
+        Ok(format!("path = {:?}\n", self.path.display().to_string()))
+    }
+}
+
+#[derive(Debug)]
+struct RecordingFactory;
+
+impl BackendFactory for RecordingFactory {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    fn config_schema(&self) -> Vec<ConfigField> {
+        vec![]
+    }
+
+    fn build(&self, config: &toml::Value) -> Result<Box<dyn Backend>, failure::Error> {
+        let path = config
+            .get("path")
+            .and_then(|path| path.as_str())
+            .ok_or_else(|| failure::format_err!("path is missing"))?;
+
+        Ok(Box::new(Recording {
+            path: PathBuf::from(path),
+        }))
+    }
+}
+
+#[test]
+fn missing_pages_are_not_found() {
+    let fixture = Fixture::start(&[("/gone", Page::new(410))]);
+    let checker = checker();
+
+    let status = checker.check(&fixture.url("/missing"));
+    assert_eq!(status.outcome(), Outcome::NotFound);
+    assert_eq!(status.status, Some(404));
+
+    assert_eq!(
+        checker.check(&fixture.url("/gone")).outcome(),
+        Outcome::NotFound
+    );
+}
+
+#[test]
+fn statuses_keep_the_order_of_the_urls() {
+    let fixture = Fixture::start(&[
+        ("/ok", Page::html("<title>Ok</title>")),
+        ("/gone", Page::new(410)),
+        ("/error", Page::new(500)),
+        (
+            "/slow",
+            Page::html("Slow").delay(Duration::from_millis(300)),
+        ),
+    ]);
+    // Nothing listens on a port that was just free
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let unreachable = format!("http://127.0.0.1:{}/", port);
+    let urls = [
+        fixture.url("/slow"),
+        fixture.url("/ok"),
+        fixture.url("/gone"),
+        fixture.url("/error"),
+        unreachable,
+    ];
+
+    let statuses = checker()
+        .parallelism(3)
+        .check_all(&urls.iter().map(|url| url.as_str()).collect::<Vec<&str>>());
+
+    assert_eq!(
+        statuses
+            .iter()
+            .map(|status| status.outcome())
+            .collect::<Vec<Outcome>>(),
+        vec![
+            Outcome::Ok,
+            Outcome::Ok,
+            Outcome::NotFound,
+            Outcome::Broken,
+            Outcome::Unreachable
+        ]
+    );
+    assert_eq!(statuses[3].status, Some(500));
+}
+
+#[test]
+fn tls_errors_are_told_apart() {
+    let status = LinkStatus {
+        status: None,
+        final_url: None,
+        redirects: vec![],
+        error: Some(HttpError::Tls("expired".to_owned())),
+        checked: chrono::Utc::now(),
+    };
+
+    assert_eq!(status.outcome(), Outcome::Tls);
+    assert_eq!(status.permanent_location(), None);
+}
+
+#[test]
+fn redirect_chains_are_followed() {
+    let fixture = Fixture::start(&[
+        ("/old", Page::redirect(301, "/older")),
+        ("/older", Page::redirect(308, "/new")),
+        ("/moved", Page::redirect(302, "/new")),
+        ("/new", Page::html("<title>New</title>")),
+    ]);
+    let checker = checker();
+
+    let status = checker.check(&fixture.url("/old"));
+    assert_eq!(status.outcome(), Outcome::Redirected);
+    assert_eq!(
+        status
+            .redirects
+            .iter()
+            .map(|redirect| redirect.status)
+            .collect::<Vec<u16>>(),
+        vec![301, 308]
+    );
+    assert_eq!(
+        status.permanent_location(),
+        Some(fixture.url("/new").as_str())
+    );
+
+    // Temporary redirects are followed, but don't move the link
+    let status = checker.check(&fixture.url("/moved"));
+    assert_eq!(status.outcome(), Outcome::Redirected);
+    assert_eq!(status.permanent_location(), None);
+}
+
+#[test]
+fn redirect_loops_are_given_up() {
+    let fixture = Fixture::start(&[
+        ("/ping", Page::redirect(301, "/pong")),
+        ("/pong", Page::redirect(301, "/ping")),
+    ]);
+
+    let status = checker().check(&fixture.url("/ping"));
+    assert_eq!(status.outcome(), Outcome::Unreachable);
+    assert_eq!(status.error, Some(HttpError::TooManyRedirects));
+}
+
+#[test]
+fn slow_pages_time_out() {
+    let fixture = Fixture::start(&[(
+        "/slow",
+        Page::html("<title>Slow</title>").delay(Duration::from_secs(3)),
+    )]);
+
+    let status = checker()
+        .timeout(Duration::from_millis(200))
+        .check(&fixture.url("/slow"));
+    assert_eq!(status.outcome(), Outcome::Unreachable);
+    assert_eq!(status.error, Some(HttpError::Timeout));
+}
+
+#[test]
+fn fixed_redirects_are_replaced_at_the_backends() {
+    let fixture = Fixture::start(&[
+        ("/old", Page::redirect(301, "/new")),
+        ("/new", Page::html("<title>New</title>")),
+        ("/kept", Page::html("<title>Kept</title>")),
+    ]);
+    let directory = temp_dir("check-fix-redirects");
+    let recorded = directory.join("recorded");
+
+    let mut registry = BackendRegistry::new();
+    registry.register(Box::new(RecordingFactory));
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml(&format!(
+            "[backends.recording]\npath = {:?}\n",
+            recorded.display().to_string()
+        ))
+        .store(directory.join("links.json"))
+        .backends(registry)
+        .build()
+        .unwrap();
+
+    keeper.add(&fixture.url("/old"), None, &[]).unwrap();
+    keeper.add(&fixture.url("/kept"), None, &[]).unwrap();
+    fs::remove_file(&recorded).unwrap();
+
+    let report = keeper.check(&checker(), &Query::new(), true).unwrap();
+
+    assert_eq!(report.results[0].rewritten_to, Some(fixture.url("/new")));
+    assert!(report.backend_errors.is_empty());
+    assert_eq!(
+        keeper
+            .get_links()
+            .unwrap()
+            .iter()
+            .map(|link| link.link().to_owned())
+            .collect::<Vec<String>>(),
+        vec![fixture.url("/new"), fixture.url("/kept")]
+    );
+    assert_eq!(
+        fs::read_to_string(&recorded).unwrap(),
+        format!(
+            "remove {}\nadd {}\n",
+            fixture.url("/old"),
+            fixture.url("/new")
+        )
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
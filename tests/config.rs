@@ -0,0 +1,155 @@
+use link_keeper::config::{self, parse_value, set_in, Config};
+use std::fs;
+use toml::Value;
+
+const HAND_WRITTEN: &str = r#"# Where my links live
+store_path = "/home/me/links"   # synced folder
+store_file_name = "links.md"
+
+[backends.git]
+# Private repository
+repository_path = "/home/me/repo"
+
+[suggestions.domains]
+"arxiv.org" = ["paper"]  # always
+"github.com" = ["code", "oss"]
+
+[some_future_table]
+answer = 42
+"#;
+
+#[test]
+fn setting_an_unchanged_value_leaves_the_file_as_it_is() {
+    let edited = set_in(HAND_WRITTEN, "store_file_name", &parse_value("links.md")).unwrap();
+
+    assert_eq!(edited, HAND_WRITTEN);
+}
+
+#[test]
+fn setting_a_value_keeps_comments_and_unknown_tables() {
+    let edited = set_in(HAND_WRITTEN, "store_path", &parse_value("/tmp/links")).unwrap();
+
+    assert_eq!(
+        edited,
+        HAND_WRITTEN.replace(
+            r#"store_path = "/home/me/links"   # synced folder"#,
+            r#"store_path = "/tmp/links"   # synced folder"#
+        )
+    );
+}
+
+#[test]
+fn setting_a_table_only_touches_the_changed_keys() {
+    let mut git = toml::value::Table::new();
+    git.insert(
+        "repository_path".to_owned(),
+        Value::String("/home/me/other".to_owned()),
+    );
+
+    let edited = set_in(HAND_WRITTEN, "backends.git", &Value::Table(git)).unwrap();
+
+    assert_eq!(
+        edited,
+        HAND_WRITTEN.replace("/home/me/repo", "/home/me/other")
+    );
+}
+
+#[test]
+fn quoted_keys_reach_into_existing_tables() {
+    let edited = set_in(
+        HAND_WRITTEN,
+        r#"suggestions.domains."arxiv.org""#,
+        &parse_value(r#"["paper", "science"]"#),
+    )
+    .unwrap();
+
+    assert_eq!(
+        edited,
+        HAND_WRITTEN.replace(
+            r#""arxiv.org" = ["paper"]  # always"#,
+            r#""arxiv.org" = ["paper", "science"]  # always"#
+        )
+    );
+}
+
+#[test]
+fn new_tables_are_added_next_to_their_siblings() {
+    let edited = set_in(
+        HAND_WRITTEN,
+        "backends.vault.vault_path",
+        &parse_value("/notes"),
+    )
+    .unwrap();
+
+    assert_eq!(
+        edited,
+        HAND_WRITTEN.replace(
+            "\n[suggestions.domains]",
+            "\n[backends.vault]\nvault_path = \"/notes\"\n\n[suggestions.domains]"
+        )
+    );
+}
+
+#[test]
+fn values_in_the_way_of_a_key_are_reported() {
+    assert!(set_in(HAND_WRITTEN, "store_path.nested", &parse_value("x")).is_err());
+}
+
+#[test]
+fn written_files_are_read_back_with_every_layer() {
+    let directory = std::env::temp_dir().join(format!("link-keeper-config-{}", std::process::id()));
+    let global_path = directory.join("link-keeper.toml");
+    let project_path = directory.join("project").join(".link-keeper.toml");
+
+    fs::create_dir_all(project_path.parent().unwrap()).unwrap();
+    fs::write(&global_path, HAND_WRITTEN).unwrap();
+    config::write_value(&project_path, "store_path", parse_value("data")).unwrap();
+
+    let vars = vec![(
+        "LINK_KEEPER_STORE_FILE_NAME".to_owned(),
+        "env.json".to_owned(),
+    )];
+    let mut config =
+        Config::load_from(global_path.clone(), Some(project_path.clone()), vars, &[]).unwrap();
+
+    let settings = config.settings().unwrap();
+    assert_eq!(settings.store_path, directory.join("project").join("data"));
+    assert_eq!(settings.store_file_name, "env.json");
+
+    config
+        .set("some_future_table.answer", parse_value("43"), false)
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&global_path).unwrap(),
+        HAND_WRITTEN.replace("answer = 42", "answer = 43")
+    );
+    assert_eq!(
+        config
+            .get("some_future_table.answer")
+            .map(|(value, _)| value),
+        Some(Value::Integer(43))
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn written_files_are_only_readable_by_the_user() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let directory =
+        std::env::temp_dir().join(format!("link-keeper-config-mode-{}", std::process::id()));
+    let path = directory.join("link-keeper.toml");
+
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(&path, "").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+    config::write_value(&path, "api.token", parse_value("secret")).unwrap();
+
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,200 @@
+mod fixture;
+
+use fixture::{link, temp_dir};
+use link_keeper::export::{self, Atom, Csv, Exporter, Opml, StaticPage};
+use link_keeper::query::Query;
+use link_keeper::{Link, LinkKeeper, LinkKeeperBuilder};
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+/// A keeper whose store holds the links
+fn keeper_with<'a>(directory: &Path, links: &[Link]) -> LinkKeeper<'a> {
+    let store = directory.join("links.json");
+    fs::write(
+        &store,
+        serde_json::to_string(&json!({ "version": 1, "links": links })).unwrap(),
+    )
+    .unwrap();
+
+    LinkKeeperBuilder::new()
+        .config_path(directory.join("link-keeper.toml"))
+        .store(store)
+        .build()
+        .unwrap()
+}
+
+fn links() -> Vec<Link> {
+    vec![
+        link(json!({
+            "link": "https://www.rust-lang.org",
+            "category": "code",
+            "title": "Rust, \"fast\"",
+            "tags": ["rust", "lang"],
+            "added": "2024-01-02T10:00:00Z",
+            "state": "read"
+        })),
+        link(json!({
+            "link": "https://example.com/a?b=1&c=2",
+            "category": "code/web",
+            "title": "A <b>bold</b> title",
+            "added": "2024-01-01T10:00:00Z"
+        })),
+        link(json!({
+            "link": "https://news.example.org",
+            "added": "2024-01-03T10:00:00Z"
+        })),
+    ]
+}
+
+#[test]
+fn exporters_are_found_by_name() {
+    for name in &["html", "csv", "opml", "atom", "page", "markdown"] {
+        assert_eq!(export::exporter(name).unwrap().name(), *name);
+    }
+
+    assert_eq!(export::exporter("CSV").unwrap().name(), "csv");
+    assert!(export::exporter("pdf").is_none());
+}
+
+#[test]
+fn csv_quotes_fields_that_need_it() {
+    let csv = Csv.export(&links()).unwrap();
+    let lines = csv.lines().collect::<Vec<&str>>();
+
+    assert_eq!(lines[0], "url,title,category,tags,added,state");
+    assert_eq!(
+        lines[1],
+        "https://www.rust-lang.org,\"Rust, \"\"fast\"\"\",code,\"rust,lang\",2024-01-02T10:00:00+00:00,read"
+    );
+    assert_eq!(
+        lines[3],
+        "https://news.example.org,,,,2024-01-03T10:00:00+00:00,unread"
+    );
+    assert_eq!(lines.len(), 4);
+}
+
+#[test]
+fn opml_groups_links_by_category() {
+    let opml = Opml.export(&links()).unwrap();
+
+    assert!(opml.contains(
+        "    <outline text=\"code\">\n      \
+         <outline text=\"Rust, &quot;fast&quot;\" type=\"link\" url=\"https://www.rust-lang.org\"/>\n    \
+         </outline>\n"
+    ));
+    assert!(opml.contains(
+        "      <outline text=\"A &lt;b&gt;bold&lt;/b&gt; title\" type=\"link\" \
+         url=\"https://example.com/a?b=1&amp;c=2\"/>\n"
+    ));
+    assert!(opml.contains(
+        "  <body>\n    \
+         <outline text=\"https://news.example.org\" type=\"link\" url=\"https://news.example.org\"/>\n"
+    ));
+}
+
+#[test]
+fn atom_has_the_newest_links_first() {
+    let feed = Atom.export(&links()).unwrap();
+
+    assert!(feed.contains("<updated>2024-01-03T10:00:00+00:00</updated>\n  <author>"));
+
+    let newest = feed.find("https://news.example.org").unwrap();
+    let middle = feed.find("https://www.rust-lang.org").unwrap();
+    let oldest = feed.find("https://example.com/a?b=1&amp;c=2").unwrap();
+    assert!(newest < middle && middle < oldest);
+
+    assert!(feed.contains("<title>A &lt;b&gt;bold&lt;/b&gt; title</title>"));
+    assert!(feed.contains(
+        "    <category term=\"code\"/>\n    \
+         <category term=\"rust\"/>\n    \
+         <category term=\"lang\"/>\n"
+    ));
+}
+
+#[test]
+fn atom_has_at_most_fifty_entries() {
+    let links = (0..60)
+        .map(|day| {
+            link(json!({
+                "link": format!("https://example.com/{}", day),
+                "added": format!("2024-01-{:02}T10:00:00Z", day % 28 + 1),
+            }))
+        })
+        .collect::<Vec<Link>>();
+
+    let feed = Atom.export(&links).unwrap();
+
+    assert_eq!(feed.matches("<entry>").count(), 50);
+    assert!(!feed.contains("2024-01-01T"));
+}
+
+#[test]
+fn static_pages_only_link_to_web_and_mail_addresses() {
+    let links = vec![
+        Link::new("javascript:alert('hi')", None).with_title(Some("Bookmarklet")),
+        Link::new(" JavaScript:alert(1)", None),
+        Link::new("data:text/html,<script>alert(1)</script>", None),
+        Link::new("HTTPS://example.com/it's", None).with_title(Some("It's")),
+        Link::new("mailto:someone@example.com", None),
+    ];
+
+    let page = StaticPage.export(&links).unwrap();
+
+    assert!(page.contains("<li data-tags=\"\">Bookmarklet<span"));
+    assert!(!page.contains("href=\"javascript"));
+    assert!(!page.contains("href=\" JavaScript"));
+    assert!(!page.contains("href=\"data"));
+    assert!(!page.contains("<script>alert"));
+    assert!(page.contains("<a href=\"HTTPS://example.com/it&#39;s\">It&#39;s</a>"));
+    assert!(page.contains("<a href=\"mailto:someone@example.com\">"));
+}
+
+#[test]
+fn static_pages_group_links_by_category() {
+    let page = StaticPage.export(&links()).unwrap();
+
+    let code = page.find("<h2>code</h2>").unwrap();
+    let web = page.find("<h2>code/web</h2>").unwrap();
+    let uncategorized = page.find("<h2>Uncategorized</h2>").unwrap();
+    assert!(uncategorized < code && code < web);
+
+    assert!(page.contains(
+        "<li data-tags=\"rust lang\"><a href=\"https://www.rust-lang.org\">Rust, &quot;fast&quot;</a>\
+         <span class=\"domain\">rust-lang.org</span></li>"
+    ));
+}
+
+#[test]
+fn exports_only_have_the_links_matching_the_query() {
+    let directory = temp_dir("export-query");
+    let keeper = keeper_with(&directory, &links());
+
+    let csv = keeper.export(&Csv, &Query::new().category("code")).unwrap();
+    assert_eq!(csv.lines().count(), 3);
+    assert!(!csv.contains("news.example.org"));
+
+    let csv = keeper.export(&Csv, &Query::new().tag("rust")).unwrap();
+    assert_eq!(csv.lines().count(), 2);
+    assert!(csv.contains("rust-lang.org"));
+
+    let csv = keeper
+        .export(&Csv, &Query::new().domain("example.org"))
+        .unwrap();
+    assert_eq!(csv.lines().count(), 2);
+    assert!(csv.contains("news.example.org"));
+
+    let feed = keeper
+        .export(&Atom, &Query::new().state(link_keeper::ReadState::Unread))
+        .unwrap();
+    assert_eq!(feed.matches("<entry>").count(), 2);
+    assert!(!feed.contains("rust-lang.org"));
+
+    let page = keeper
+        .export(&StaticPage, &Query::new().newest_first().limit(1))
+        .unwrap();
+    assert!(page.contains("news.example.org"));
+    assert!(!page.contains("example.com/a"));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,149 @@
+//! Helpers shared by the tests: empty directories, links from their stored
+//! JSON and a local HTTP server answering with fixed pages, for the tests
+//! fetching pages
+
+// Every test uses only some of it
+#![allow(dead_code)]
+
+use link_keeper::Link;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tiny_http::{Header, Response, Server};
+
+/// An empty directory for the test, removed first if an earlier run left it
+pub fn temp_dir(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("link-keeper-{}-{}", name, process::id()));
+
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+
+    directory
+}
+
+/// A link from the JSON it's stored as
+pub fn link(value: serde_json::Value) -> Link {
+    serde_json::from_value(value).unwrap()
+}
+
+/// What the server answers with at a path
+#[derive(Debug, Clone)]
+pub struct Page {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    delay: Option<Duration>,
+}
+
+impl Page {
+    pub fn new(status: u16) -> Self {
+        Page {
+            status,
+            headers: vec![],
+            body: vec![],
+            delay: None,
+        }
+    }
+
+    pub fn html(html: &str) -> Self {
+        Page::new(200)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .body(html.as_bytes())
+    }
+
+    /// A redirect to the path on the same server
+    pub fn redirect(status: u16, location: &str) -> Self {
+        Page::new(status).header("Location", location)
+    }
+
+    pub fn header(mut self, field: &str, value: &str) -> Self {
+        self.headers.push((field.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn body(mut self, body: &[u8]) -> Self {
+        self.body = body.to_vec();
+        self
+    }
+
+    /// Wait before answering
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+/// The running server, stopped when dropped. Paths without a page are
+/// answered with 404.
+pub struct Fixture {
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+    url: String,
+}
+
+impl Fixture {
+    pub fn start(pages: &[(&str, Page)]) -> Self {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let pages = pages
+            .iter()
+            .map(|(path, page)| ((*path).to_owned(), page.clone()))
+            .collect::<HashMap<String, Page>>();
+
+        let thread = {
+            let server = Arc::clone(&server);
+
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    let page = pages
+                        .get(request.url())
+                        .cloned()
+                        .unwrap_or_else(|| Page::new(404));
+
+                    // Slow pages don't hold up the others
+                    thread::spawn(move || {
+                        if let Some(delay) = page.delay {
+                            thread::sleep(delay);
+                        }
+
+                        let mut response =
+                            Response::from_data(page.body).with_status_code(page.status);
+
+                        for (field, value) in &page.headers {
+                            response.add_header(
+                                Header::from_bytes(field.as_bytes(), value.as_bytes()).unwrap(),
+                            );
+                        }
+
+                        let _ = request.respond(response);
+                    });
+                }
+            })
+        };
+
+        Fixture {
+            server,
+            thread: Some(thread),
+            url,
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        self.server.unblock();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use link_keeper::archive::readable_text;
+use link_keeper::bookmarks;
+use link_keeper::metadata;
+
+#[test]
+fn a_tag_left_open_at_the_end_is_text() {
+    let metadata = metadata::extract("<title>x</title><", "https://example.com");
+    assert_eq!(metadata.title.as_deref(), Some("x"));
+
+    assert_eq!(readable_text("<p>Hello</p><"), "Hello\n\n<");
+}
+
+#[test]
+fn a_tag_left_open_before_a_multibyte_character_is_text() {
+    assert_eq!(readable_text("<p>caf<é"), "caf <é");
+    assert_eq!(readable_text("<p>café</p><ü"), "café\n\n<ü");
+}
+
+#[test]
+fn truncated_bookmark_files_keep_the_complete_links() {
+    let links = bookmarks::parse(
+        "<DL><p><DT><A HREF=\"https://example.com\">Example</A>\n<DT><A HREF=\"https://exa",
+    );
+
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].link(), "https://example.com");
+}
+
+#[test]
+fn comments_end_at_their_closing_dashes() {
+    let links = bookmarks::parse(
+        "<!-- a > b <A HREF=\"https://example.org\">Hidden</A> -->\n\
+         <DL><p><DT><A HREF=\"https://example.com\">Example</A>\n</DL>",
+    );
+
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].link(), "https://example.com");
+    assert_eq!(links[0].title(), Some("Example"));
+
+    assert_eq!(readable_text("<p>Hello<!-- a > b --></p>"), "Hello");
+}
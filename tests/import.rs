@@ -0,0 +1,151 @@
+use chrono::{DateTime, Utc};
+use link_keeper::import::{self, BrowserJson, Importer, Pinboard, Pocket, Raindrop};
+use link_keeper::{Link, ReadState};
+
+fn added(link: &Link) -> Option<String> {
+    link.added().map(|added| added.to_rfc3339())
+}
+
+/// Whether the link entered its state during the import
+fn changed_during(link: &Link, started: DateTime<Utc>) -> bool {
+    link.state_changed(link.state())
+        .is_some_and(|changed| changed >= started && changed <= Utc::now())
+}
+
+#[test]
+fn importers_are_found_by_name() {
+    for name in &["netscape", "pocket", "pinboard", "raindrop", "browser-json"] {
+        assert_eq!(import::importer(name).unwrap().name(), *name);
+    }
+
+    assert!(import::importer("delicious").is_none());
+}
+
+#[test]
+fn pocket_exports_have_unread_and_archived_links() {
+    let started = Utc::now();
+    let links = Pocket.parse(include_str!("exports/pocket.html")).unwrap();
+
+    assert_eq!(links.len(), 3);
+
+    assert_eq!(links[0].link(), "https://example.com/unread");
+    assert_eq!(links[0].title(), Some("An unread article"));
+    assert_eq!(links[0].tags(), ["rust", "lang/go"]);
+    assert_eq!(
+        added(&links[0]).as_deref(),
+        Some("2023-11-14T22:13:20+00:00")
+    );
+    assert_eq!(links[0].state(), ReadState::Unread);
+    assert_eq!(links[0].state_changed(ReadState::Unread), None);
+
+    // A title that's only the URL is no title
+    assert_eq!(links[1].title(), None);
+    assert!(links[1].tags().is_empty());
+
+    assert_eq!(links[2].link(), "https://example.com/archived");
+    assert_eq!(links[2].title(), Some("Tom & Jerry"));
+    assert_eq!(links[2].state(), ReadState::Archived);
+    assert!(changed_during(&links[2], started));
+}
+
+#[test]
+fn pinboard_exports_mark_links_not_to_read_as_read() {
+    let started = Utc::now();
+    let links = Pinboard
+        .parse(include_str!("exports/pinboard.json"))
+        .unwrap();
+
+    assert_eq!(links.len(), 2);
+
+    assert_eq!(links[0].link(), "https://example.com/later");
+    assert_eq!(links[0].title(), Some("Read later"));
+    assert_eq!(links[0].tags(), ["rust", "lang/go"]);
+    assert_eq!(
+        added(&links[0]).as_deref(),
+        Some("2023-11-14T22:13:20+00:00")
+    );
+    assert_eq!(links[0].state(), ReadState::Unread);
+    assert_eq!(links[0].state_changed(ReadState::Read), None);
+
+    assert_eq!(links[1].title(), None);
+    assert_eq!(links[1].state(), ReadState::Read);
+    assert!(changed_during(&links[1], started));
+
+    assert!(Pinboard.parse("{\"not\": \"a list\"}").is_err());
+}
+
+#[test]
+fn raindrop_exports_are_read_as_csv() {
+    let started = Utc::now();
+    let links = Raindrop
+        .parse(include_str!("exports/raindrop.csv"))
+        .unwrap();
+
+    assert_eq!(links.len(), 2);
+
+    assert_eq!(links[0].link(), "https://example.com/a");
+    assert_eq!(links[0].title(), Some("A title, with a comma"));
+    assert_eq!(links[0].category(), Some("Reading/Rust"));
+    assert_eq!(links[0].tags(), ["rust", "lang/go"]);
+    assert_eq!(
+        added(&links[0]).as_deref(),
+        Some("2023-11-14T22:13:20+00:00")
+    );
+    assert_eq!(links[0].state(), ReadState::Unread);
+
+    assert_eq!(links[1].title(), Some("Quoted \"word\""));
+    assert_eq!(links[1].category(), None);
+    assert_eq!(links[1].state(), ReadState::Read);
+    assert!(changed_during(&links[1], started));
+}
+
+#[test]
+fn raindrop_exports_need_a_url_column() {
+    assert!(Raindrop.parse("").is_err());
+    assert!(Raindrop.parse("id,title\n1,Title\n").is_err());
+
+    let links = Raindrop
+        .parse("url,title\r\nhttps://example.com,Title\r\n")
+        .unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].state(), ReadState::Unread);
+}
+
+#[test]
+fn firefox_backups_keep_their_folders() {
+    let links = BrowserJson
+        .parse(include_str!("exports/firefox.json"))
+        .unwrap();
+
+    assert_eq!(links.len(), 2);
+
+    assert_eq!(links[0].link(), "https://doc.rust-lang.org/book/");
+    assert_eq!(links[0].title(), Some("The book"));
+    assert_eq!(links[0].category(), Some("menu/Rust"));
+    assert_eq!(links[0].tags(), ["rust", "docs"]);
+    assert_eq!(
+        added(&links[0]).as_deref(),
+        Some("2023-11-14T22:13:20+00:00")
+    );
+
+    assert_eq!(links[1].link(), "https://example.com/unfiled");
+    assert_eq!(links[1].title(), None);
+    assert_eq!(links[1].category(), None);
+}
+
+#[test]
+fn chrome_bookmarks_keep_their_folders() {
+    let links = BrowserJson
+        .parse(include_str!("exports/chrome.json"))
+        .unwrap();
+
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].link(), "https://example.com/chrome");
+    assert_eq!(links[0].title(), Some("Example"));
+    assert_eq!(links[0].category(), Some("Bookmarks bar/Work"));
+    assert_eq!(
+        added(&links[0]).as_deref(),
+        Some("2023-11-21T00:26:40+00:00")
+    );
+    assert_eq!(links[0].state(), ReadState::Unread);
+}
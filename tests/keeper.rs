@@ -0,0 +1,645 @@
+mod fixture;
+
+use fixture::temp_dir;
+use link_keeper::backend::{AccessToken, Backend, BackendFactory, BackendRegistry, ConfigField};
+use link_keeper::http::{HttpClient, HttpError, Request, Response};
+use link_keeper::import::Netscape;
+use link_keeper::metadata::Enricher;
+use link_keeper::query::Query;
+use link_keeper::{Link, LinkKeeper, LinkKeeperBuilder, ReadState};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+fn entries(directory: &Path) -> Vec<String> {
+    let mut entries = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect::<Vec<String>>();
+    entries.sort();
+
+    entries
+}
+
+/// The paths of the recording backends built so far, tests run at the same
+/// time so each counts its own
+static BUILT_BACKENDS: Mutex<Vec<PathBuf>> = Mutex::new(vec![]);
+
+fn times_built(path: &Path) -> usize {
+    BUILT_BACKENDS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|built| *built == path)
+        .count()
+}
+
+/// Writes the URL of every added link to a file, the size of every batch
+/// of links and the URL of every updated link
+#[derive(Debug)]
+struct Recording {
+    path: PathBuf,
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("Recording")
+    }
+}
+
+impl Backend for Recording {
+    fn add(&self, _link_keeper: &mut LinkKeeper) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn sign_in(&self, _access_token: &AccessToken) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn sign_out(&self, _access_token: &AccessToken) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn add_link(&self, link: &Link) -> Result<(), failure::Error> {
+        let mut recorded = fs::read_to_string(&self.path).unwrap_or_default();
+        recorded.push_str(&format!("{}\n", link.link()));
+
+        Ok(fs::write(&self.path, recorded)?)
+    }
+
+    fn add_links(&self, links: &[Link]) -> Result<(), failure::Error> {
+        let mut recorded = fs::read_to_string(&self.path).unwrap_or_default();
+        recorded.push_str(&format!("batch of {}\n", links.len()));
+
+        Ok(fs::write(&self.path, recorded)?)
+    }
+
+    fn update_link(&self, link: &Link) -> Result<(), failure::Error> {
+        let mut recorded = fs::read_to_string(&self.path).unwrap_or_default();
+        recorded.push_str(&format!("updated {}\n", link.link()));
+
+        Ok(fs::write(&self.path, recorded)?)
+    }
+
+    fn get_toml_config(&self) -> Result<String, toml::ser::Error> {
+        Ok(format!("path = {:?}\n", self.path.display().to_string()))
+    }
+}
+
+#[derive(Debug)]
+struct RecordingFactory;
+
+impl BackendFactory for RecordingFactory {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    fn config_schema(&self) -> Vec<ConfigField> {
+        vec![]
+    }
+
+    fn build(&self, config: &toml::Value) -> Result<Box<dyn Backend>, failure::Error> {
+        let path = config
+            .get("path")
+            .and_then(|path| path.as_str())
+            .ok_or_else(|| failure::format_err!("path is missing"))?;
+
+        BUILT_BACKENDS.lock().unwrap().push(PathBuf::from(path));
+
+        Ok(Box::new(Recording {
+            path: PathBuf::from(path),
+        }))
+    }
+}
+
+#[test]
+fn building_writes_nothing() {
+    let directory = temp_dir("builder-writes-nothing");
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_path(directory.join("config").join("link-keeper.toml"))
+        .store(directory.join("store").join("links.json"))
+        .build()
+        .unwrap();
+
+    assert_eq!(keeper.get_links().unwrap(), vec![]);
+    assert_eq!(entries(&directory), Vec::<String>::new());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn links_are_kept_in_the_given_store() {
+    let directory = temp_dir("builder-store");
+    let store = directory.join("store").join("links.json");
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_path(directory.join("link-keeper.toml"))
+        .store(&store)
+        .build()
+        .unwrap();
+
+    let errors = keeper
+        .add(
+            "https://example.com",
+            Some("examples"),
+            &["rust".to_owned()],
+        )
+        .unwrap();
+
+    assert!(errors.is_empty());
+    assert_eq!(entries(&directory), vec!["store"]);
+
+    let links = keeper.get_links().unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].link(), "https://example.com");
+    assert_eq!(links[0].category(), Some("examples"));
+    assert_eq!(links[0].tags(), ["rust"]);
+
+    let stored = fs::read_to_string(&store).unwrap();
+    assert!(stored.starts_with(r#"{"version":1,"links":["#));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn the_configuration_can_be_given_as_toml() {
+    let directory = temp_dir("builder-toml");
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml(&format!(
+            "store_path = {:?}\nstore_file_name = \"links.md\"\n",
+            directory.display().to_string()
+        ))
+        .build()
+        .unwrap();
+
+    keeper.add("https://example.com", None, &[]).unwrap();
+
+    assert_eq!(entries(&directory), vec!["links.md", "links.md.lock"]);
+    assert_eq!(keeper.get_links().unwrap().len(), 1);
+    assert!(keeper.config().global_path().is_none());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn invalid_configuration_is_an_error() {
+    assert!(LinkKeeperBuilder::new()
+        .config_toml("store_path = 3")
+        .build()
+        .is_err());
+    assert!(LinkKeeperBuilder::new()
+        .config_toml("store_path = ")
+        .build()
+        .is_err());
+}
+
+#[test]
+fn configured_backends_are_built_once_and_get_new_links() {
+    let directory = temp_dir("builder-backends");
+    let recorded = directory.join("recorded");
+
+    let mut registry = BackendRegistry::new();
+    registry.register(Box::new(RecordingFactory));
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml(&format!(
+            "[backends.recording]\npath = {:?}\n",
+            recorded.display().to_string()
+        ))
+        .store(directory.join("links.json"))
+        .backends(registry)
+        .build()
+        .unwrap();
+
+    assert_eq!(times_built(&recorded), 1);
+    assert_eq!(keeper.get_activated_backends().len(), 1);
+
+    keeper.add("https://example.com/a", None, &[]).unwrap();
+    keeper.add("https://example.com/b", None, &[]).unwrap();
+
+    assert_eq!(times_built(&recorded), 1);
+    assert_eq!(
+        fs::read_to_string(&recorded).unwrap(),
+        "https://example.com/a\nhttps://example.com/b\n"
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn backends_that_cant_be_loaded_are_left_out() {
+    let directory = temp_dir("builder-bad-backends");
+    let recorded = directory.join("recorded");
+
+    let mut registry = BackendRegistry::new();
+    registry.register(Box::new(RecordingFactory));
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml(&format!(
+            "[backends.unknown]\nkey = 1\n\
+             [backends.recording]\npath = {:?}\n",
+            recorded.display().to_string()
+        ))
+        .store(directory.join("links.json"))
+        .backends(registry)
+        .build()
+        .unwrap();
+
+    assert_eq!(keeper.get_activated_backends().len(), 1);
+
+    let mut registry = BackendRegistry::new();
+    registry.register(Box::new(RecordingFactory));
+
+    // The recording backend needs a path
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml("[backends.recording]\n")
+        .store(directory.join("links.json"))
+        .backends(registry)
+        .build()
+        .unwrap();
+
+    assert!(keeper.get_activated_backends().is_empty());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn batches_leave_out_duplicates_and_reach_backends_together() {
+    let directory = temp_dir("builder-batch");
+    let recorded = directory.join("recorded");
+
+    let mut registry = BackendRegistry::new();
+    registry.register(Box::new(RecordingFactory));
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml(&format!(
+            "[backends.recording]\npath = {:?}\n",
+            recorded.display().to_string()
+        ))
+        .store(directory.join("links.json"))
+        .backends(registry)
+        .build()
+        .unwrap();
+
+    keeper.add("https://example.com/a", None, &[]).unwrap();
+
+    let report = keeper
+        .add_links(vec![
+            Link::new("https://example.com/b", None),
+            Link::new("https://EXAMPLE.com/a/", None),
+            Link::new("https://example.com/c", None),
+            Link::new("https://example.com/b?utm_source=chat", None),
+        ])
+        .unwrap();
+
+    let urls = |links: &[Link]| {
+        links
+            .iter()
+            .map(|link| link.link().to_owned())
+            .collect::<Vec<String>>()
+    };
+
+    assert_eq!(
+        urls(&report.added),
+        vec!["https://example.com/b", "https://example.com/c"]
+    );
+    assert_eq!(
+        urls(&report.duplicates),
+        vec![
+            "https://EXAMPLE.com/a/",
+            "https://example.com/b?utm_source=chat"
+        ]
+    );
+    assert!(report.backend_errors.is_empty());
+    assert_eq!(keeper.get_links().unwrap().len(), 3);
+    assert_eq!(
+        fs::read_to_string(&recorded).unwrap(),
+        "https://example.com/a\nbatch of 2\n"
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn imported_links_reach_backends() {
+    let directory = temp_dir("builder-import");
+    let recorded = directory.join("recorded");
+    let bookmarks = directory.join("bookmarks.html");
+
+    let mut registry = BackendRegistry::new();
+    registry.register(Box::new(RecordingFactory));
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml(&format!(
+            "[backends.recording]\npath = {:?}\n",
+            recorded.display().to_string()
+        ))
+        .store(directory.join("links.json"))
+        .backends(registry)
+        .build()
+        .unwrap();
+
+    keeper.add("https://example.com/a", None, &[]).unwrap();
+    keeper.add("https://example.com/b", None, &[]).unwrap();
+    fs::write(
+        &bookmarks,
+        "<DL><p>\n\
+         <DT><A HREF=\"https://example.com/a\">Titled now</A>\n\
+         <DT><A HREF=\"https://example.com/b\"></A>\n\
+         <DT><A HREF=\"https://example.com/c\">New</A>\n\
+         </DL><p>\n",
+    )
+    .unwrap();
+
+    let report = keeper.import(&Netscape, &bookmarks, false).unwrap();
+
+    assert_eq!((report.added, report.skipped, report.merged), (1, 1, 1));
+    assert!(report.backend_errors.is_empty());
+    assert_eq!(
+        fs::read_to_string(&recorded).unwrap(),
+        "https://example.com/a\nhttps://example.com/b\nbatch of 1\nupdated https://example.com/a\n"
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn backends_that_only_append_dont_fail_removals() {
+    let directory = temp_dir("builder-append-only");
+    let recorded = directory.join("recorded");
+
+    let mut registry = BackendRegistry::new();
+    registry.register(Box::new(RecordingFactory));
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml(&format!(
+            "[backends.recording]\npath = {:?}\n",
+            recorded.display().to_string()
+        ))
+        .store(directory.join("links.json"))
+        .backends(registry)
+        .build()
+        .unwrap();
+
+    keeper.add("https://example.com/a", None, &[]).unwrap();
+    let id = keeper.get_links().unwrap()[0].id();
+
+    assert!(keeper.remove(&id).unwrap().is_empty());
+    assert!(keeper.get_links().unwrap().is_empty());
+    assert_eq!(
+        fs::read_to_string(&recorded).unwrap(),
+        "https://example.com/a\n"
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+/// What the browser does to the selected link: filter, edit, mark as read
+#[test]
+fn edited_links_are_stored_and_reach_backends() {
+    let directory = temp_dir("builder-edit");
+    let recorded = directory.join("recorded");
+
+    let mut registry = BackendRegistry::new();
+    registry.register(Box::new(RecordingFactory));
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml(&format!(
+            "[backends.recording]\npath = {:?}\n",
+            recorded.display().to_string()
+        ))
+        .store(directory.join("links.json"))
+        .backends(registry)
+        .build()
+        .unwrap();
+
+    keeper
+        .add(
+            "https://example.com/rust",
+            Some("code"),
+            &["lang/rust".to_owned()],
+        )
+        .unwrap();
+    keeper
+        .add(
+            "https://example.com/go",
+            Some("code/go"),
+            &["lang/go".to_owned()],
+        )
+        .unwrap();
+    keeper.add("https://example.org/news", None, &[]).unwrap();
+
+    let urls = |query: &Query| {
+        keeper
+            .query(query)
+            .unwrap()
+            .iter()
+            .map(|link| link.link().to_owned())
+            .collect::<Vec<String>>()
+    };
+
+    assert_eq!(urls(&Query::new().category("code")).len(), 2);
+    assert_eq!(
+        urls(&Query::new().category("code").text("GO")),
+        vec!["https://example.com/go"]
+    );
+    assert_eq!(
+        urls(&Query::new().tag("lang")),
+        vec!["https://example.com/rust", "https://example.com/go"]
+    );
+
+    let link = keeper.query(&Query::new().text("news")).unwrap().remove(0);
+    let id = link.id();
+    let edited = link
+        .with_title(Some("News"))
+        .with_category(Some("reading"))
+        .with_tags(vec!["daily".to_owned()]);
+
+    assert!(keeper.update(edited.clone()).unwrap().is_empty());
+    assert_eq!(keeper.get_link(&id).unwrap(), edited);
+    assert_eq!(urls(&Query::new().text("news").tag("daily")).len(), 1);
+
+    assert!(keeper.set_state(&id, ReadState::Read).unwrap().is_empty());
+    assert_eq!(keeper.get_link(&id).unwrap().state(), ReadState::Read);
+    assert_eq!(
+        urls(&Query::new().state(ReadState::Unread)),
+        vec!["https://example.com/rust", "https://example.com/go"]
+    );
+
+    let recorded = fs::read_to_string(&recorded).unwrap();
+    assert!(
+        recorded.ends_with("updated https://example.org/news\nupdated https://example.org/news\n")
+    );
+
+    let missing = Link::new("https://example.net", None);
+    assert!(keeper.update(missing).is_err());
+    assert_eq!(keeper.get_links().unwrap().len(), 3);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn links_added_again_are_merged_into_the_stored_ones() {
+    let directory = temp_dir("builder-add-again");
+    let recorded = directory.join("recorded");
+
+    let mut registry = BackendRegistry::new();
+    registry.register(Box::new(RecordingFactory));
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml(&format!(
+            "[backends.recording]\npath = {:?}\n",
+            recorded.display().to_string()
+        ))
+        .store(directory.join("links.json"))
+        .backends(registry)
+        .build()
+        .unwrap();
+
+    keeper
+        .add("https://example.com/a", None, &["rust".to_owned()])
+        .unwrap();
+    keeper
+        .add(
+            "https://EXAMPLE.com/a/",
+            Some("reading"),
+            &["docs".to_owned()],
+        )
+        .unwrap();
+
+    let links = keeper.get_links().unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].link(), "https://example.com/a");
+    assert_eq!(links[0].category(), Some("reading"));
+    assert_eq!(links[0].tags(), ["rust", "docs"]);
+    assert_eq!(keeper.get_link(&links[0].id()).unwrap(), links[0]);
+    assert_eq!(
+        fs::read_to_string(&recorded).unwrap(),
+        "https://example.com/a\nupdated https://example.com/a\n"
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+/// Answers every request with an empty page and remembers what was asked
+#[derive(Debug, Default)]
+struct Requested {
+    urls: Arc<Mutex<Vec<String>>>,
+}
+
+impl HttpClient for Requested {
+    fn send(&self, request: &Request) -> Result<Response, HttpError> {
+        self.urls.lock().unwrap().push(request.url.to_owned());
+
+        Ok(Response {
+            status: 200,
+            url: request.url.to_owned(),
+            location: None,
+            content_type: Some("text/html".to_owned()),
+            body: vec![],
+            truncated: false,
+        })
+    }
+}
+
+#[test]
+fn duplicates_are_left_out_before_fetching() {
+    let directory = temp_dir("builder-add-urls");
+    let requested = Requested::default();
+    let urls = Arc::clone(&requested.urls);
+
+    let mut keeper = LinkKeeperBuilder::new()
+        .config_toml("")
+        .store(directory.join("links.json"))
+        .build()
+        .unwrap();
+    keeper.add("https://example.com/a", None, &[]).unwrap();
+    keeper.set_enricher(Enricher::new(Box::new(requested)));
+
+    let report = keeper
+        .add_urls(
+            &[
+                "https://example.com/b".to_owned(),
+                "https://example.com/a/".to_owned(),
+                "https://EXAMPLE.com/b".to_owned(),
+            ],
+            None,
+            &[],
+        )
+        .unwrap();
+
+    assert_eq!(report.added.len(), 1);
+    assert_eq!(report.duplicates.len(), 2);
+    assert_eq!(*urls.lock().unwrap(), vec!["https://example.com/b"]);
+    assert_eq!(keeper.get_links().unwrap().len(), 2);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn old_stores_are_only_migrated_when_asked() {
+    let directory = temp_dir("builder-migrate");
+    let store = directory.join("links.json");
+    fs::write(&store, r#"[{"link":"https://example.com"}]"#).unwrap();
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_path(directory.join("link-keeper.toml"))
+        .store(&store)
+        .build()
+        .unwrap();
+
+    assert!(keeper.get_links().is_err());
+    assert_eq!(entries(&directory), vec!["links.json"]);
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_path(directory.join("link-keeper.toml"))
+        .store(&store)
+        .migrate(true)
+        .build()
+        .unwrap();
+
+    assert_eq!(keeper.migrations().len(), 1);
+    assert_eq!(keeper.get_links().unwrap().len(), 1);
+    assert_eq!(entries(&directory), vec!["links.json", "links.json.v0.bak"]);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn other_keepers_wait_for_the_locked_store() {
+    let directory = temp_dir("lock");
+    let config = format!("store_path = {:?}\n", directory.display().to_string());
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml(&config)
+        .build()
+        .unwrap();
+    let lock = keeper.lock_store().unwrap();
+
+    // The lock is held again by the methods changing the store
+    keeper.add("https://example.com/first", None, &[]).unwrap();
+
+    let (added, was_added) = mpsc::channel();
+    let other = thread::spawn(move || {
+        let keeper = LinkKeeperBuilder::new()
+            .config_toml(&config)
+            .build()
+            .unwrap();
+        keeper.add("https://example.com/second", None, &[]).unwrap();
+        added.send(()).unwrap();
+    });
+
+    assert!(was_added.recv_timeout(Duration::from_millis(200)).is_err());
+    drop(lock);
+    was_added.recv_timeout(Duration::from_secs(5)).unwrap();
+    other.join().unwrap();
+
+    assert_eq!(keeper.get_links().unwrap().len(), 2);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
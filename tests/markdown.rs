@@ -0,0 +1,145 @@
+mod fixture;
+
+use fixture::{link, temp_dir};
+use link_keeper::annotation::Annotation;
+use link_keeper::markdown;
+use link_keeper::{Link, LinkKeeperBuilder, ReadState};
+use serde_json::json;
+use std::fs;
+
+#[test]
+fn states_timestamps_and_annotations_round_trip() {
+    let links = vec![
+        link(json!({
+            "link": "https://example.com/read",
+            "category": "articles",
+            "title": "Read [it]",
+            "tags": ["lang/rust"],
+            "added": "2024-01-01T10:00:00Z",
+            "state": "read",
+            "state_changes": {
+                "reading": "2024-01-02T10:00:00Z",
+                "read": "2024-01-03T10:00:00Z"
+            },
+            "annotations": [
+                {
+                    "kind": "note",
+                    "text": "First line\nsecond line",
+                    "created": "2024-01-02T11:00:00Z"
+                },
+                {
+                    "kind": "highlight",
+                    "text": "A -- quoted --> passage",
+                    "created": "2024-01-02T12:00:00Z"
+                }
+            ]
+        })),
+        link(json!({
+            "link": "https://example.com/archived",
+            "state": "archived",
+            "state_changes": { "archived": "2024-02-01T10:00:00Z" }
+        })),
+        Link::new("https://example.com/unread", None),
+    ];
+
+    let written = markdown::write(&links).unwrap();
+
+    assert!(written.contains(
+        "- [Read \\[it\\]](<https://example.com/read>) in articles #lang/rust (read) <!-- link-keeper "
+    ));
+    assert!(written.contains("\n  - Note (2024-01-02): First line\n    second line\n"));
+    assert!(written.contains("\n  > A -- quoted --> passage\n  > — highlighted 2024-01-02\n"));
+    assert_eq!(markdown::parse(&written).unwrap(), links);
+}
+
+#[test]
+fn line_breaks_and_comments_in_titles_dont_split_items() {
+    let links = vec![
+        Link::new("https://example.com/a", Some("one\ntwo"))
+            .with_title(Some("A title\r\n- <https://example.com/b>\nover lines")),
+        Link::new("https://example.com/c", None)
+            .with_title(Some("Looks like <!-- link-keeper {\"link\":\"x\"} -->")),
+        Link::new("https://example.com/d", None).with_title(Some("<b>-->")),
+    ];
+
+    let written = markdown::write(&links).unwrap();
+
+    assert!(written.contains(
+        "- [A title - <https://example.com/b> over lines](<https://example.com/a>) in one two <!--"
+    ));
+    assert_eq!(
+        written
+            .lines()
+            .filter(|line| line.starts_with("- "))
+            .count(),
+        3
+    );
+    assert_eq!(markdown::parse(&written).unwrap(), links);
+}
+
+#[test]
+fn items_added_by_hand_are_read() {
+    let links = markdown::parse(
+        "# Links\n\n\
+         - [Example](<https://example.com>) #rust #lang/go\n\
+         * <https://example.org> in somewhere\n\
+         - not a link\n\
+         \x20 - https://example.net/nested\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        links,
+        vec![
+            Link::new("https://example.com", None)
+                .with_title(Some("Example"))
+                .with_tags(vec!["rust".to_owned(), "lang/go".to_owned()]),
+            Link::new("https://example.org", None),
+        ]
+    );
+}
+
+#[test]
+fn the_markdown_store_keeps_states_and_annotations() {
+    let directory = temp_dir("markdown-store");
+    let store = directory.join("links.md");
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_path(directory.join("link-keeper.toml"))
+        .store(&store)
+        .build()
+        .unwrap();
+
+    keeper
+        .add("https://example.com", Some("examples"), &[])
+        .unwrap();
+    let id = keeper.get_links().unwrap()[0].id();
+
+    keeper.set_state(&id, ReadState::Read).unwrap();
+    keeper
+        .annotate(&id, Annotation::note("Worth\nreading"))
+        .unwrap();
+    keeper
+        .annotate(&id, Annotation::highlight("The quote"))
+        .unwrap();
+
+    let stored = fs::read_to_string(&store).unwrap();
+    assert!(stored.contains("- <https://example.com> in examples (read) <!-- link-keeper "));
+    assert!(stored.contains("  - Note ("));
+    assert!(stored.contains("  > The quote\n"));
+
+    let reopened = LinkKeeperBuilder::new()
+        .config_path(directory.join("link-keeper.toml"))
+        .store(&store)
+        .build()
+        .unwrap();
+    let links = reopened.get_links().unwrap();
+
+    assert_eq!(links, keeper.get_links().unwrap());
+    assert_eq!(links[0].state(), ReadState::Read);
+    assert!(links[0].state_changed(ReadState::Read).is_some());
+    assert_eq!(links[0].annotations().len(), 2);
+    assert_eq!(links[0].annotations()[0].text, "Worth\nreading");
+
+    fs::remove_dir_all(&directory).unwrap();
+}
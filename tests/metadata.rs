@@ -0,0 +1,133 @@
+mod fixture;
+
+use fixture::{Fixture, Page};
+use link_keeper::http::UreqClient;
+use link_keeper::metadata::{self, Enricher};
+use link_keeper::Link;
+
+fn enricher() -> Enricher {
+    Enricher::new(Box::new(UreqClient::new()))
+}
+
+#[test]
+fn titles_and_descriptions_are_fetched() {
+    let fixture = Fixture::start(&[(
+        "/article",
+        Page::html(
+            "<html><head><title>An article</title>\
+             <meta name=\"description\" content=\"What it&#39;s about\">\
+             <link rel=\"canonical\" href=\"/articles/1\">\
+             </head><body>Text</body></html>",
+        ),
+    )]);
+
+    let mut link = Link::new(&fixture.url("/article"), None);
+    enricher().enrich(&mut link).unwrap();
+
+    assert_eq!(link.title(), Some("An article"));
+
+    let metadata = link.metadata().unwrap();
+    assert_eq!(metadata.description.as_deref(), Some("What it's about"));
+    assert_eq!(metadata.canonical_url, Some(fixture.url("/articles/1")));
+    assert!(metadata.fetched.is_some());
+}
+
+#[test]
+fn pages_are_decoded_with_their_charset() {
+    let fixture = Fixture::start(&[
+        (
+            "/header",
+            Page::new(200)
+                .header("Content-Type", "text/html; charset=ISO-8859-1")
+                .body(b"<title>Caf\xe9</title>"),
+        ),
+        (
+            "/meta",
+            Page::new(200)
+                .header("Content-Type", "text/html")
+                .body(b"<head><meta charset=\"windows-1252\"><title>Na\xefve</title></head>"),
+        ),
+        ("/utf-8", Page::html("<title>Smörgås</title>")),
+    ]);
+
+    let enricher = enricher();
+    let title = |path: &str| enricher.fetch(&fixture.url(path)).unwrap().title;
+
+    assert_eq!(title("/header").as_deref(), Some("Café"));
+    assert_eq!(title("/meta").as_deref(), Some("Naïve"));
+    assert_eq!(title("/utf-8").as_deref(), Some("Smörgås"));
+}
+
+#[test]
+fn other_content_types_have_no_metadata() {
+    let fixture = Fixture::start(&[(
+        "/paper.pdf",
+        Page::new(200)
+            .header("Content-Type", "application/pdf")
+            .body(b"%PDF-1.4 <title>Not a page</title>"),
+    )]);
+
+    let mut link = Link::new(&fixture.url("/paper.pdf"), None);
+    enricher().enrich(&mut link).unwrap();
+
+    assert_eq!(link.title(), None);
+
+    let metadata = link.metadata().unwrap();
+    assert_eq!(metadata.title, None);
+    assert!(metadata.fetched.is_some());
+}
+
+#[test]
+fn failed_fetches_leave_the_link_alone() {
+    let fixture = Fixture::start(&[]);
+
+    let mut link = Link::new(&fixture.url("/gone"), None);
+    let err = enricher().enrich(&mut link).unwrap_err();
+
+    assert!(err.to_string().contains("404"));
+    assert!(link.metadata().is_none());
+}
+
+#[test]
+fn open_graph_fills_in_missing_metadata() {
+    let extracted = metadata::extract(
+        "<html><head>\
+         <meta property=\"og:title\" content=\" Shared title \">\
+         <meta property=\"og:description\" content=\"Shared\">\
+         <meta name=\"description\" content=\"Plain\">\
+         <meta property=\"og:image\" content=\"images/cover.png\">\
+         <meta property=\"og:url\" content=\"https://example.com/shared\">\
+         <link rel=\"shortcut icon\" href=\"/icon.png\">\
+         </head><body><title>Not the title</title></body></html>",
+        "https://example.com/posts/1",
+    );
+
+    assert_eq!(extracted.title.as_deref(), Some("Shared title"));
+    assert_eq!(extracted.description.as_deref(), Some("Shared"));
+    assert_eq!(
+        extracted.canonical_url.as_deref(),
+        Some("https://example.com/shared")
+    );
+    assert_eq!(
+        extracted.favicon.as_deref(),
+        Some("https://example.com/icon.png")
+    );
+    assert_eq!(
+        extracted.image.as_deref(),
+        Some("https://example.com/posts/images/cover.png")
+    );
+}
+
+#[test]
+fn pages_without_metadata_have_the_default_favicon() {
+    let extracted = metadata::extract("<p>Only text</p>", "https://example.com/a/b");
+
+    assert_eq!(extracted.title, None);
+    assert_eq!(extracted.description, None);
+    assert_eq!(extracted.canonical_url, None);
+    assert_eq!(
+        extracted.favicon.as_deref(),
+        Some("https://example.com/favicon.ico")
+    );
+    assert!(extracted.fetched.is_some());
+}
@@ -0,0 +1,173 @@
+mod fixture;
+
+use fixture::temp_dir;
+use link_keeper::config::Config;
+use link_keeper::markdown;
+use link_keeper::migrate::{self, Kind, CONFIG_VERSION, STORE_VERSION};
+use link_keeper::Link;
+use std::fs;
+
+const OLD_CONFIG: &str = "\
+# Where this file is
+config_path = \"/old\"
+config_file_name = \"link-keeper.toml\"
+# Where the links are kept
+store_file_name = \"links.json\"
+
+[backends.git]
+repo = \"~/links\"
+";
+
+#[test]
+fn old_configs_are_upgraded_and_kept_as_a_backup() {
+    let directory = temp_dir("migrate-config");
+    let path = directory.join("link-keeper.toml");
+    fs::write(&path, OLD_CONFIG).unwrap();
+
+    let checked = migrate::migrate_config(&path, true).unwrap().unwrap();
+    assert_eq!(checked.kind, Kind::Config);
+    assert_eq!((checked.from, checked.to), (0, CONFIG_VERSION));
+    assert_eq!(checked.changes.len(), 1);
+    assert_eq!(checked.backup, None);
+    assert_eq!(fs::read_to_string(&path).unwrap(), OLD_CONFIG);
+
+    let migration = migrate::migrate_config(&path, false).unwrap().unwrap();
+    let backup = directory.join("link-keeper.toml.v0.bak");
+    assert_eq!(migration.backup.as_ref(), Some(&backup));
+    assert_eq!(fs::read_to_string(&backup).unwrap(), OLD_CONFIG);
+
+    let migrated = fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        migrated,
+        "version = 1\n\
+         # Where this file is\n\
+         # Where the links are kept\n\
+         store_file_name = \"links.json\"\n\
+         \n\
+         [backends.git]\n\
+         repo = \"~/links\"\n"
+    );
+
+    // Nothing is left to do
+    assert_eq!(migrate::migrate_config(&path, false).unwrap(), None);
+    assert_eq!(fs::read_to_string(&path).unwrap(), migrated);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn newer_and_missing_files_arent_migrated() {
+    let directory = temp_dir("migrate-newer");
+    let path = directory.join("link-keeper.toml");
+
+    assert_eq!(migrate::migrate_config(&path, false).unwrap(), None);
+
+    fs::write(&path, "").unwrap();
+    assert_eq!(migrate::migrate_config(&path, false).unwrap(), None);
+
+    fs::write(&path, format!("version = {}\n", CONFIG_VERSION + 1)).unwrap();
+    assert!(migrate::migrate_config(&path, false).is_err());
+
+    let store = directory.join("links.json");
+    fs::write(
+        &store,
+        format!("{{\"version\":{},\"links\":[]}}", STORE_VERSION + 1),
+    )
+    .unwrap();
+    assert!(migrate::migrate_store(&store, false, false).is_err());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn bare_lists_of_links_are_upgraded() {
+    let directory = temp_dir("migrate-store");
+    let store = directory.join("links.json");
+    let old = "[{\"link\":\"https://example.com\",\"category\":null}]";
+    fs::write(&store, old).unwrap();
+
+    let migration = migrate::migrate_store(&store, false, false)
+        .unwrap()
+        .unwrap();
+    assert_eq!(migration.kind, Kind::Store);
+    assert_eq!((migration.from, migration.to), (0, STORE_VERSION));
+    assert_eq!(
+        fs::read_to_string(directory.join("links.json.v0.bak")).unwrap(),
+        old
+    );
+
+    let migrated =
+        serde_json::from_str::<serde_json::Value>(&fs::read_to_string(&store).unwrap()).unwrap();
+    assert_eq!(migrated["version"], STORE_VERSION);
+    assert_eq!(migrated["links"][0]["link"], "https://example.com");
+
+    assert_eq!(migrate::migrate_store(&store, false, false).unwrap(), None);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn markdown_stores_are_written_again() {
+    let directory = temp_dir("migrate-markdown");
+    let store = directory.join("links.md");
+    let old = "- [Example](<https://example.com>) #rust\n";
+    fs::write(&store, old).unwrap();
+
+    let migration = migrate::migrate_store(&store, true, false)
+        .unwrap()
+        .unwrap();
+    assert_eq!(migration.from, 0);
+    assert_eq!(
+        fs::read_to_string(directory.join("links.md.v0.bak")).unwrap(),
+        old
+    );
+
+    let migrated = fs::read_to_string(&store).unwrap();
+    assert_eq!(markdown::version(&migrated), STORE_VERSION);
+    assert_eq!(
+        markdown::parse(&migrated).unwrap(),
+        vec![Link::new("https://example.com", None)
+            .with_title(Some("Example"))
+            .with_tags(vec!["rust".to_owned()])]
+    );
+
+    assert_eq!(migrate::migrate_store(&store, true, false).unwrap(), None);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn configs_are_read_again_after_they_are_migrated() {
+    let directory = temp_dir("migrate-all");
+    let global = directory.join("link-keeper.toml");
+    let store = directory.join("links.json");
+    fs::write(&global, OLD_CONFIG).unwrap();
+    fs::write(&store, "[]").unwrap();
+
+    let mut config =
+        Config::load_from(global.clone(), None, Vec::<(String, String)>::new(), &[]).unwrap();
+
+    let checked = migrate::migrate(&mut config, &store, true).unwrap();
+    assert_eq!(checked.len(), 2);
+    assert!(!directory.join("link-keeper.toml.v0.bak").exists());
+    assert!(!directory.join("links.json.v0.bak").exists());
+
+    let migrations = migrate::migrate(&mut config, &store, false).unwrap();
+    assert_eq!(
+        migrations
+            .iter()
+            .map(|migration| migration.kind)
+            .collect::<Vec<Kind>>(),
+        vec![Kind::Config, Kind::Store]
+    );
+    assert!(config.get("config_path").is_none());
+    assert!(config.get("backends.git.repo").is_some());
+    assert!(directory.join("link-keeper.toml.v0.bak").exists());
+    assert!(directory.join("links.json.v0.bak").exists());
+
+    assert!(migrate::migrate(&mut config, &store, false)
+        .unwrap()
+        .is_empty());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,59 @@
+use link_keeper::normalize::extract_urls;
+
+#[test]
+fn urls_are_picked_out_of_text() {
+    let text = "Check these out: https://rust-lang.org, and http://example.com/a?b=c#d!\n\
+                Also <https://docs.rs/serde> and \"https://crates.io\".";
+
+    assert_eq!(
+        extract_urls(text),
+        vec![
+            "https://rust-lang.org",
+            "http://example.com/a?b=c#d",
+            "https://docs.rs/serde",
+            "https://crates.io",
+        ]
+    );
+}
+
+#[test]
+fn brackets_are_kept_only_when_opened_in_the_url() {
+    let text = "(see https://en.wikipedia.org/wiki/Rust_(programming_language)). \
+                [link](https://github.com/drager/link-keeper)";
+
+    assert_eq!(
+        extract_urls(text),
+        vec![
+            "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+            "https://github.com/drager/link-keeper",
+        ]
+    );
+}
+
+#[test]
+fn one_url_per_line() {
+    assert_eq!(
+        extract_urls("https://a.example\r\nhttps://b.example/\n\nhttps://c.example\n"),
+        vec![
+            "https://a.example",
+            "https://b.example/",
+            "https://c.example"
+        ]
+    );
+}
+
+#[test]
+fn schemes_without_a_host_are_not_urls() {
+    assert_eq!(
+        extract_urls("http:// https:///path httpbin"),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn schemes_are_matched_in_any_case() {
+    assert_eq!(
+        extract_urls("HTTPS://Example.com/Path and Http://example.org, not HTTP:/x"),
+        vec!["https://Example.com/Path", "http://example.org"]
+    );
+}
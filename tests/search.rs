@@ -0,0 +1,222 @@
+mod fixture;
+
+use fixture::{link, temp_dir};
+use link_keeper::query::Query;
+use link_keeper::search::{Index, Search};
+use link_keeper::{Link, LinkKeeperBuilder};
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+fn links() -> Vec<Link> {
+    vec![
+        link(json!({
+            "link": "https://blog.example.com/ownership",
+            "title": "Understanding ownership and borrowing",
+            "category": "code/rust",
+            "tags": ["lang/rust"],
+            "state": "read"
+        })),
+        link(json!({
+            "link": "https://github.com/rust-lang/rust",
+            "title": "The Rust compiler",
+            "tags": ["lang/rust", "tools"],
+            "annotations": [{
+                "kind": "note",
+                "text": "memory safety without borrowing a garbage collector",
+                "created": "2024-01-01T10:00:00Z"
+            }]
+        })),
+        link(json!({
+            "link": "https://example.org/gc",
+            "title": "Garbage collection",
+            "category": "code/go",
+            "tags": ["lang/go"],
+            "annotations": [{
+                "kind": "highlight",
+                "text": "safety memory",
+                "created": "2024-01-01T10:00:00Z"
+            }]
+        })),
+    ]
+}
+
+fn indexed(links: &[Link], archive: &Path) -> Index {
+    let mut index = Index::default();
+    index.update(links, archive);
+
+    index
+}
+
+fn urls(search: &str, links: &[Link]) -> Vec<String> {
+    Search::parse(search, Query::new())
+        .unwrap()
+        .run(&indexed(links, Path::new("archive")), links)
+        .iter()
+        .map(|result| result.link.link().to_owned())
+        .collect()
+}
+
+#[test]
+fn matches_in_titles_rank_first() {
+    let links = links();
+
+    assert_eq!(
+        urls("borrowing", &links),
+        vec![
+            "https://blog.example.com/ownership",
+            "https://github.com/rust-lang/rust"
+        ]
+    );
+    assert_eq!(
+        urls("garbage", &links),
+        vec![
+            "https://example.org/gc",
+            "https://github.com/rust-lang/rust"
+        ]
+    );
+}
+
+#[test]
+fn every_word_has_to_match() {
+    let links = links();
+
+    assert_eq!(
+        urls("Rust COMPILER", &links),
+        vec!["https://github.com/rust-lang/rust"]
+    );
+    assert!(urls("rust python", &links).is_empty());
+    assert!(urls("nothing", &links).is_empty());
+}
+
+#[test]
+fn scores_are_ordered_best_first() {
+    let links = links();
+    let results = Search::parse("memory safety", Query::new())
+        .unwrap()
+        .run(&indexed(&links, Path::new("archive")), &links);
+
+    assert_eq!(results.len(), 2);
+    assert!(results[0].score >= results[1].score);
+    assert!(results.iter().all(|result| result.score > 0.0));
+}
+
+#[test]
+fn quoted_phrases_match_words_in_order() {
+    let links = links();
+
+    assert_eq!(
+        urls("\"memory safety\"", &links),
+        vec!["https://github.com/rust-lang/rust"]
+    );
+    assert_eq!(
+        urls("\"safety memory\"", &links),
+        vec!["https://example.org/gc"]
+    );
+    assert!(urls("\"ownership borrowing\"", &links).is_empty());
+}
+
+#[test]
+fn filters_select_the_links_searched() {
+    let links = links();
+
+    assert_eq!(
+        urls("memory tag:lang/go", &links),
+        vec!["https://example.org/gc"]
+    );
+    assert_eq!(
+        urls("borrowing tag:lang", &links),
+        vec![
+            "https://blog.example.com/ownership",
+            "https://github.com/rust-lang/rust"
+        ]
+    );
+    assert_eq!(
+        urls("borrowing category:code", &links),
+        vec!["https://blog.example.com/ownership"]
+    );
+    assert_eq!(
+        urls("borrowing domain:github.com", &links),
+        vec!["https://github.com/rust-lang/rust"]
+    );
+    assert_eq!(
+        urls("borrowing state:unread", &links),
+        vec!["https://github.com/rust-lang/rust"]
+    );
+    assert!(Search::parse("state:someday", Query::new()).is_err());
+}
+
+#[test]
+fn only_changed_links_are_indexed_again() {
+    let directory = temp_dir("search-index");
+    let archive = directory.join("archive");
+    fs::create_dir_all(&archive).unwrap();
+    fs::write(archive.join("text.txt"), "an archived page about lifetimes").unwrap();
+
+    let archived = link(json!({
+        "link": "https://example.com/archived",
+        "snapshot": {
+            "html": "html",
+            "text": "text",
+            "archived": "2024-01-01T10:00:00Z"
+        }
+    }));
+    let mut links = links();
+    links.push(archived);
+
+    let mut index = Index::default();
+    assert!(index.update(&links, &archive));
+    assert!(!index.update(&links, &archive));
+
+    let search = Search::parse("lifetimes", Query::new()).unwrap();
+    assert_eq!(search.run(&index, &links).len(), 1);
+
+    fs::write(archive.join("text.txt"), "an archived page about traits").unwrap();
+    assert!(index.update(&links, &archive));
+    assert!(search.run(&index, &links).is_empty());
+
+    links[0] = links[0].clone().with_title(Some("Lifetimes explained"));
+    assert!(index.update(&links, &archive));
+    assert_eq!(
+        search.run(&index, &links)[0].link.link(),
+        "https://blog.example.com/ownership"
+    );
+
+    links.remove(0);
+    assert!(index.update(&links, &archive));
+    assert!(search.run(&index, &links).is_empty());
+    assert_eq!(index, indexed(&links, &archive));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn the_index_is_saved_next_to_the_store() {
+    let directory = temp_dir("search-keeper");
+    let keeper = LinkKeeperBuilder::new()
+        .config_path(directory.join("link-keeper.toml"))
+        .store(directory.join("links.json"))
+        .build()
+        .unwrap();
+
+    keeper
+        .add("https://example.com/ownership", None, &[])
+        .unwrap();
+
+    let search = Search::parse("ownership", Query::new()).unwrap();
+    let results = keeper.search(&search).unwrap();
+    assert_eq!(results.len(), 1);
+
+    let index_path = directory.join("link_keeper_index.json");
+    let saved = Index::load(&index_path);
+    assert_ne!(saved, Index::default());
+
+    keeper
+        .add("https://example.com/borrowing", None, &[])
+        .unwrap();
+    assert_eq!(keeper.search(&search).unwrap().len(), 1);
+    assert_ne!(Index::load(&index_path), saved);
+    assert!(!directory.join(".link_keeper_index.json.tmp").exists());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,123 @@
+mod fixture;
+
+use fixture::temp_dir;
+use link_keeper::suggest::{self, SuggestionRules};
+use link_keeper::{Link, LinkKeeperBuilder};
+use std::fs;
+
+fn link(url: &str, category: Option<&str>, tags: &[&str]) -> Link {
+    Link::new(url, category).with_tags(tags.iter().map(|tag| (*tag).to_owned()).collect())
+}
+
+fn history() -> Vec<Link> {
+    vec![
+        link(
+            "https://blog.rust-lang.org/a",
+            Some("reading"),
+            &["lang/rust"],
+        ),
+        link(
+            "https://blog.rust-lang.org/b",
+            Some("reading"),
+            &["lang/rust", "release"],
+        ),
+        link("https://blog.rust-lang.org/c", Some("news"), &["lang/rust"]),
+        link("https://example.com/async", None, &["async", "lang/rust"]),
+        link("https://go.dev/blog", None, &["lang/go"]),
+    ]
+}
+
+#[test]
+fn tags_used_on_the_domain_are_suggested_first() {
+    let suggestions = suggest::suggest(
+        &Link::new("https://blog.rust-lang.org/d", None),
+        &history(),
+        &SuggestionRules::default(),
+    );
+
+    assert_eq!(suggestions.tags[0], "lang/rust");
+    assert!(suggestions.tags.contains(&"release".to_owned()));
+    assert!(!suggestions.tags.contains(&"lang/go".to_owned()));
+    assert_eq!(suggestions.category.as_deref(), Some("reading"));
+}
+
+#[test]
+fn tags_named_like_words_of_the_link_are_suggested() {
+    let suggestions = suggest::suggest(
+        &Link::new("https://other.example.org/release/notes", None)
+            .with_title(Some("Async in practice")),
+        &history(),
+        &SuggestionRules::default(),
+    );
+
+    assert!(suggestions.tags.contains(&"release".to_owned()));
+    assert!(suggestions.tags.contains(&"async".to_owned()));
+    assert!(!suggestions.tags.contains(&"lang/go".to_owned()));
+    // Used together with `async`
+    assert!(suggestions.tags.contains(&"lang/rust".to_owned()));
+    assert_eq!(suggestions.category, None);
+}
+
+#[test]
+fn domain_rules_apply_to_subdomains() {
+    let mut rules = SuggestionRules::default();
+    rules
+        .domains
+        .insert("www.arxiv.org".to_owned(), vec!["#paper".to_owned()]);
+
+    let on_subdomain = suggest::suggest(
+        &Link::new("https://export.arxiv.org/abs/1", None),
+        &[],
+        &rules,
+    );
+    assert_eq!(on_subdomain.tags, vec!["paper"]);
+
+    let elsewhere = suggest::suggest(&Link::new("https://notarxiv.org/abs/1", None), &[], &rules);
+    assert!(elsewhere.tags.is_empty());
+}
+
+#[test]
+fn tags_the_link_has_arent_suggested() {
+    let suggestions = suggest::suggest(
+        &link("https://blog.rust-lang.org/d", None, &["lang/rust"]),
+        &history(),
+        &SuggestionRules::default(),
+    );
+
+    assert!(!suggestions.tags.contains(&"lang/rust".to_owned()));
+    assert!(suggestions.tags.contains(&"release".to_owned()));
+}
+
+#[test]
+fn at_most_eight_tags_are_suggested() {
+    let tags = (0..12)
+        .map(|number| format!("tag{}", number))
+        .collect::<Vec<_>>();
+    let history = vec![Link::new("https://example.com/a", None).with_tags(tags)];
+
+    let suggestions = suggest::suggest(
+        &Link::new("https://example.com/b", None),
+        &history,
+        &SuggestionRules::default(),
+    );
+
+    assert_eq!(suggestions.tags.len(), 8);
+}
+
+#[test]
+fn links_are_added_when_suggestions_cant_be_made() {
+    let directory = temp_dir("suggest-invalid");
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml("[suggestions]\ndomains = \"oops\"\n")
+        .store(directory.join("links.json"))
+        .build()
+        .unwrap();
+
+    let link = Link::new("https://example.com", None);
+    assert!(keeper.suggest(&link).is_err());
+
+    keeper.add("https://example.com", None, &[]).unwrap();
+    assert_eq!(keeper.get_links().unwrap().len(), 1);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,229 @@
+mod fixture;
+
+use fixture::temp_dir;
+use link_keeper::backend::{AccessToken, Backend, BackendFactory, BackendRegistry, ConfigField};
+use link_keeper::tags;
+use link_keeper::{Link, LinkKeeper, LinkKeeperBuilder};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Fails to update links with the tag it's configured with
+#[derive(Debug)]
+struct Failing {
+    tag: String,
+}
+
+impl fmt::Display for Failing {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("Failing")
+    }
+}
+
+impl Backend for Failing {
+    fn add(&self, _link_keeper: &mut LinkKeeper) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn sign_in(&self, _access_token: &AccessToken) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn sign_out(&self, _access_token: &AccessToken) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn add_link(&self, _link: &Link) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    fn update_link(&self, link: &Link) -> Result<(), failure::Error> {
+        if link.tags().contains(&self.tag) {
+            Err(failure::format_err!("Can't update {}", link.link()))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn get_toml_config(&self) -> Result<String, toml::ser::Error> {
+        Ok(format!("tag = {:?}\n", self.tag))
+    }
+}
+
+#[derive(Debug)]
+struct FailingFactory;
+
+impl BackendFactory for FailingFactory {
+    fn name(&self) -> &'static str {
+        "failing"
+    }
+
+    fn config_schema(&self) -> Vec<ConfigField> {
+        vec![]
+    }
+
+    fn build(&self, config: &toml::Value) -> Result<Box<dyn Backend>, failure::Error> {
+        Ok(Box::new(Failing {
+            tag: config["tag"].as_str().unwrap_or_default().to_owned(),
+        }))
+    }
+}
+
+/// A keeper with a link for each of the lists of tags
+fn keeper_with<'a>(directory: &Path, config: &str, tagged: &[&[&str]]) -> LinkKeeper<'a> {
+    let mut registry = BackendRegistry::new();
+    registry.register(Box::new(FailingFactory));
+
+    let keeper = LinkKeeperBuilder::new()
+        .config_toml(config)
+        .store(directory.join("links.json"))
+        .backends(registry)
+        .build()
+        .unwrap();
+
+    for (number, tags) in tagged.iter().enumerate() {
+        let tags = tags.iter().map(|tag| (*tag).to_owned()).collect::<Vec<_>>();
+
+        keeper
+            .add(&format!("https://example.com/{}", number), None, &tags)
+            .unwrap();
+    }
+
+    keeper
+}
+
+fn tags_of(keeper: &LinkKeeper) -> Vec<Vec<String>> {
+    keeper
+        .get_links()
+        .unwrap()
+        .iter()
+        .map(|link| link.tags().to_vec())
+        .collect()
+}
+
+#[test]
+fn nested_tags_are_within_their_parents() {
+    assert!(tags::is_within("lang", "lang"));
+    assert!(tags::is_within("lang/rust", "lang"));
+    assert!(tags::is_within("lang/rust/async", "lang"));
+    assert!(!tags::is_within("language", "lang"));
+    assert!(!tags::is_within("lang", "lang/rust"));
+
+    assert_eq!(tags::with_parents("a/b/c"), vec!["a", "a/b", "a/b/c"]);
+    assert_eq!(tags::normalize(" #lang / rust/ "), "lang/rust");
+}
+
+#[test]
+fn renaming_moves_the_nested_tags() {
+    assert_eq!(
+        tags::rename("lang/rust", "lang", "code/lang").as_deref(),
+        Some("code/lang/rust")
+    );
+    assert_eq!(
+        tags::rename("lang", "lang", "code/lang").as_deref(),
+        Some("code/lang")
+    );
+    assert_eq!(tags::rename("language", "lang", "code/lang"), None);
+}
+
+#[test]
+fn stored_tags_are_renamed_with_their_children() {
+    let directory = temp_dir("tags-rename");
+    let keeper = keeper_with(
+        &directory,
+        "",
+        &[&["lang"], &["lang/rust", "web"], &["language"], &[]],
+    );
+
+    let report = keeper.rename_tag("lang", "code/lang").unwrap();
+
+    assert_eq!(report.updated, 2);
+    assert!(report.failed.is_empty());
+    assert_eq!(
+        tags_of(&keeper),
+        vec![
+            vec!["code/lang"],
+            vec!["code/lang/rust", "web"],
+            vec!["language"],
+            vec![],
+        ]
+    );
+    assert_eq!(
+        keeper.tag_counts().unwrap(),
+        vec![
+            ("code".to_owned(), 2),
+            ("code/lang".to_owned(), 2),
+            ("code/lang/rust".to_owned(), 1),
+            ("language".to_owned(), 1),
+            ("web".to_owned(), 1),
+        ]
+    );
+
+    assert!(keeper.rename_tag("lang", " / ").is_err());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn tags_are_merged_into_an_existing_tag() {
+    let directory = temp_dir("tags-merge");
+    let keeper = keeper_with(
+        &directory,
+        "",
+        &[&["rust", "rustlang"], &["rustlang/async"], &["go"]],
+    );
+
+    let report = keeper.merge_tags(&["rustlang", "go"], "rust").unwrap();
+
+    assert_eq!(report.updated, 3);
+    assert_eq!(
+        tags_of(&keeper),
+        vec![vec!["rust"], vec!["rust/async"], vec!["rust"]]
+    );
+
+    let report = keeper.merge_tags(&["missing"], "rust").unwrap();
+    assert_eq!(report.updated, 0);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn deleting_a_tag_removes_its_children() {
+    let directory = temp_dir("tags-delete");
+    let keeper = keeper_with(&directory, "", &[&["lang/rust", "web"], &["language"]]);
+
+    let report = keeper.delete_tag("#lang").unwrap();
+
+    assert_eq!(report.updated, 1);
+    assert_eq!(tags_of(&keeper), vec![vec!["web"], vec!["language"]]);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn links_backends_fail_to_update_are_reported() {
+    let directory = temp_dir("tags-failed");
+    let keeper = keeper_with(
+        &directory,
+        "[backends.failing]\ntag = \"code/lang\"\n",
+        &[&["lang"], &["lang/rust"]],
+    );
+
+    let report = keeper.rename_tag("lang", "code/lang").unwrap();
+
+    // The store is changed anyway
+    assert_eq!(report.updated, 2);
+    assert_eq!(
+        tags_of(&keeper),
+        vec![vec!["code/lang"], vec!["code/lang/rust"]]
+    );
+    assert_eq!(
+        report.failed,
+        vec![(
+            "https://example.com/0".to_owned(),
+            "Can't update https://example.com/0".to_owned()
+        )]
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}